@@ -0,0 +1,30 @@
+-- Per-category variables (for example "difficulty" or "glitched") which split
+-- a category's leaderboard into one board per combination of their values
+
+CREATE TABLE variables (
+    id BIGSERIAL PRIMARY KEY,
+    game BIGINT NOT NULL REFERENCES games(id) ON DELETE CASCADE,
+    category BIGINT NOT NULL REFERENCES categories(id) ON DELETE CASCADE,
+    name VARCHAR(128) NOT NULL,
+    flags BIGINT NOT NULL DEFAULT 0
+);
+
+CREATE INDEX variables_category_index ON variables USING HASH (category);
+
+CREATE TABLE variable_values (
+    id BIGSERIAL PRIMARY KEY,
+    variable BIGINT NOT NULL REFERENCES variables(id) ON DELETE CASCADE,
+    name VARCHAR(128) NOT NULL,
+    flags BIGINT NOT NULL DEFAULT 0
+);
+
+CREATE INDEX variable_values_variable_index ON variable_values USING HASH (variable);
+
+CREATE TABLE run_variables (
+    run BIGINT NOT NULL REFERENCES runs(id) ON DELETE CASCADE,
+    variable BIGINT NOT NULL REFERENCES variables(id) ON DELETE CASCADE,
+    value BIGINT NOT NULL REFERENCES variable_values(id) ON DELETE CASCADE,
+    PRIMARY KEY (run, variable)
+);
+
+CREATE INDEX run_variables_value_index ON run_variables USING HASH (value);
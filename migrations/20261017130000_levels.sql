@@ -0,0 +1,17 @@
+-- Levels of a game, for individual-level leaderboards. Categories marked
+-- per_level have one board per level, and their runs record the level.
+
+CREATE TABLE levels (
+    id BIGSERIAL PRIMARY KEY,
+    game BIGINT NOT NULL REFERENCES games(id) ON DELETE CASCADE,
+    name VARCHAR(128) NOT NULL,
+    flags BIGINT NOT NULL DEFAULT 0
+);
+
+CREATE INDEX levels_game_index ON levels USING HASH (game);
+
+ALTER TABLE categories ADD COLUMN per_level BOOL NOT NULL DEFAULT false;
+
+ALTER TABLE runs ADD COLUMN level BIGINT REFERENCES levels(id) ON DELETE CASCADE;
+
+CREATE INDEX runs_level_index ON runs USING HASH (level);
@@ -0,0 +1,13 @@
+-- Categories can accept several timing methods, and rank by one of them.
+-- timing_methods is a bitset of TimingMethods, and primary_timing is
+-- 0 for real time, 1 for in-game time and 2 for load-removed time.
+-- runs.time keeps holding real time, and like it, 0 means "not recorded".
+
+ALTER TABLE categories ADD COLUMN timing_methods SMALLINT NOT NULL DEFAULT 1;
+ALTER TABLE categories ADD COLUMN primary_timing SMALLINT NOT NULL DEFAULT 0;
+
+ALTER TABLE runs ADD COLUMN igt BIGINT NOT NULL DEFAULT 0;
+ALTER TABLE runs ADD COLUMN lrt BIGINT NOT NULL DEFAULT 0;
+
+CREATE INDEX runs_igt_index ON runs (igt);
+CREATE INDEX runs_lrt_index ON runs (lrt);
@@ -0,0 +1,25 @@
+-- Verified runs which were beaten by another verified run from the same
+-- runner, on the same board, are obsoleted and hidden from the leaderboard
+
+ALTER TABLE runs ADD COLUMN obsoleted BOOL NOT NULL DEFAULT false;
+
+UPDATE runs SET obsoleted = true
+WHERE runs.status = 1 AND runs.id NOT IN (
+    SELECT DISTINCT ON (
+        pb.category, pb.submitter, pb.level,
+        ARRAY(SELECT value FROM run_variables WHERE run = pb.id ORDER BY variable)
+    ) pb.id
+    FROM runs AS pb
+    JOIN categories ON pb.category = categories.id
+    WHERE pb.status = 1
+    ORDER BY
+        pb.category, pb.submitter, pb.level,
+        ARRAY(SELECT value FROM run_variables WHERE run = pb.id ORDER BY variable),
+        CASE
+            WHEN categories.scoreboard THEN -pb.score
+            WHEN categories.primary_timing = 1 THEN pb.igt
+            WHEN categories.primary_timing = 2 THEN pb.lrt
+            ELSE pb.time
+        END ASC,
+        pb.created_at ASC, pb.id ASC
+);
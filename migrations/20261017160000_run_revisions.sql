@@ -0,0 +1,19 @@
+-- Every edit to a run stores the version it replaced here
+
+CREATE TABLE run_revisions (
+    id BIGSERIAL PRIMARY KEY,
+    run BIGINT NOT NULL REFERENCES runs(id) ON DELETE CASCADE,
+    editor BIGINT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    video VARCHAR(256) NOT NULL,
+    description VARCHAR(4000) NOT NULL,
+    score BIGINT NOT NULL,
+    time BIGINT NOT NULL,
+    igt BIGINT NOT NULL,
+    lrt BIGINT NOT NULL,
+    level BIGINT REFERENCES levels(id) ON DELETE SET NULL,
+    variables BIGINT[] NOT NULL,
+    status SMALLINT NOT NULL,
+    edited_at TIMESTAMP NOT NULL
+);
+
+CREATE INDEX run_revisions_run_index ON run_revisions USING HASH (run);
@@ -0,0 +1,14 @@
+-- Verifiers can explain why a run was verified or rejected. Each game
+-- keeps a list of canned reasons to pick from, and the final note is
+-- stored on the run itself so it survives the canned reason being deleted.
+
+CREATE TABLE canned_reasons (
+    id BIGSERIAL PRIMARY KEY,
+    game BIGINT NOT NULL REFERENCES games(id) ON DELETE CASCADE,
+    reason VARCHAR(512) NOT NULL,
+    flags BIGINT NOT NULL DEFAULT 0
+);
+
+CREATE INDEX canned_reasons_game_index ON canned_reasons USING HASH (game);
+
+ALTER TABLE runs ADD COLUMN review_note TEXT;
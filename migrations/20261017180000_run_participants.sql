@@ -0,0 +1,17 @@
+-- Co-op runs. A category declares how many players its runs have, and every
+-- runner besides the submitter is recorded here, either as a registered user
+-- or as a guest name for runners without an account.
+
+ALTER TABLE categories ADD COLUMN players SMALLINT NOT NULL DEFAULT 1
+    CHECK (players >= 1);
+
+CREATE TABLE run_participants (
+    run BIGINT NOT NULL REFERENCES runs(id) ON DELETE CASCADE,
+    position SMALLINT NOT NULL,
+    user_id BIGINT REFERENCES users(id) ON DELETE CASCADE,
+    guest_name VARCHAR(64),
+    PRIMARY KEY (run, position),
+    CHECK ((user_id IS NULL) <> (guest_name IS NULL))
+);
+
+CREATE INDEX run_participants_user_index ON run_participants USING HASH (user_id);
@@ -0,0 +1,17 @@
+-- LiveSplit splits attached to runs. The original .lss file lives in the
+-- bucket, and the segments parsed from it are kept here for display.
+
+CREATE TABLE run_splits (
+    run BIGINT PRIMARY KEY REFERENCES runs(id) ON DELETE CASCADE,
+    comparisons VARCHAR(128)[] NOT NULL
+);
+
+CREATE TABLE run_segments (
+    run BIGINT NOT NULL REFERENCES run_splits(run) ON DELETE CASCADE,
+    position SMALLINT NOT NULL,
+    name VARCHAR(256) NOT NULL,
+    split_time BIGINT NOT NULL,
+    best_segment BIGINT NOT NULL,
+    comparison_times BIGINT[] NOT NULL,
+    PRIMARY KEY (run, position)
+);
@@ -0,0 +1,17 @@
+-- Comment threads under runs. Private comments are only shown to the
+-- submitter and the game's team.
+
+CREATE TABLE run_comments (
+    id BIGSERIAL PRIMARY KEY,
+    parent BIGINT NOT NULL REFERENCES runs(id) ON DELETE CASCADE,
+    game BIGINT NOT NULL REFERENCES games(id) ON DELETE CASCADE,
+    author BIGINT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    created_at TIMESTAMP NOT NULL,
+    edited_at TIMESTAMP,
+    content VARCHAR(4000) NOT NULL,
+    private BOOLEAN NOT NULL DEFAULT false,
+    flags BIGINT NOT NULL DEFAULT 0
+);
+
+CREATE INDEX run_comment_parent_index ON run_comments USING HASH (parent);
+CREATE INDEX run_comment_author_index ON run_comments USING HASH (author);
@@ -0,0 +1,15 @@
+-- Platforms a game can be run on, and whether a run was on an emulator.
+
+CREATE TABLE platforms (
+    id BIGSERIAL PRIMARY KEY,
+    game BIGINT NOT NULL REFERENCES games(id) ON DELETE CASCADE,
+    name VARCHAR(128) NOT NULL,
+    flags BIGINT NOT NULL DEFAULT 0
+);
+
+CREATE INDEX platforms_game_index ON platforms USING HASH (game);
+
+ALTER TABLE runs ADD COLUMN platform BIGINT REFERENCES platforms(id) ON DELETE SET NULL;
+ALTER TABLE runs ADD COLUMN emulated BOOL NOT NULL DEFAULT false;
+
+CREATE INDEX runs_platform_index ON runs USING HASH (platform);
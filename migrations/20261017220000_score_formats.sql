@@ -0,0 +1,8 @@
+-- Scoreboards can rank lower scores first, and show their scores with decimal
+-- places and a unit. Scores are still stored as whole numbers, scaled up by
+-- the category's precision.
+
+ALTER TABLE categories ADD COLUMN lower_score_wins BOOL NOT NULL DEFAULT false;
+ALTER TABLE categories ADD COLUMN score_precision SMALLINT NOT NULL DEFAULT 0
+    CHECK (score_precision BETWEEN 0 AND 6);
+ALTER TABLE categories ADD COLUMN score_unit VARCHAR(32) NOT NULL DEFAULT '';
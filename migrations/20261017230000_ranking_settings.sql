@@ -0,0 +1,9 @@
+-- How a game's overall ranking awards points for placements on its leaderboards.
+-- Games without a row here use the defaults.
+
+CREATE TABLE ranking_settings (
+    game BIGINT PRIMARY KEY REFERENCES games(id) ON DELETE CASCADE,
+    formula SMALLINT NOT NULL DEFAULT 0,
+    first_place_points BIGINT NOT NULL DEFAULT 100
+        CHECK (first_place_points > 0)
+);
@@ -0,0 +1,13 @@
+-- Run search pages through results with keyset pagination, comparing against
+-- the (sort key, id) of the last run on the previous page, so every way to
+-- narrow down and sort runs gets a btree index ending in the run id.
+
+CREATE INDEX runs_created_at_index ON runs (created_at, id);
+CREATE INDEX runs_game_created_at_index ON runs (game, created_at, id);
+CREATE INDEX runs_category_created_at_index ON runs (category, created_at, id);
+CREATE INDEX runs_submitter_created_at_index ON runs (submitter, created_at, id);
+CREATE INDEX runs_status_created_at_index ON runs (status, created_at, id);
+CREATE INDEX runs_category_score_sort_index ON runs (category, score, id);
+CREATE INDEX runs_category_time_sort_index ON runs (category, time, id);
+CREATE INDEX runs_category_igt_sort_index ON runs (category, igt, id);
+CREATE INDEX runs_category_lrt_sort_index ON runs (category, lrt, id);
@@ -0,0 +1,3 @@
+ALTER TABLE users ADD COLUMN email_verified BOOL NOT NULL DEFAULT false;
+-- accounts made before addresses were verified keep working
+UPDATE users SET email_verified = true;
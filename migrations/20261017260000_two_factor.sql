@@ -0,0 +1,16 @@
+-- Authenticator app secrets for two-factor authentication.
+-- Users without a row here log in with just their password.
+
+CREATE TABLE user_totp (
+    user_id BIGINT PRIMARY KEY REFERENCES users(id) ON DELETE CASCADE,
+    secret BYTEA NOT NULL,
+    -- the newest time step a code was accepted for, so codes can't be used twice
+    last_step BIGINT NOT NULL DEFAULT 0
+);
+
+-- Single-use codes for logging in without the authenticator app
+CREATE TABLE totp_recovery_codes (
+    user_id BIGINT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    code_hash TEXT NOT NULL,
+    PRIMARY KEY (user_id, code_hash)
+);
@@ -0,0 +1,17 @@
+-- Accounts on other sites which users can log in with, through OpenID Connect or OAuth2
+
+CREATE TABLE linked_accounts (
+    user_id BIGINT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    -- the provider's name in OAUTH_PROVIDERS
+    provider TEXT NOT NULL,
+    -- the provider's id for the account, which doesn't change like names do
+    subject TEXT NOT NULL,
+    -- what the provider calls the account, to show in settings
+    display_name TEXT,
+    created_at TIMESTAMP NOT NULL,
+    PRIMARY KEY (provider, subject),
+    UNIQUE (user_id, provider)
+);
+
+-- Accounts made by logging in with another site don't have a password until they set one
+ALTER TABLE users ALTER COLUMN password DROP NOT NULL;
@@ -0,0 +1,7 @@
+-- Editing a run can also change where it was done and who it was done with,
+-- so revisions keep those too. Participants are stored in position order.
+
+ALTER TABLE run_revisions ADD COLUMN platform BIGINT REFERENCES platforms(id) ON DELETE SET NULL;
+ALTER TABLE run_revisions ADD COLUMN emulated BOOL NOT NULL DEFAULT false;
+ALTER TABLE run_revisions ADD COLUMN participant_users BIGINT[] NOT NULL DEFAULT '{}';
+ALTER TABLE run_revisions ADD COLUMN participant_guests VARCHAR(64)[] NOT NULL DEFAULT '{}';
@@ -0,0 +1,61 @@
+-- Boards always filter on a value of every variable, so runs submitted before a
+-- variable had values are given its first one, and values runs were submitted
+-- with can't be deleted out from under them.
+
+INSERT INTO run_variables (run, variable, value)
+SELECT runs.id, variables.id, (
+    SELECT MIN(variable_values.id) FROM variable_values
+    WHERE variable_values.variable = variables.id
+)
+FROM runs JOIN variables ON variables.category = runs.category
+WHERE EXISTS (SELECT 1 FROM variable_values WHERE variable_values.variable = variables.id)
+AND NOT EXISTS (
+    SELECT 1 FROM run_variables
+    WHERE run_variables.run = runs.id AND run_variables.variable = variables.id
+);
+
+ALTER TABLE run_variables DROP CONSTRAINT run_variables_value_fkey;
+ALTER TABLE run_variables ADD CONSTRAINT run_variables_value_fkey
+    FOREIGN KEY (value) REFERENCES variable_values(id);
+
+-- the filled in runs now share boards with runs that picked the first value
+UPDATE runs SET obsoleted = (
+    runs.status = 1 AND runs.id NOT IN (
+        SELECT DISTINCT ON (pb.category, team, pb.level, variables) pb.id
+        FROM runs AS pb
+        JOIN categories ON pb.category = categories.id
+        CROSS JOIN LATERAL (
+            SELECT ARRAY(
+                SELECT runner FROM (
+                    SELECT pb.submitter::TEXT AS runner
+                    UNION ALL
+                    SELECT COALESCE(
+                        run_participants.user_id::TEXT,
+                        '~' || run_participants.guest_name
+                    )
+                    FROM run_participants WHERE run_participants.run = pb.id
+                ) AS runners ORDER BY runner
+            ) AS team,
+            ARRAY(
+                SELECT value FROM run_variables WHERE run = pb.id ORDER BY variable
+            ) AS variables
+        ) AS board
+        WHERE pb.status = 1
+        AND CASE
+            WHEN categories.scoreboard THEN TRUE
+            WHEN categories.primary_timing = 1 THEN pb.igt <> 0
+            WHEN categories.primary_timing = 2 THEN pb.lrt <> 0
+            ELSE pb.time <> 0
+        END
+        ORDER BY
+            pb.category, team, pb.level, variables,
+            CASE
+                WHEN categories.scoreboard AND categories.lower_score_wins THEN pb.score
+                WHEN categories.scoreboard THEN -pb.score
+                WHEN categories.primary_timing = 1 THEN pb.igt
+                WHEN categories.primary_timing = 2 THEN pb.lrt
+                ELSE pb.time
+            END ASC,
+            pb.created_at ASC, pb.id ASC
+    )
+);
@@ -0,0 +1,7 @@
+-- Deleting a level used to delete every run on it. Levels with runs can't be
+-- deleted anymore. The check waits for the end of the statement (unlike
+-- RESTRICT), so deleting a whole game still takes its levels and runs with it.
+
+ALTER TABLE runs DROP CONSTRAINT runs_level_fkey;
+ALTER TABLE runs ADD CONSTRAINT runs_level_fkey
+    FOREIGN KEY (level) REFERENCES levels(id) ON DELETE NO ACTION;
@@ -31,6 +31,8 @@ pub enum Error {
     S3(#[from] s3::error::S3Error),
     #[error("multipart upload error: {0}")]
     Multipart(#[from] axum_extra::extract::multipart::MultipartError),
+    #[error("multipart form invalid: {0}")]
+    MultipartRejection(#[from] axum_extra::extract::multipart::MultipartRejection),
     #[error("dependency tokio task panicked: {0}")]
     TaskJoin(#[from] tokio::task::JoinError),
     #[error("integer out of range (this is a bug): {0}")]
@@ -46,13 +48,23 @@ pub enum Error {
     #[error("Failed to validate submission: \n{0}")]
     MultiFormValidation(#[from] garde::Report),
     #[error("Form data invalid: {0}")]
-    FormRejection(#[from] axum::extract::rejection::FormRejection),
+    FormRejection(#[from] axum_extra::extract::FormRejection),
     #[error("Failed to validate submission: {0}")]
     CustomFormValidation(String),
     #[error("Failed to parse URL: {0}")]
     UrlParse(#[from] url::ParseError),
     #[error("Failed to parse or render image: {0}")]
     Image(#[from] image::ImageError),
+    #[error("Failed to read splits file: {0}")]
+    Splits(#[from] quick_xml::DeError),
+    #[error("Invalid email address: {0}")]
+    MailAddress(#[from] lettre::address::AddressError),
+    #[error("Failed to build email: {0}")]
+    MailBuild(#[from] lettre::error::Error),
+    #[error("Failed to send email: {0}")]
+    Smtp(#[from] lettre::transport::smtp::Error),
+    #[error("SMTP_URL is required when MAIL_TRANSPORT is smtp")]
+    MissingSmtpUrl,
     #[error("URL missing query pair {0}")]
     MissingQueryPair(&'static str),
     #[error("This endpoint needs authorization")]
@@ -81,6 +93,54 @@ pub enum Error {
         "You can't delete the default category for a game, change the default category first!!"
     )]
     CannotDeleteDefaultCategory,
+    #[error("That variable value isn't part of this category!")]
+    InvalidVariableValue,
+    #[error("Runs were submitted with that variable value, so it can't be deleted!")]
+    VariableValueInUse,
+    #[error("That level isn't part of that game!")]
+    InvalidGameLevelPair,
+    #[error("Runs in a per-level category must have a level!")]
+    MissingLevel,
+    #[error("Runs were submitted on that level, so it can't be deleted!")]
+    LevelInUse,
+    #[error("Some runs of this category have no level, so it can't have a board per level!")]
+    LevellessRuns,
+    #[error("That platform isn't part of that game!")]
+    InvalidGamePlatformPair,
+    #[error("Runs of a game with platforms must have a platform!")]
+    MissingPlatform,
+    #[error("That canned reason isn't part of that game!")]
+    InvalidGameCannedReasonPair,
+    #[error("{0} is already reviewing this run!")]
+    RunClaimed(String),
+    #[error("This run has already been reviewed!")]
+    RunAlreadyReviewed,
+    #[error("Verify your email address before submitting runs!")]
+    EmailNotVerified,
+    #[error("This verification link is invalid, expired, or was already used!")]
+    InvalidVerificationLink,
+    #[error("This password reset link is invalid, expired, or was already used!")]
+    InvalidResetLink,
+    #[error("That two-factor authentication code is incorrect or has expired!")]
+    InvalidTwoFactorCode,
+    #[error("Enable two-factor authentication on your own account first!")]
+    TwoFactorRequired,
+    #[error("Too many incorrect two-factor authentication codes, try again later!")]
+    TwoFactorLockedOut,
+    #[error("This login has expired or was already used, try logging in again!")]
+    InvalidOAuthState,
+    #[error("The identity provider didn't log you in: {0}")]
+    OAuthDenied(String),
+    #[error("The identity provider didn't say what your account's {0} is")]
+    OAuthMissingField(String),
+    #[error("The identity provider didn't share your email address, which new accounts need!")]
+    OAuthNoEmail,
+    #[error("That account is already linked to another user!")]
+    OAuthAccountInUse,
+    #[error("An account already uses that email address. Log in to it, then link this one from your settings!")]
+    OAuthEmailInUse,
+    #[error("Set a password with \"forgot password\" before unlinking your only linked account!")]
+    LastLoginMethod,
     #[error("URL being parsed does not have a domain!")]
     NoDomainInUrl,
     #[error("Translation file did not have stem!")]
@@ -182,10 +242,14 @@ impl Error {
             | Error::Impossible(_)
             | Error::TaskJoin(_)
             | Error::Io(_)
+            | Error::MailBuild(_)
+            | Error::Smtp(_)
+            | Error::MissingSmtpUrl
             | Error::Format(_)
             | Error::TryFromInt(_)
             | Error::UrlParse(_)
             | Error::MissingQueryPair(_)
+            | Error::OAuthMissingField(_)
             | Error::TooManyRows(_, _)
             | Error::RowDoesNotMatchInputGame
             | Error::NoDomainInUrl
@@ -197,15 +261,41 @@ impl Error {
             | Error::FormRejection(_)
             | Error::MultiFormValidation(_)
             | Error::Multipart(_)
+            | Error::MultipartRejection(_)
             | Error::Image(_)
+            | Error::Splits(_)
+            | Error::MailAddress(_)
+            | Error::InvalidVerificationLink
+            | Error::InvalidResetLink
+            | Error::InvalidTwoFactorCode
+            | Error::TwoFactorRequired
+            | Error::InvalidOAuthState
+            | Error::OAuthDenied(_)
+            | Error::OAuthNoEmail
+            | Error::OAuthAccountInUse
+            | Error::OAuthEmailInUse
+            | Error::LastLoginMethod
             | Error::ImageTooTall(_)
             | Error::ImageTooWide(_)
             | Error::NeedsLogin(_)
             | Error::TokenHasIdButIdIsUnkown
             | Error::InvalidGameCategoryPair
-            | Error::CannotDeleteDefaultCategory => StatusCode::BAD_REQUEST,
-            Error::InvalidPassword | Error::InsufficientPermissions => StatusCode::UNAUTHORIZED,
+            | Error::CannotDeleteDefaultCategory
+            | Error::InvalidVariableValue
+            | Error::VariableValueInUse
+            | Error::InvalidGameLevelPair
+            | Error::MissingLevel
+            | Error::LevelInUse
+            | Error::LevellessRuns
+            | Error::InvalidGamePlatformPair
+            | Error::MissingPlatform
+            | Error::InvalidGameCannedReasonPair => StatusCode::BAD_REQUEST,
+            Error::InvalidPassword | Error::InsufficientPermissions | Error::EmailNotVerified => {
+                StatusCode::UNAUTHORIZED
+            }
             Error::NotFound => StatusCode::NOT_FOUND,
+            Error::RunClaimed(_) | Error::RunAlreadyReviewed => StatusCode::CONFLICT,
+            Error::TwoFactorLockedOut => StatusCode::TOO_MANY_REQUESTS,
         }
     }
 }
@@ -128,3 +128,31 @@ impl IdMarker for ForumPostMarker {}
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub struct ForumCommentMarker;
 impl IdMarker for ForumCommentMarker {}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct VariableMarker;
+impl IdMarker for VariableMarker {}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct VariableValueMarker;
+impl IdMarker for VariableValueMarker {}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct LevelMarker;
+impl IdMarker for LevelMarker {}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct RunRevisionMarker;
+impl IdMarker for RunRevisionMarker {}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct CannedReasonMarker;
+impl IdMarker for CannedReasonMarker {}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct RunCommentMarker;
+impl IdMarker for RunCommentMarker {}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct PlatformMarker;
+impl IdMarker for PlatformMarker {}
@@ -0,0 +1,92 @@
+use std::path::PathBuf;
+
+use lettre::{
+    message::{header::ContentType, Mailbox},
+    AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor,
+};
+use rand::distributions::{Alphanumeric, DistString};
+
+use crate::{config::Config, AppState, Error};
+
+/// How outgoing mail is delivered
+#[derive(serde::Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum MailTransportKind {
+    Smtp,
+    File,
+    #[default]
+    Stdout,
+}
+
+pub enum Mailer {
+    Smtp(AsyncSmtpTransport<Tokio1Executor>),
+    /// Writes each message to its own `.eml` file in this directory
+    File(PathBuf),
+    /// Logs each message, for local development
+    Stdout,
+}
+
+impl Mailer {
+    pub fn from_config(config: &Config) -> Result<Self, Error> {
+        let mailer = match config.mail_transport {
+            MailTransportKind::Smtp => {
+                let url = config.smtp_url.as_deref().ok_or(Error::MissingSmtpUrl)?;
+                Self::Smtp(AsyncSmtpTransport::<Tokio1Executor>::from_url(url)?.build())
+            }
+            MailTransportKind::File => {
+                let dir = PathBuf::from(&config.mail_dir);
+                std::fs::create_dir_all(&dir)?;
+                Self::File(dir)
+            }
+            MailTransportKind::Stdout => Self::Stdout,
+        };
+        Ok(mailer)
+    }
+
+    /// Send a plain text message from the configured sender
+    pub async fn send(
+        &self,
+        config: &Config,
+        to: &str,
+        subject: &str,
+        body: String,
+    ) -> Result<(), Error> {
+        let message = Message::builder()
+            .from(config.mail_from.parse::<Mailbox>()?)
+            .to(to.parse::<Mailbox>()?)
+            .subject(subject)
+            .header(ContentType::TEXT_PLAIN)
+            .body(body)?;
+        match self {
+            Self::Smtp(transport) => {
+                transport.send(message).await?;
+            }
+            Self::File(dir) => {
+                let name = Alphanumeric.sample_string(&mut rand::thread_rng(), 16);
+                tokio::fs::write(dir.join(format!("{name}.eml")), message.formatted()).await?;
+            }
+            Self::Stdout => {
+                info!(
+                    message = %String::from_utf8_lossy(&message.formatted()),
+                    "not sending mail, MAIL_TRANSPORT is stdout"
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Render `template` and mail it to `to`. The first line of the rendered template is the subject.
+pub async fn send_template<T: serde::Serialize>(
+    state: &AppState,
+    to: &str,
+    template: &str,
+    data: T,
+) -> Result<(), Error> {
+    let rendered = state.render(template, data)?.0;
+    let (subject, body) = rendered.split_once('\n').unwrap_or((&rendered, ""));
+    state
+        .mailer
+        .send(&state.config, to, subject.trim(), body.trim().to_string())
+        .await
+}
@@ -2,7 +2,9 @@ mod config;
 mod error;
 mod id;
 mod language;
+mod mail;
 mod model;
+mod oauth;
 mod router;
 mod routes;
 mod state;
@@ -51,7 +53,7 @@ async fn main() {
     info!("Starting server on http://localhost:{}", state.config.port);
     let app = router::build(state);
     let tcp = TcpListener::bind(bind_address).await.unwrap();
-    axum::serve(tcp, app)
+    axum::serve(tcp, app.into_make_service_with_connect_info::<SocketAddr>())
         .with_graceful_shutdown(vss::shutdown_signal())
         .await
         .unwrap();
@@ -0,0 +1,44 @@
+use crate::{
+    id::{CannedReasonMarker, GameMarker, Id},
+    AppState, Error,
+};
+
+/// A reason verifiers of a game can pick from instead of writing one out
+#[derive(serde::Serialize, serde::Deserialize, Debug, Hash, PartialEq, Eq, Clone)]
+pub struct CannedReason {
+    pub id: Id<CannedReasonMarker>,
+    pub game: Id<GameMarker>,
+    pub reason: String,
+    pub flags: i64,
+}
+
+impl CannedReason {
+    pub async fn from_db(
+        state: &AppState,
+        id: Id<CannedReasonMarker>,
+    ) -> Result<CannedReason, Error> {
+        query_as!(
+            CannedReason,
+            "SELECT id, game, reason, flags FROM canned_reasons WHERE id = $1",
+            id.get()
+        )
+        .fetch_optional(&state.postgres)
+        .await?
+        .ok_or(Error::NotFound)
+    }
+
+    /// Fetch every canned reason of a game, ordered by creation.
+    pub async fn from_db_game(
+        state: &AppState,
+        game: Id<GameMarker>,
+    ) -> Result<Vec<CannedReason>, Error> {
+        let reasons = query_as!(
+            CannedReason,
+            "SELECT id, game, reason, flags FROM canned_reasons WHERE game = $1 ORDER BY id",
+            game.get()
+        )
+        .fetch_all(&state.postgres)
+        .await?;
+        Ok(reasons)
+    }
+}
@@ -1,5 +1,8 @@
+use sqlx::PgExecutor;
+
+use super::{SortBy, TimingMethod, TimingMethods};
 use crate::{
-    id::{CategoryMarker, GameMarker, Id},
+    id::{CategoryMarker, GameMarker, Id, UserMarker},
     AppState, Error,
 };
 
@@ -11,6 +14,18 @@ pub struct Category {
     pub description: String,
     pub rules: String,
     pub scoreboard: bool,
+    pub per_level: bool,
+    pub timing_methods: TimingMethods,
+    pub primary_timing: TimingMethod,
+    /// How many runners each run has, more than one for co-op categories
+    pub players: i16,
+    /// Whether lower scores rank higher on a scoreboard
+    pub lower_score_wins: bool,
+    /// How many decimal places scores have. Scores are stored as whole numbers,
+    /// so a score of `1234` with a precision of 2 is shown as `12.34`.
+    pub score_precision: i16,
+    /// Shown after scores, like `points` or `m`
+    pub score_unit: String,
     pub flags: i64,
 }
 
@@ -18,13 +33,152 @@ impl Category {
     pub async fn from_db(state: &AppState, id: Id<CategoryMarker>) -> Result<Category, Error> {
         query_as!(
             Category,
-            "SELECT id, game, name, description, rules, scoreboard, flags FROM categories WHERE id = $1",
+            "SELECT id, game, name, description, rules, scoreboard,
+            per_level, timing_methods, primary_timing, players,
+            lower_score_wins, score_precision, score_unit, flags
+            FROM categories WHERE id = $1",
             id.get()
         )
         .fetch_optional(&state.postgres)
         .await?
         .ok_or(Error::NotFound)
     }
+
+    /// How runs on this category's leaderboards are ranked
+    pub fn sort_by(&self) -> SortBy {
+        if self.scoreboard {
+            SortBy::Score {
+                lower_wins: self.lower_score_wins,
+            }
+        } else {
+            SortBy::Time(self.primary_timing)
+        }
+    }
+
+    /// Per-level boards only show runs on a level, so categories can only be made
+    /// per-level if all of their runs have one
+    pub async fn check_can_be_per_level(
+        executor: impl PgExecutor<'_>,
+        category: Id<CategoryMarker>,
+    ) -> Result<(), Error> {
+        let levelless = query!(
+            r#"SELECT EXISTS(
+                SELECT 1 FROM runs WHERE category = $1 AND level IS NULL
+            ) AS "levelless!""#,
+            category.get()
+        )
+        .fetch_one(executor)
+        .await?
+        .levelless;
+        if levelless {
+            Err(Error::LevellessRuns)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Work out which verified runs in a category have been beaten by another
+    /// verified run from the same runners, on the same level and variable values,
+    /// and mark those as obsoleted. Co-op runs are compared with the other runs of
+    /// the same team, whoever submitted them. Runs without a time by the primary
+    /// timing method are never personal bests. Pass a user to only update the runs
+    /// they took part in.
+    pub async fn update_obsoleted(
+        executor: impl PgExecutor<'_>,
+        category: Id<CategoryMarker>,
+        maybe_runner: Option<Id<UserMarker>>,
+    ) -> Result<(), Error> {
+        // teams are the sorted ids of their runners, with guests named after a ~
+        query!(
+            "WITH personal_bests AS (
+                SELECT DISTINCT ON (team, pb.level, variables) pb.id
+                FROM runs AS pb
+                JOIN categories ON pb.category = categories.id
+                CROSS JOIN LATERAL (
+                    SELECT ARRAY(
+                        SELECT runner FROM (
+                            SELECT pb.submitter::TEXT AS runner
+                            UNION ALL
+                            SELECT COALESCE(
+                                run_participants.user_id::TEXT,
+                                '~' || run_participants.guest_name
+                            )
+                            FROM run_participants WHERE run_participants.run = pb.id
+                        ) AS runners ORDER BY runner
+                    ) AS team,
+                    ARRAY(
+                        SELECT value FROM run_variables WHERE run = pb.id ORDER BY variable
+                    ) AS variables
+                ) AS board
+                WHERE pb.category = $1 AND pb.status = 1
+                AND ($2::BIGINT IS NULL OR pb.submitter = $2 OR EXISTS (
+                    SELECT 1 FROM run_participants
+                    WHERE run_participants.run = pb.id AND run_participants.user_id = $2
+                ))
+                AND CASE
+                    WHEN categories.scoreboard THEN TRUE
+                    WHEN categories.primary_timing = 1 THEN pb.igt <> 0
+                    WHEN categories.primary_timing = 2 THEN pb.lrt <> 0
+                    ELSE pb.time <> 0
+                END
+                ORDER BY
+                    team, pb.level, variables,
+                    CASE
+                        WHEN categories.scoreboard AND categories.lower_score_wins THEN pb.score
+                        WHEN categories.scoreboard THEN -pb.score
+                        WHEN categories.primary_timing = 1 THEN pb.igt
+                        WHEN categories.primary_timing = 2 THEN pb.lrt
+                        ELSE pb.time
+                    END ASC,
+                    pb.created_at ASC, pb.id ASC
+            )
+            UPDATE runs
+            SET obsoleted = (
+                runs.status = 1 AND runs.id NOT IN (SELECT id FROM personal_bests)
+            )
+            WHERE runs.category = $1 AND ($2::BIGINT IS NULL OR runs.submitter = $2 OR EXISTS (
+                SELECT 1 FROM run_participants
+                WHERE run_participants.run = runs.id AND run_participants.user_id = $2
+            ))",
+            category.get(),
+            maybe_runner.map(Id::get)
+        )
+        .execute(executor)
+        .await?;
+        Ok(())
+    }
+}
+
+/// Parse a score typed in by a runner, like `12.5`, into its stored form
+/// for a category with `precision` decimal places
+pub fn parse_score(input: &str, precision: i16) -> Result<i64, Error> {
+    let invalid = || Error::CustomFormValidation(format!("invalid score {input}"));
+    let precision = usize::try_from(precision).map_err(|_| invalid())?;
+    let (whole, fraction) = input.trim().split_once('.').unwrap_or((input.trim(), ""));
+    if fraction.len() > precision {
+        return Err(Error::CustomFormValidation(format!(
+            "score can have at most {precision} decimal places"
+        )));
+    }
+    let all_digits = |part: &str| part.bytes().all(|byte| byte.is_ascii_digit());
+    if whole.is_empty() || !all_digits(whole) || !all_digits(fraction) {
+        return Err(invalid());
+    }
+    format!("{whole}{fraction:0<precision$}")
+        .parse()
+        .map_err(|_| invalid())
+}
+
+/// Format a stored score for a category with `precision` decimal places
+pub fn format_score(score: i64, precision: i16) -> String {
+    let precision = usize::try_from(precision).unwrap_or(0);
+    if precision == 0 {
+        return score.to_string();
+    }
+    let digits = format!("{:0>width$}", score.unsigned_abs(), width = precision + 1);
+    let (whole, fraction) = digits.split_at(digits.len() - precision);
+    let sign = if score < 0 { "-" } else { "" };
+    format!("{sign}{whole}.{fraction}")
 }
 
 #[derive(serde::Serialize, serde::Deserialize, Debug, Encode, Hash, PartialEq, Eq, Clone)]
@@ -33,6 +187,7 @@ pub struct MiniCategory {
     pub id: Id<CategoryMarker>,
     pub game: Id<GameMarker>,
     pub scoreboard: bool,
+    pub per_level: bool,
     pub flags: i64,
 }
 
@@ -54,4 +209,117 @@ mod test {
         assert_eq!(user, test_category());
         Ok(())
     }
+
+    #[sqlx::test(fixtures(path = "../fixtures", scripts("add_game", "add_user")))]
+    async fn obsolete_slower_runs(db: PgPool) -> Result<(), Error> {
+        // two verified runs, a faster pending one, and a slower verified one
+        query!(
+            "INSERT INTO runs
+            (id, game, category, submitter, video, description, score, time,
+            verifier, status, created_at, verified_at)
+            VALUES
+            (10, 1, 1, 1, '', '', 0, 5000, 1, 1, NOW(), NOW()),
+            (11, 1, 1, 1, '', '', 0, 4000, 1, 1, NOW(), NOW()),
+            (12, 1, 1, 1, '', '', 0, 3000, NULL, 0, NOW(), NULL),
+            (13, 1, 1, 1, '', '', 0, 4500, 1, 1, NOW(), NOW())"
+        )
+        .execute(&db)
+        .await?;
+        Category::update_obsoleted(&db, Id::new(1), Some(Id::new(1))).await?;
+        let obsoleted: Vec<i64> = query!("SELECT id FROM runs WHERE obsoleted ORDER BY id")
+            .fetch_all(&db)
+            .await?
+            .into_iter()
+            .map(|row| row.id)
+            .collect();
+        assert_eq!(obsoleted, vec![10, 13]);
+        Ok(())
+    }
+
+    #[sqlx::test(fixtures(path = "../fixtures", scripts("add_game", "add_user")))]
+    async fn obsolete_per_team(db: PgPool) -> Result<(), Error> {
+        query!(
+            "INSERT INTO users
+            (id, email, username, password, biography, admin, stylesheet, banner, pfp,
+            flags, created_at)
+            VALUES (2, 'other@example.com', 'other', '', '', false, false, false, false, 0, NOW())"
+        )
+        .execute(&db)
+        .await?;
+        // 10 and 12 are by the same team, submitted by different runners
+        query!(
+            "INSERT INTO runs
+            (id, game, category, submitter, video, description, score, time,
+            verifier, status, created_at, verified_at)
+            VALUES
+            (10, 1, 1, 1, '', '', 0, 1000, 1, 1, NOW(), NOW()),
+            (11, 1, 1, 1, '', '', 0, 2000, 1, 1, NOW(), NOW()),
+            (12, 1, 1, 2, '', '', 0, 1500, 1, 1, NOW(), NOW()),
+            (13, 1, 1, 2, '', '', 0, 3000, 1, 1, NOW(), NOW())"
+        )
+        .execute(&db)
+        .await?;
+        query!(
+            "INSERT INTO run_participants (run, position, user_id, guest_name)
+            VALUES (10, 1, 2, NULL), (11, 1, NULL, 'guest'), (12, 1, 1, NULL)"
+        )
+        .execute(&db)
+        .await?;
+        Category::update_obsoleted(&db, Id::new(1), Some(Id::new(2))).await?;
+        let obsoleted: Vec<i64> = query!("SELECT id FROM runs WHERE obsoleted ORDER BY id")
+            .fetch_all(&db)
+            .await?
+            .into_iter()
+            .map(|row| row.id)
+            .collect();
+        assert_eq!(obsoleted, vec![12]);
+        Ok(())
+    }
+
+    #[test]
+    fn score_formats() {
+        assert_eq!(parse_score("12", 0).unwrap(), 12);
+        assert_eq!(parse_score(" 12.5 ", 2).unwrap(), 1250);
+        assert_eq!(parse_score("0.05", 2).unwrap(), 5);
+        assert!(parse_score("12.345", 2).is_err());
+        assert!(parse_score("1.5", 0).is_err());
+        assert!(parse_score("-3", 0).is_err());
+        assert!(parse_score("", 0).is_err());
+        assert!(parse_score(".5", 1).is_err());
+        assert_eq!(format_score(12, 0), "12");
+        assert_eq!(format_score(1250, 2), "12.50");
+        assert_eq!(format_score(5, 2), "0.05");
+        assert_eq!(format_score(-5, 1), "-0.5");
+    }
+
+    #[sqlx::test(fixtures(path = "../fixtures", scripts("add_game", "add_user")))]
+    async fn levelless_runs_block_per_level(db: PgPool) {
+        Category::check_can_be_per_level(&db, Id::new(1))
+            .await
+            .unwrap();
+        query!(
+            "INSERT INTO runs
+            (id, game, category, submitter, video, description, score, time,
+            status, created_at)
+            VALUES (10, 1, 1, 1, '', '', 0, 1000, 0, NOW())"
+        )
+        .execute(&db)
+        .await
+        .unwrap();
+        assert!(matches!(
+            Category::check_can_be_per_level(&db, Id::new(1)).await,
+            Err(Error::LevellessRuns)
+        ));
+        query!("INSERT INTO levels (id, game, name) VALUES (1, 1, 'one')")
+            .execute(&db)
+            .await
+            .unwrap();
+        query!("UPDATE runs SET level = 1")
+            .execute(&db)
+            .await
+            .unwrap();
+        Category::check_can_be_per_level(&db, Id::new(1))
+            .await
+            .unwrap();
+    }
 }
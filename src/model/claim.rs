@@ -0,0 +1,182 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use redis::{AsyncCommands, ExistenceCheck, Script, SetExpiry, SetOptions};
+
+use super::{ResolvedRun, User};
+use crate::{
+    id::{Id, RunMarker, UserMarker},
+    AppState, Error,
+};
+
+/// A verifier's claim on a pending run, so two verifiers don't review it at once
+#[derive(serde::Serialize, serde::Deserialize, Debug, Hash, PartialEq, Eq, Clone)]
+pub struct RunClaim {
+    pub user: Id<UserMarker>,
+    pub username: String,
+    /// Seconds since the unix epoch
+    pub claimed_at: u64,
+}
+
+/// A pending run, along with whoever has claimed it
+#[derive(serde::Serialize, Debug, Clone)]
+pub struct ClaimedRun {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub claim: Option<RunClaim>,
+    /// Whether other verifiers can take over the claim
+    pub claim_stale: bool,
+    #[serde(flatten)]
+    pub run: ResolvedRun,
+}
+
+impl RunClaim {
+    /// Claims expire on their own after this many seconds
+    pub const TTL: u64 = 60 * 60;
+    /// Claims older than this many seconds can be released or stolen by other verifiers
+    pub const STALE_AFTER: u64 = 15 * 60;
+
+    fn key(run: Id<RunMarker>) -> String {
+        format!("claim:run:{run}")
+    }
+
+    fn now() -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs()
+    }
+
+    pub fn is_stale_at(&self, now: u64) -> bool {
+        now.saturating_sub(self.claimed_at) >= Self::STALE_AFTER
+    }
+
+    pub fn is_stale(&self) -> bool {
+        self.is_stale_at(Self::now())
+    }
+
+    pub async fn from_redis(state: &AppState, run: Id<RunMarker>) -> Result<Option<Self>, Error> {
+        state.get_redis_object(Self::key(run)).await
+    }
+
+    /// Attach the current claim to each of `runs`
+    pub async fn claimed_runs(
+        state: &AppState,
+        runs: Vec<ResolvedRun>,
+    ) -> Result<Vec<ClaimedRun>, Error> {
+        if runs.is_empty() {
+            return Ok(Vec::new());
+        }
+        let keys: Vec<String> = runs.iter().map(|run| Self::key(run.id)).collect();
+        let raw_claims: Vec<Option<String>> = state.redis.get().await?.mget(keys).await?;
+        let now = Self::now();
+        runs.into_iter()
+            .zip(raw_claims)
+            .map(|(run, raw_claim)| {
+                let claim: Option<Self> = raw_claim
+                    .map(|raw| serde_json::from_str(&raw))
+                    .transpose()?;
+                let claim_stale = claim.as_ref().is_some_and(|claim| claim.is_stale_at(now));
+                Ok(ClaimedRun {
+                    claim,
+                    claim_stale,
+                    run,
+                })
+            })
+            .collect()
+    }
+
+    /// Claim `run` for `user`. Claims held by someone else can only be taken over once stale.
+    pub async fn claim(state: &AppState, run: Id<RunMarker>, user: &User) -> Result<Self, Error> {
+        // only replaces the claim if it's still the one that was read, so two verifiers
+        // taking over the same stale claim can't both think they got it
+        let take_over = Script::new(
+            r"if redis.call('GET', KEYS[1]) == ARGV[1] then
+                redis.call('SET', KEYS[1], ARGV[2], 'EX', ARGV[3])
+                return 1
+            end
+            return 0",
+        );
+        let claim = Self {
+            user: user.id,
+            username: user.username.clone(),
+            claimed_at: Self::now(),
+        };
+        let claim_str = serde_json::to_string(&claim)?;
+        let key = Self::key(run);
+        let mut redis = state.redis.get().await?;
+        loop {
+            let fresh_options = SetOptions::default()
+                .conditional_set(ExistenceCheck::NX)
+                .with_expiration(SetExpiry::EX(Self::TTL));
+            let claimed: bool = redis.set_options(&key, &claim_str, fresh_options).await?;
+            if claimed {
+                return Ok(claim);
+            }
+            // the claim could have been released since, in which case try again
+            let Some(existing_str): Option<String> = redis.get(&key).await? else {
+                continue;
+            };
+            let existing: Self = serde_json::from_str(&existing_str)?;
+            if existing.user != user.id && !existing.is_stale() {
+                return Err(Error::RunClaimed(existing.username));
+            }
+            let taken_over: bool = take_over
+                .key(&key)
+                .arg(&existing_str)
+                .arg(&claim_str)
+                .arg(Self::TTL)
+                .invoke_async(&mut redis)
+                .await?;
+            if taken_over {
+                return Ok(claim);
+            }
+        }
+    }
+
+    /// Release the claim on `run`. Only the claimant can release a claim before it goes stale.
+    pub async fn release(
+        state: &AppState,
+        run: Id<RunMarker>,
+        user: Id<UserMarker>,
+    ) -> Result<(), Error> {
+        Self::check_unclaimed(state, run, user).await?;
+        Self::clear(state, run).await
+    }
+
+    /// Errors if `run` is claimed by someone other than `user`
+    pub async fn check_unclaimed(
+        state: &AppState,
+        run: Id<RunMarker>,
+        user: Id<UserMarker>,
+    ) -> Result<(), Error> {
+        match Self::from_redis(state, run).await? {
+            Some(existing) if existing.user != user && !existing.is_stale() => {
+                Err(Error::RunClaimed(existing.username))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    pub async fn clear(state: &AppState, run: Id<RunMarker>) -> Result<(), Error> {
+        let _: () = state.redis.get().await?.del(Self::key(run)).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn claims_go_stale() {
+        let claim = RunClaim {
+            user: Id::new(1),
+            username: "test".to_string(),
+            claimed_at: 1000,
+        };
+        assert!(!claim.is_stale_at(1000));
+        assert!(!claim.is_stale_at(1000 + RunClaim::STALE_AFTER - 1));
+        assert!(claim.is_stale_at(1000 + RunClaim::STALE_AFTER));
+        // a clock that went backwards shouldn't make claims stale
+        assert!(!claim.is_stale_at(0));
+    }
+}
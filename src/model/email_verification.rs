@@ -0,0 +1,182 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use rand::distributions::{Alphanumeric, DistString};
+use redis::AsyncCommands;
+
+use super::User;
+use crate::{
+    id::{Id, UserMarker},
+    AppState, Error,
+};
+
+/// A link which proves a user can read mail sent to an address
+#[derive(Debug, Hash, PartialEq, Eq, Clone)]
+pub struct EmailVerification {
+    pub user: Id<UserMarker>,
+    pub email: String,
+    /// Seconds since the unix epoch
+    pub expires: u64,
+    /// Kept in redis until the link is used, so it only works once
+    pub nonce: String,
+}
+
+#[derive(serde::Serialize, Debug, Clone)]
+struct VerificationMail<'a> {
+    username: &'a str,
+    language: &'a str,
+    link: String,
+}
+
+impl EmailVerification {
+    /// Links stop working after this many seconds
+    pub const TTL: u64 = 24 * 60 * 60;
+    const KEY_CONTEXT: &'static str = "speederboard 2026-10-17 email verification links";
+
+    pub fn new(user: Id<UserMarker>, email: String) -> Self {
+        Self {
+            user,
+            email,
+            expires: Self::now() + Self::TTL,
+            nonce: Alphanumeric.sample_string(&mut rand::thread_rng(), 32),
+        }
+    }
+
+    fn nonce_key(nonce: &str) -> String {
+        format!("verification:email:{nonce}")
+    }
+
+    fn now() -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs()
+    }
+
+    fn signature(&self, secret_key: &str) -> blake3::Hash {
+        let key = blake3::derive_key(Self::KEY_CONTEXT, secret_key.as_bytes());
+        let mut hasher = blake3::Hasher::new_keyed(&key);
+        hasher.update(&self.user.get().to_le_bytes());
+        hasher.update(&self.expires.to_le_bytes());
+        hasher.update(self.nonce.as_bytes());
+        hasher.update(self.email.as_bytes());
+        hasher.finalize()
+    }
+
+    /// Whether `signature` was made for this link by `secret_key`, and it hasn't expired
+    pub fn is_valid_at(&self, secret_key: &str, signature: &str, now: u64) -> bool {
+        let Ok(signature) = blake3::Hash::from_hex(signature) else {
+            return false;
+        };
+        // hashes compare in constant time
+        now < self.expires && signature == self.signature(secret_key)
+    }
+
+    pub fn is_valid(&self, secret_key: &str, signature: &str) -> bool {
+        self.is_valid_at(secret_key, signature, Self::now())
+    }
+
+    pub fn link(&self, state: &AppState) -> String {
+        let query = url::form_urlencoded::Serializer::new(String::new())
+            .append_pair("user", &self.user.to_string())
+            .append_pair("email", &self.email)
+            .append_pair("expires", &self.expires.to_string())
+            .append_pair("nonce", &self.nonce)
+            .append_pair(
+                "signature",
+                &self.signature(&state.config.secret_key).to_hex(),
+            )
+            .finish();
+        format!("{}/verify-email?{query}", state.config.root_url)
+    }
+
+    /// Email the link to the address it verifies
+    pub async fn send(&self, state: &AppState, user: &User) -> Result<(), Error> {
+        let _: () = state
+            .redis
+            .get()
+            .await?
+            .set_ex(Self::nonce_key(&self.nonce), self.user.get(), Self::TTL)
+            .await?;
+        let mail = VerificationMail {
+            username: &user.username,
+            language: user.language.unwrap_or_default().lang_code(),
+            link: self.link(state),
+        };
+        crate::mail::send_template(state, &self.email, "mail/verify_email.jinja", mail).await
+    }
+
+    /// Email the link without waiting for the mail server. Accounts work without a verified
+    /// address, so failing to send is only logged.
+    pub fn send_in_background(self, state: &AppState, user: User) {
+        let state = state.clone();
+        tokio::spawn(async move {
+            if let Err(source) = self.send(&state, &user).await {
+                error!(?source, "failed to send verification email");
+            }
+        });
+    }
+
+    /// Mark the address as verified, using up the link. Links for an address the user has
+    /// since changed away from don't count.
+    pub async fn confirm(&self, state: &AppState) -> Result<(), Error> {
+        let owner: Option<i64> = state
+            .redis
+            .get()
+            .await?
+            .get_del(Self::nonce_key(&self.nonce))
+            .await?;
+        if owner != Some(self.user.get()) {
+            return Err(Error::InvalidVerificationLink);
+        }
+        let updated = query!(
+            "UPDATE users SET email_verified = true WHERE id = $1 AND email = $2",
+            self.user.get(),
+            self.email
+        )
+        .execute(&state.postgres)
+        .await?
+        .rows_affected();
+        if updated == 0 {
+            return Err(Error::InvalidVerificationLink);
+        }
+        Ok(())
+    }
+
+    pub async fn is_verified(state: &AppState, user: Id<UserMarker>) -> Result<bool, Error> {
+        let verified = query!("SELECT email_verified FROM users WHERE id = $1", user.get())
+            .fetch_optional(&state.postgres)
+            .await?
+            .is_some_and(|row| row.email_verified);
+        Ok(verified)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn signed_links() {
+        let verification = EmailVerification {
+            user: Id::new(1),
+            email: "test@example.com".to_string(),
+            expires: 1000,
+            nonce: "nonce".to_string(),
+        };
+        let signature = verification.signature("key").to_hex();
+        assert!(verification.is_valid_at("key", &signature, 999));
+        assert!(!verification.is_valid_at("key", &signature, 1000));
+        assert!(!verification.is_valid_at("other key", &signature, 999));
+        assert!(!verification.is_valid_at("key", "not a signature", 999));
+        let changed = EmailVerification {
+            email: "other@example.com".to_string(),
+            ..verification.clone()
+        };
+        assert!(!changed.is_valid_at("key", &signature, 999));
+        let changed = EmailVerification {
+            nonce: "other nonce".to_string(),
+            ..verification
+        };
+        assert!(!changed.is_valid_at("key", &signature, 999));
+    }
+}
@@ -17,6 +17,13 @@ pub struct Game {
 }
 
 impl Game {
+    /// Team members need two-factor authentication enabled to use their permissions
+    pub const REQUIRE_TWO_FACTOR: i64 = 1 << 0;
+
+    pub fn requires_two_factor(&self) -> bool {
+        self.flags & Self::REQUIRE_TWO_FACTOR != 0
+    }
+
     pub async fn from_db_slug(state: &AppState, slug: &str) -> Result<Self, Error> {
         match state.get_redis_object(format!("game:{slug}")).await {
             Ok(Some(game)) => return Ok(game),
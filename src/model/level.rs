@@ -0,0 +1,122 @@
+use sqlx::PgConnection;
+
+use crate::{
+    id::{GameMarker, Id, LevelMarker},
+    AppState, Error,
+};
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Hash, PartialEq, Eq, Clone)]
+pub struct Level {
+    pub id: Id<LevelMarker>,
+    pub game: Id<GameMarker>,
+    pub name: String,
+    pub flags: i64,
+}
+
+impl Level {
+    pub async fn from_db(state: &AppState, id: Id<LevelMarker>) -> Result<Level, Error> {
+        query_as!(
+            Level,
+            "SELECT id, game, name, flags FROM levels WHERE id = $1",
+            id.get()
+        )
+        .fetch_optional(&state.postgres)
+        .await?
+        .ok_or(Error::NotFound)
+    }
+
+    /// Fetch every level of a game, ordered by creation.
+    pub async fn from_db_game(state: &AppState, game: Id<GameMarker>) -> Result<Vec<Level>, Error> {
+        let levels = query_as!(
+            Level,
+            "SELECT id, game, name, flags FROM levels WHERE game = $1 ORDER BY id",
+            game.get()
+        )
+        .fetch_all(&state.postgres)
+        .await?;
+        Ok(levels)
+    }
+
+    /// Delete a level of `game`, as long as no runs were submitted on it
+    pub async fn delete(
+        conn: &mut PgConnection,
+        game: Id<GameMarker>,
+        level: Id<LevelMarker>,
+    ) -> Result<(), Error> {
+        let in_use = query!(
+            r#"SELECT EXISTS(SELECT 1 FROM runs WHERE level = $1) AS "in_use!""#,
+            level.get()
+        )
+        .fetch_one(&mut *conn)
+        .await?
+        .in_use;
+        if in_use {
+            return Err(Error::LevelInUse);
+        }
+        query!(
+            "DELETE FROM levels WHERE id = $1 AND game = $2",
+            level.get(),
+            game.get()
+        )
+        .execute(&mut *conn)
+        .await?;
+        Ok(())
+    }
+
+    pub fn collapse_optional(
+        id: Option<Id<LevelMarker>>,
+        game: Option<Id<GameMarker>>,
+        name: Option<String>,
+        flags: Option<i64>,
+    ) -> Option<Level> {
+        Some(Level {
+            id: id?,
+            game: game?,
+            name: name?,
+            flags: flags?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use sqlx::PgPool;
+
+    use super::*;
+
+    #[sqlx::test(fixtures(path = "../fixtures", scripts("add_game", "add_user")))]
+    async fn levels_with_runs_are_kept(db: PgPool) {
+        query!("INSERT INTO levels (id, game, name) VALUES (1, 1, 'one'), (2, 1, 'two')")
+            .execute(&db)
+            .await
+            .unwrap();
+        query!(
+            "INSERT INTO runs
+            (id, game, category, level, submitter, video, description, score, time,
+            status, created_at)
+            VALUES (10, 1, 1, 1, 1, '', '', 0, 1000, 0, NOW())"
+        )
+        .execute(&db)
+        .await
+        .unwrap();
+        let mut conn = db.acquire().await.unwrap();
+        assert!(matches!(
+            Level::delete(&mut conn, Id::new(1), Id::new(1)).await,
+            Err(Error::LevelInUse)
+        ));
+        Level::delete(&mut conn, Id::new(1), Id::new(2))
+            .await
+            .unwrap();
+        let levels = query!("SELECT id FROM levels")
+            .fetch_all(&db)
+            .await
+            .unwrap();
+        assert_eq!(levels.len(), 1);
+        assert_eq!(levels[0].id, 1);
+        // the run itself is still there
+        query!("SELECT id FROM runs WHERE id = 10")
+            .fetch_one(&db)
+            .await
+            .unwrap();
+    }
+}
@@ -0,0 +1,237 @@
+use rand::Rng;
+
+use super::User;
+use crate::{
+    id::{Id, UserMarker},
+    language::Language,
+    oauth::Identity,
+    AppState, Error,
+};
+
+/// An account on another site, which its user can log in with
+#[derive(serde::Serialize, Debug, Clone)]
+pub struct LinkedAccount {
+    pub provider: String,
+    pub subject: String,
+    pub display_name: Option<String>,
+    pub created_at: chrono::NaiveDateTime,
+}
+
+impl LinkedAccount {
+    pub async fn from_db_user(state: &AppState, user: Id<UserMarker>) -> Result<Vec<Self>, Error> {
+        let accounts = query_as!(
+            LinkedAccount,
+            "SELECT provider, subject, display_name, created_at
+            FROM linked_accounts WHERE user_id = $1 ORDER BY provider",
+            user.get()
+        )
+        .fetch_all(&state.postgres)
+        .await?;
+        Ok(accounts)
+    }
+
+    /// Who the account `subject` on `provider` is linked to
+    pub async fn find_user(
+        state: &AppState,
+        provider: &str,
+        subject: &str,
+    ) -> Result<Option<Id<UserMarker>>, Error> {
+        let user = query!(
+            "SELECT user_id FROM linked_accounts WHERE provider = $1 AND subject = $2",
+            provider,
+            subject
+        )
+        .fetch_optional(&state.postgres)
+        .await?
+        .map(|row| Id::new(row.user_id));
+        Ok(user)
+    }
+
+    /// Link `identity` to `user`, replacing any account they had linked from `provider`
+    pub async fn link(
+        state: &AppState,
+        user: Id<UserMarker>,
+        provider: &str,
+        identity: &Identity,
+    ) -> Result<(), Error> {
+        match Self::find_user(state, provider, &identity.subject).await? {
+            Some(owner) if owner != user => return Err(Error::OAuthAccountInUse),
+            _ => {}
+        }
+        let mut trans = state.postgres.begin().await?;
+        query!(
+            "DELETE FROM linked_accounts WHERE user_id = $1 AND provider = $2",
+            user.get(),
+            provider
+        )
+        .execute(trans.as_mut())
+        .await?;
+        query!(
+            "INSERT INTO linked_accounts (user_id, provider, subject, display_name, created_at)
+            VALUES ($1, $2, $3, $4, NOW())",
+            user.get(),
+            provider,
+            identity.subject,
+            identity.name
+        )
+        .execute(trans.as_mut())
+        .await?;
+        trans.commit().await?;
+        Ok(())
+    }
+
+    /// Unlink the account `user` had linked from `provider`. Users without a password
+    /// have to keep one linked account, or they couldn't log in.
+    pub async fn unlink(
+        state: &AppState,
+        user: Id<UserMarker>,
+        provider: &str,
+    ) -> Result<(), Error> {
+        let mut trans = state.postgres.begin().await?;
+        query!(
+            "DELETE FROM linked_accounts WHERE user_id = $1 AND provider = $2",
+            user.get(),
+            provider
+        )
+        .execute(trans.as_mut())
+        .await?;
+        let login_methods = query!(
+            r#"SELECT (users.password IS NOT NULL) AS "has_password!",
+            (SELECT COUNT(*) FROM linked_accounts WHERE user_id = $1) AS "linked!"
+            FROM users WHERE id = $1"#,
+            user.get()
+        )
+        .fetch_one(trans.as_mut())
+        .await?;
+        if !login_methods.has_password && login_methods.linked == 0 {
+            return Err(Error::LastLoginMethod);
+        }
+        trans.commit().await?;
+        Ok(())
+    }
+
+    /// Make a new account for someone logging in with `identity` for the first time
+    pub async fn sign_up(
+        state: &AppState,
+        provider: &str,
+        identity: &Identity,
+    ) -> Result<User, Error> {
+        let email = identity.email.as_deref().ok_or(Error::OAuthNoEmail)?;
+        let taken = query!("SELECT id FROM users WHERE email = $1", email)
+            .fetch_optional(&state.postgres)
+            .await?
+            .is_some();
+        // logging in as whoever owns the address would let the provider take over accounts
+        if taken {
+            return Err(Error::OAuthEmailInUse);
+        }
+        let username = Self::free_username(state, identity).await?;
+        let mut trans = state.postgres.begin().await?;
+        let row = query!(
+            "INSERT INTO users
+            (username, email, email_verified, password, stylesheet, flags,
+                pfp, banner, biography, admin, created_at)
+            VALUES ($1, $2, $3, NULL, false, 0, false, false, '', false, NOW())
+            RETURNING id, username, stylesheet, pfp, banner,
+            biography, admin, created_at, flags, language",
+            username,
+            email,
+            identity.email_verified
+        )
+        .fetch_one(trans.as_mut())
+        .await?;
+        query!(
+            "INSERT INTO linked_accounts (user_id, provider, subject, display_name, created_at)
+            VALUES ($1, $2, $3, $4, NOW())",
+            row.id,
+            provider,
+            identity.subject,
+            identity.name
+        )
+        .execute(trans.as_mut())
+        .await?;
+        trans.commit().await?;
+        Ok(User {
+            id: Id::new(row.id),
+            username: row.username,
+            stylesheet: row.stylesheet,
+            biography: row.biography,
+            pfp: row.pfp,
+            banner: row.banner,
+            admin: row.admin,
+            created_at: row.created_at,
+            flags: row.flags,
+            language: row
+                .language
+                .map(|v| Language::from_lang_code(&v))
+                .unwrap_or_default(),
+        })
+    }
+
+    /// A username like the one the user has on the provider, which nobody has taken yet
+    async fn free_username(state: &AppState, identity: &Identity) -> Result<String, Error> {
+        let wanted = identity
+            .name
+            .as_deref()
+            .or_else(|| identity.email.as_deref()?.split('@').next())
+            .unwrap_or_default();
+        let base = username_from(wanted);
+        let mut username = base.clone();
+        loop {
+            let taken = query!("SELECT id FROM users WHERE username = $1", username)
+                .fetch_optional(&state.postgres)
+                .await?
+                .is_some();
+            if !taken {
+                return Ok(username);
+            }
+            username = with_suffix(&base);
+        }
+    }
+}
+
+/// Squash `name` into something that passes [`crate::util::validate_slug`]
+fn username_from(name: &str) -> String {
+    let username: String = name
+        .chars()
+        .map(|character| character.to_ascii_lowercase())
+        .filter(|character| character.is_ascii_lowercase() || ['_', '-', '.'].contains(character))
+        .take(crate::util::MAX_USERNAME_LEN)
+        .collect();
+    if username.len() < crate::util::MIN_USERNAME_LEN {
+        String::from("runner")
+    } else {
+        username
+    }
+}
+
+/// `base` with a few random letters on the end, still short enough to be a username
+fn with_suffix(base: &str) -> String {
+    const SUFFIX_LEN: usize = 4;
+    let mut rng = rand::thread_rng();
+    let suffix: String = (0..SUFFIX_LEN)
+        .map(|_| char::from(rng.gen_range(b'a'..=b'z')))
+        .collect();
+    let keep = base
+        .len()
+        .min(crate::util::MAX_USERNAME_LEN - SUFFIX_LEN - 1);
+    format!("{}_{suffix}", &base[..keep])
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn usernames() {
+        assert_eq!(username_from("Cool Runner 99"), "coolrunner");
+        assert_eq!(username_from("x"), "runner");
+        assert_eq!(username_from("日本語"), "runner");
+        let long = username_from("averyveryverylongname");
+        assert_eq!(long.len(), crate::util::MAX_USERNAME_LEN);
+        let suffixed = with_suffix(&long);
+        assert_eq!(suffixed.len(), crate::util::MAX_USERNAME_LEN);
+        assert!(suffixed.starts_with("averyveryve_"));
+        assert!(crate::util::validate_slug(&suffixed, &()).is_ok());
+    }
+}
@@ -1,6 +1,6 @@
 use redis::AsyncCommands;
 
-use super::{Permissions, User};
+use super::{Game, Permissions, TwoFactor, User};
 use crate::{
     id::{GameMarker, Id, UserMarker},
     language::Language,
@@ -16,6 +16,34 @@ pub struct Member {
 
 impl Member {
     pub async fn from_db(
+        state: &AppState,
+        user: Id<UserMarker>,
+        game: &Game,
+    ) -> Result<Option<Self>, Error> {
+        let member = Self::from_db_unchecked(state, user, game.id).await?;
+        match member {
+            Some(member) => Ok(Some(member.apply_two_factor_policy(state, game).await?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Members of games which require two-factor authentication have no permissions
+    /// there until they enable it
+    pub async fn apply_two_factor_policy(
+        mut self,
+        state: &AppState,
+        game: &Game,
+    ) -> Result<Self, Error> {
+        if game.requires_two_factor()
+            && !self.perms.is_empty()
+            && !TwoFactor::is_enabled(state, self.user.id).await?
+        {
+            self.perms = Permissions::EMPTY;
+        }
+        Ok(self)
+    }
+
+    async fn from_db_unchecked(
         state: &AppState,
         user: Id<UserMarker>,
         game: Id<GameMarker>,
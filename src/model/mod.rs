@@ -1,15 +1,48 @@
+mod canned_reason;
 mod category;
+mod claim;
+mod email_verification;
 mod forum;
 mod game;
+mod level;
+mod linked_account;
 mod member;
+mod password_reset;
 mod permissions;
+mod platform;
+mod ranking;
+mod revision;
 mod run;
+mod run_comment;
+mod session;
+mod splits;
+mod timing;
+mod two_factor;
 mod user;
+mod variable;
 
-pub use category::{Category, MiniCategory};
+pub use canned_reason::CannedReason;
+pub use category::{format_score, parse_score, Category, MiniCategory};
+pub use claim::{ClaimedRun, RunClaim};
+pub use email_verification::EmailVerification;
 pub use forum::{ForumComment, ForumPost};
 pub use game::Game;
+pub use level::Level;
+pub use linked_account::LinkedAccount;
 pub use member::Member;
+pub use password_reset::PasswordReset;
 pub use permissions::Permissions;
-pub use run::{DateSort, ResolvedRun, RunStatus, SortBy};
+pub use platform::Platform;
+pub use ranking::{GameRanking, RankingFormula, RankingSettings};
+pub use revision::RunRevision;
+pub use run::{
+    DateSort, LeaderboardFilter, PageCursor, RankedRun, ResolvedRun, RunSearch, RunStatus,
+    SearchSort, SortBy,
+};
+pub use run_comment::RunComment;
+pub use session::{ClientInfo, Session, SessionInfo};
+pub use splits::Splits;
+pub use timing::{parse_duration, TimingMethod, TimingMethods};
+pub use two_factor::{Enrolment, LoginAttempt, TwoFactor};
 pub use user::{User, UserUpdate};
+pub use variable::{Variable, VariableValue};
@@ -0,0 +1,114 @@
+use rand::distributions::{Alphanumeric, DistString};
+use redis::{AsyncCommands, ExistenceCheck, SetExpiry, SetOptions};
+
+use super::{Session, User};
+use crate::{id::Id, AppState, Error};
+
+/// A single-use token, emailed to a user, which lets them set a new password.
+/// Tokens are stored at `token:reset:{token}`, pointing at the user's id, and
+/// `ratelimit:reset:{email}` is set while an address can't be sent another.
+pub struct PasswordReset;
+
+#[derive(serde::Serialize, Debug, Clone)]
+struct ResetMail<'a> {
+    username: &'a str,
+    language: &'a str,
+    link: String,
+}
+
+impl PasswordReset {
+    /// Tokens stop working after this many seconds
+    pub const TTL: u64 = 60 * 60;
+    /// An address is sent at most one link in this many seconds
+    pub const RATE_LIMIT: u64 = 5 * 60;
+
+    fn key(token: &str) -> String {
+        format!("token:reset:{token}")
+    }
+
+    /// Spelling an address differently doesn't get around the limit
+    fn rate_limit_key(email: &str) -> String {
+        format!("ratelimit:reset:{}", email.trim().to_lowercase())
+    }
+
+    /// Email a reset link to the owner of `email`. Nothing is sent if there is no such account,
+    /// or if the address was sent one recently, but callers shouldn't say so, so the form
+    /// can't be used to find out who has an account or to flood someone's inbox.
+    pub async fn request(state: &AppState, email: &str) -> Result<(), Error> {
+        let limit = SetOptions::default()
+            .conditional_set(ExistenceCheck::NX)
+            .with_expiration(SetExpiry::EX(Self::RATE_LIMIT));
+        let allowed: bool = state
+            .redis
+            .get()
+            .await?
+            .set_options(Self::rate_limit_key(email), 1, limit)
+            .await?;
+        if !allowed {
+            return Ok(());
+        }
+        let Some(row) = query!("SELECT id FROM users WHERE email = $1", email)
+            .fetch_optional(&state.postgres)
+            .await?
+        else {
+            return Ok(());
+        };
+        let user = User::from_db(state, Id::new(row.id)).await?;
+        let token = Alphanumeric.sample_string(&mut rand::thread_rng(), 64);
+        let _: () = state
+            .redis
+            .get()
+            .await?
+            .set_ex(Self::key(&token), user.id.get(), Self::TTL)
+            .await?;
+        let mail = ResetMail {
+            username: &user.username,
+            language: user.language.unwrap_or_default().lang_code(),
+            link: format!("{}/reset-password?token={token}", state.config.root_url),
+        };
+        crate::mail::send_template(state, email, "mail/reset_password.jinja", mail).await
+    }
+
+    /// Check that `token` can still be used, without using it up
+    pub async fn check(state: &AppState, token: &str) -> Result<(), Error> {
+        let exists: bool = state.redis.get().await?.exists(Self::key(token)).await?;
+        if exists {
+            Ok(())
+        } else {
+            Err(Error::InvalidResetLink)
+        }
+    }
+
+    /// Use up `token` to set a new password for its user, logging them out everywhere
+    pub async fn redeem(state: &AppState, token: &str, password: String) -> Result<(), Error> {
+        let maybe_id: Option<i64> = state.redis.get().await?.get_del(Self::key(token)).await?;
+        let user = Id::new(maybe_id.ok_or(Error::InvalidResetLink)?);
+        let password_hash = state
+            .spawn_rayon(
+                |state, password| crate::util::hash_password(password.as_bytes(), &state.argon),
+                password,
+            )
+            .await??;
+        query!(
+            "UPDATE users SET password = $2 WHERE id = $1",
+            user.get(),
+            password_hash
+        )
+        .execute(&state.postgres)
+        .await?;
+        Session::end_all(state, user).await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn rate_limit_ignores_spelling() {
+        assert_eq!(
+            PasswordReset::rate_limit_key(" Test@Example.com "),
+            PasswordReset::rate_limit_key("test@example.com")
+        );
+    }
+}
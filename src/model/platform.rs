@@ -0,0 +1,43 @@
+use crate::{
+    id::{GameMarker, Id, PlatformMarker},
+    AppState, Error,
+};
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Hash, PartialEq, Eq, Clone)]
+pub struct Platform {
+    pub id: Id<PlatformMarker>,
+    pub game: Id<GameMarker>,
+    pub name: String,
+    pub flags: i64,
+}
+
+impl Platform {
+    /// Fetch every platform of a game, ordered by creation.
+    pub async fn from_db_game(
+        state: &AppState,
+        game: Id<GameMarker>,
+    ) -> Result<Vec<Platform>, Error> {
+        let platforms = query_as!(
+            Platform,
+            "SELECT id, game, name, flags FROM platforms WHERE game = $1 ORDER BY id",
+            game.get()
+        )
+        .fetch_all(&state.postgres)
+        .await?;
+        Ok(platforms)
+    }
+
+    pub fn collapse_optional(
+        id: Option<Id<PlatformMarker>>,
+        game: Option<Id<GameMarker>>,
+        name: Option<String>,
+        flags: Option<i64>,
+    ) -> Option<Platform> {
+        Some(Platform {
+            id: id?,
+            game: game?,
+            name: name?,
+            flags: flags?,
+        })
+    }
+}
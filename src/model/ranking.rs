@@ -0,0 +1,282 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
+
+use redis::AsyncCommands;
+
+use super::{Category, Game, LeaderboardFilter, Level, RankedRun, ResolvedRun, RunStatus};
+use crate::{
+    id::{GameMarker, Id, LevelMarker, UserMarker},
+    AppState, Error,
+};
+
+/// How many points a placement on one leaderboard is worth
+#[derive(
+    serde::Serialize, serde::Deserialize, Debug, Default, Hash, PartialEq, Eq, Clone, Copy,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum RankingFormula {
+    /// First place gets the full points, and every place after it one less
+    #[default]
+    Linear,
+    /// Points are scaled by how many runners placed at or below, so winning
+    /// a crowded leaderboard is worth the same as winning an empty one
+    Relative,
+    /// Points are divided by the placement, so second place gets half
+    Inverse,
+}
+
+impl RankingFormula {
+    pub const ALL: [Self; 3] = [Self::Linear, Self::Relative, Self::Inverse];
+
+    /// Points for `placement` (starting at 1) on a leaderboard with `runners` runners
+    pub fn points(self, first_place_points: i64, placement: i64, runners: i64) -> i64 {
+        match self {
+            Self::Linear => (first_place_points - (placement - 1)).max(0),
+            Self::Relative => first_place_points * (runners - placement + 1) / runners.max(1),
+            Self::Inverse => first_place_points / placement.max(1),
+        }
+    }
+}
+
+impl From<i16> for RankingFormula {
+    fn from(value: i16) -> Self {
+        match value {
+            1 => Self::Relative,
+            2 => Self::Inverse,
+            _ => Self::Linear,
+        }
+    }
+}
+
+impl From<RankingFormula> for i16 {
+    fn from(value: RankingFormula) -> Self {
+        match value {
+            RankingFormula::Linear => 0,
+            RankingFormula::Relative => 1,
+            RankingFormula::Inverse => 2,
+        }
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Hash, PartialEq, Eq, Clone)]
+pub struct RankingSettings {
+    pub formula: RankingFormula,
+    pub first_place_points: i64,
+}
+
+impl Default for RankingSettings {
+    fn default() -> Self {
+        Self {
+            formula: RankingFormula::default(),
+            first_place_points: 100,
+        }
+    }
+}
+
+impl RankingSettings {
+    pub async fn from_db(state: &AppState, game: Id<GameMarker>) -> Result<Self, Error> {
+        let settings = query!(
+            "SELECT formula, first_place_points FROM ranking_settings WHERE game = $1",
+            game.get()
+        )
+        .fetch_optional(&state.postgres)
+        .await?
+        .map(|row| Self {
+            formula: row.formula.into(),
+            first_place_points: row.first_place_points,
+        });
+        Ok(settings.unwrap_or_default())
+    }
+}
+
+/// One runner's standing in a game's overall ranking
+#[derive(serde::Serialize, serde::Deserialize, Debug, Hash, PartialEq, Eq, Clone)]
+pub struct RankingEntry {
+    /// Standard competition ranking, like [`RankedRun::rank`]
+    pub rank: i64,
+    pub user: Id<UserMarker>,
+    pub username: String,
+    pub points: i64,
+    /// How many leaderboards the runner placed on
+    pub leaderboards: i64,
+}
+
+/// A game's overall ranking, which sums up the points each runner earned across
+/// every leaderboard of the game. Every level of a per-level category counts as
+/// its own leaderboard, and runs are credited to their submitter.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Hash, PartialEq, Eq, Clone)]
+pub struct GameRanking {
+    pub settings: RankingSettings,
+    pub entries: Vec<RankingEntry>,
+}
+
+impl GameRanking {
+    /// Rankings are invalidated whenever runs change, so this is just a backstop
+    const TTL: u64 = 60 * 60;
+    /// Leaderboards with more personal bests than this only count their top runs
+    const MAX_RUNS_PER_LEADERBOARD: usize = 10_000;
+
+    fn key(game: Id<GameMarker>) -> String {
+        format!("ranking:game:{game}")
+    }
+
+    /// Get the ranking from the cache, working it out if it isn't there
+    pub async fn get(state: &AppState, game: Arc<Game>) -> Result<Self, Error> {
+        match state.get_redis_object(Self::key(game.id)).await {
+            Ok(Some(ranking)) => return Ok(ranking),
+            Ok(None) => trace!(game = game.id.get(), "did not find ranking in redis cache"),
+            Err(source) => error!(
+                ?source,
+                game = game.id.get(),
+                "an error occured trying to find ranking in redis cache"
+            ),
+        };
+        let ranking = Self::build(state, game.clone()).await?;
+        state
+            .set_redis_object(Self::key(game.id), &ranking, Self::TTL)
+            .await?;
+        Ok(ranking)
+    }
+
+    /// Drop the cached ranking of `game`, which has to be done whenever its verified runs change
+    pub async fn invalidate(state: &AppState, game: Id<GameMarker>) -> Result<(), Error> {
+        state.redis.get().await?.del(Self::key(game)).await?;
+        Ok(())
+    }
+
+    async fn build(state: &AppState, game: Arc<Game>) -> Result<Self, Error> {
+        let settings = RankingSettings::from_db(state, game.id).await?;
+        let categories = query_as!(
+            Category,
+            "SELECT * FROM categories WHERE game = $1 ORDER BY id",
+            game.id.get()
+        )
+        .fetch_all(&state.postgres)
+        .await?;
+        let levels = Level::from_db_game(state, game.id).await?;
+        let mut totals: HashMap<Id<UserMarker>, RankingEntry> = HashMap::new();
+        for category in categories {
+            let leaderboards: Vec<Option<Id<LevelMarker>>> = if category.per_level {
+                levels.iter().map(|level| Some(level.id)).collect()
+            } else {
+                vec![None]
+            };
+            for level in leaderboards {
+                let runs = ResolvedRun::fetch_leaderboard(
+                    state,
+                    game.clone(),
+                    RunStatus::Verified,
+                    LeaderboardFilter {
+                        category: Some(category.id),
+                        level,
+                        personal_bests_only: true,
+                        ..Default::default()
+                    },
+                    category.sort_by(),
+                    Self::MAX_RUNS_PER_LEADERBOARD,
+                    None,
+                )
+                .await?
+                .ranked();
+                let runners = runner_placements(&runs);
+                let runner_count = i64::try_from(runners.len())?;
+                for (run, placement) in runners {
+                    let entry = totals
+                        .entry(run.submitter.id)
+                        .or_insert_with(|| RankingEntry {
+                            rank: 0,
+                            user: run.submitter.id,
+                            username: run.submitter.username.clone(),
+                            points: 0,
+                            leaderboards: 0,
+                        });
+                    entry.points += settings.formula.points(
+                        settings.first_place_points,
+                        placement,
+                        runner_count,
+                    );
+                    entry.leaderboards += 1;
+                }
+            }
+        }
+        let mut entries: Vec<RankingEntry> = totals.into_values().collect();
+        rank_entries(&mut entries);
+        Ok(Self { settings, entries })
+    }
+}
+
+/// Keep only the best run of each runner from `runs`, which are in leaderboard order,
+/// and work out their placement among the runners
+fn runner_placements(runs: &[RankedRun]) -> Vec<(&ResolvedRun, i64)> {
+    let mut placements: Vec<(&ResolvedRun, i64)> = Vec::new();
+    let mut seen = HashSet::new();
+    let mut previous_rank = None;
+    for ranked in runs {
+        if !seen.insert(ranked.run.submitter.id) {
+            continue;
+        }
+        let placement = match (previous_rank, placements.last()) {
+            (Some(rank), Some((_, placement))) if rank == ranked.rank => *placement,
+            _ => i64::try_from(placements.len()).unwrap_or(i64::MAX) + 1,
+        };
+        previous_rank = Some(ranked.rank);
+        placements.push((&ranked.run, placement));
+    }
+    placements
+}
+
+/// Sort `entries` by points and give each of them their rank
+fn rank_entries(entries: &mut [RankingEntry]) {
+    entries.sort_by(|a, b| {
+        b.points
+            .cmp(&a.points)
+            .then_with(|| a.username.cmp(&b.username))
+    });
+    let mut previous_points = None;
+    let mut previous_rank = 0;
+    for (index, entry) in entries.iter_mut().enumerate() {
+        if previous_points != Some(entry.points) {
+            previous_rank = i64::try_from(index).unwrap_or(i64::MAX) + 1;
+        }
+        previous_points = Some(entry.points);
+        entry.rank = previous_rank;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn formulas() {
+        assert_eq!(RankingFormula::Linear.points(100, 1, 10), 100);
+        assert_eq!(RankingFormula::Linear.points(100, 3, 10), 98);
+        assert_eq!(RankingFormula::Linear.points(2, 5, 10), 0);
+        assert_eq!(RankingFormula::Relative.points(100, 1, 4), 100);
+        assert_eq!(RankingFormula::Relative.points(100, 4, 4), 25);
+        assert_eq!(RankingFormula::Inverse.points(100, 2, 10), 50);
+        for formula in RankingFormula::ALL {
+            assert_eq!(RankingFormula::from(i16::from(formula)), formula);
+        }
+    }
+
+    #[test]
+    fn ranks_share_ties() {
+        let entry = |username: &str, points| RankingEntry {
+            rank: 0,
+            user: Id::new(1),
+            username: username.to_string(),
+            points,
+            leaderboards: 1,
+        };
+        let mut entries = vec![entry("c", 50), entry("a", 100), entry("b", 100)];
+        rank_entries(&mut entries);
+        let ranks: Vec<(&str, i64)> = entries
+            .iter()
+            .map(|entry| (entry.username.as_str(), entry.rank))
+            .collect();
+        assert_eq!(ranks, vec![("a", 1), ("b", 1), ("c", 3)]);
+    }
+}
@@ -0,0 +1,185 @@
+use chrono::NaiveDateTime;
+use sqlx::PgExecutor;
+
+use super::{run::Participant, RunStatus};
+use crate::{
+    id::{
+        Id, LevelMarker, PlatformMarker, RunMarker, RunRevisionMarker, UserMarker,
+        VariableValueMarker,
+    },
+    AppState, Error,
+};
+
+/// A previous version of a run, saved whenever the run is edited
+#[derive(serde::Serialize, serde::Deserialize, Debug, Hash, PartialEq, Eq, Clone)]
+pub struct RunRevision {
+    pub id: Id<RunRevisionMarker>,
+    pub run: Id<RunMarker>,
+    /// The user whose edit replaced this version
+    pub editor: Id<UserMarker>,
+    pub editor_username: String,
+    pub video: String,
+    pub description: String,
+    pub score: i64,
+    pub time: i64,
+    pub igt: i64,
+    pub lrt: i64,
+    pub level: Option<Id<LevelMarker>>,
+    pub variables: Vec<Id<VariableValueMarker>>,
+    pub platform: Option<Id<PlatformMarker>>,
+    pub emulated: bool,
+    /// The runners besides the submitter, in the order they were listed
+    pub participants: Vec<Participant>,
+    pub status: RunStatus,
+    pub edited_at: NaiveDateTime,
+}
+
+impl RunRevision {
+    /// Fetch every revision of a run, newest first
+    pub async fn from_db_run(state: &AppState, run: Id<RunMarker>) -> Result<Vec<Self>, Error> {
+        let revisions = query!(
+            r#"SELECT run_revisions.id, run_revisions.run, run_revisions.editor,
+            users.username, run_revisions.video, run_revisions.description,
+            run_revisions.score, run_revisions.time, run_revisions.igt,
+            run_revisions.lrt, run_revisions.level, run_revisions.variables,
+            run_revisions.platform, run_revisions.emulated,
+            run_revisions.participant_users AS "participant_users: Vec<Option<i64>>",
+            ARRAY(
+                SELECT COALESCE(participant.username, runners.guest_name)
+                FROM UNNEST(run_revisions.participant_users, run_revisions.participant_guests)
+                WITH ORDINALITY AS runners(user_id, guest_name, position)
+                LEFT JOIN users AS participant ON runners.user_id = participant.id
+                ORDER BY runners.position
+            ) AS "participant_names!: Vec<String>",
+            run_revisions.status, run_revisions.edited_at
+            FROM run_revisions JOIN users ON run_revisions.editor = users.id
+            WHERE run_revisions.run = $1
+            ORDER BY run_revisions.id DESC"#,
+            run.get()
+        )
+        .fetch_all(&state.postgres)
+        .await?
+        .into_iter()
+        .map(|row| Self {
+            id: Id::new(row.id),
+            run: Id::new(row.run),
+            editor: Id::new(row.editor),
+            editor_username: row.username,
+            video: row.video,
+            description: row.description,
+            score: row.score,
+            time: row.time,
+            igt: row.igt,
+            lrt: row.lrt,
+            level: row.level.map(Id::new),
+            variables: row.variables.into_iter().map(Id::new).collect(),
+            platform: row.platform.map(Id::new),
+            emulated: row.emulated,
+            participants: row
+                .participant_users
+                .into_iter()
+                .zip(row.participant_names)
+                .map(|(user, name)| Participant {
+                    user: user.map(Id::new),
+                    name,
+                })
+                .collect(),
+            status: row.status.into(),
+            edited_at: row.edited_at,
+        })
+        .collect();
+        Ok(revisions)
+    }
+
+    /// Save the current version of `run` as a revision, before `editor` changes it
+    pub async fn save(
+        executor: impl PgExecutor<'_>,
+        run: Id<RunMarker>,
+        editor: Id<UserMarker>,
+    ) -> Result<(), Error> {
+        query!(
+            "INSERT INTO run_revisions
+            (
+                run, editor, video, description, score, time,
+                igt, lrt, level, variables, platform, emulated,
+                participant_users, participant_guests, status, edited_at
+            )
+            SELECT id, $2, video, description, score, time,
+            igt, lrt, level,
+            ARRAY(
+                SELECT value FROM run_variables
+                WHERE run_variables.run = runs.id
+                ORDER BY variable
+            ),
+            platform, emulated,
+            ARRAY(
+                SELECT user_id FROM run_participants
+                WHERE run_participants.run = runs.id
+                ORDER BY position
+            ),
+            ARRAY(
+                SELECT guest_name FROM run_participants
+                WHERE run_participants.run = runs.id
+                ORDER BY position
+            ),
+            status, NOW()
+            FROM runs WHERE id = $1",
+            run.get(),
+            editor.get()
+        )
+        .execute(executor)
+        .await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use sqlx::PgPool;
+
+    use super::*;
+
+    #[sqlx::test(fixtures(path = "../fixtures", scripts("add_game", "add_user")))]
+    async fn save_copies_run(db: PgPool) -> Result<(), Error> {
+        query!(
+            "INSERT INTO runs
+            (id, game, category, submitter, video, description, score, time,
+            verifier, status, created_at, verified_at)
+            VALUES (10, 1, 1, 1, 'https://example.com', 'first try', 0, 5000, 1, 1, NOW(), NOW())"
+        )
+        .execute(&db)
+        .await?;
+        query!(
+            "INSERT INTO run_participants (run, position, user_id, guest_name)
+            VALUES (10, 1, NULL, 'friend')"
+        )
+        .execute(&db)
+        .await?;
+        RunRevision::save(&db, Id::new(10), Id::new(1)).await?;
+        query!(
+            "UPDATE runs SET time = 4000, description = 'second try', emulated = true
+            WHERE id = 10"
+        )
+        .execute(&db)
+        .await?;
+        query!("DELETE FROM run_participants WHERE run = 10")
+            .execute(&db)
+            .await?;
+        let revision = query!(
+            r#"SELECT editor, description, time, status, emulated,
+            participant_users AS "participant_users: Vec<Option<i64>>",
+            participant_guests AS "participant_guests: Vec<Option<String>>"
+            FROM run_revisions WHERE run = 10"#
+        )
+        .fetch_one(&db)
+        .await?;
+        assert_eq!(revision.editor, 1);
+        assert_eq!(revision.description, "first try");
+        assert_eq!(revision.time, 5000);
+        assert_eq!(revision.status, 1);
+        assert!(!revision.emulated);
+        assert_eq!(revision.participant_users, [None]);
+        assert_eq!(revision.participant_guests, [Some("friend".to_string())]);
+        Ok(())
+    }
+}
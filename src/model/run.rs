@@ -3,9 +3,12 @@ use std::{cmp::Ordering, sync::Arc};
 use chrono::NaiveDateTime;
 use sqlx::{postgres::PgRow, PgPool, Row};
 
-use super::{Category, Game, User};
+use super::{Category, Game, Level, Platform, TimingMethod, User};
 use crate::{
-    id::{CategoryMarker, GameMarker, Id, RunMarker, UserMarker},
+    id::{
+        CategoryMarker, GameMarker, Id, LevelMarker, PlatformMarker, RunMarker, UserMarker,
+        VariableValueMarker,
+    },
     language::Language,
     AppState, Error,
 };
@@ -96,10 +99,14 @@ pub struct Run {
     pub description: String,
     pub score: i64,
     pub time: i64,
+    pub igt: i64,
+    pub lrt: i64,
     pub status: RunStatus,
     pub created_at: NaiveDateTime,
     pub edited_at: Option<NaiveDateTime>,
     pub verified_at: Option<NaiveDateTime>,
+    pub obsoleted: bool,
+    pub review_note: Option<String>,
     pub flags: i64,
 }
 
@@ -115,56 +122,341 @@ pub struct ResolvedRun {
     pub description: String,
     pub score: i64,
     pub time: i64,
+    pub igt: i64,
+    pub lrt: i64,
     pub status: RunStatus,
     pub created_at: NaiveDateTime,
     pub edited_at: Option<NaiveDateTime>,
     pub verified_at: Option<NaiveDateTime>,
+    /// Set when a newer verified run from the same runners beat this one,
+    /// or when it has no time by its category's primary timing method
+    pub obsoleted: bool,
+    /// Left by the verifier when verifying or rejecting this run
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub review_note: Option<String>,
     pub flags: i64,
+    /// The values this run was submitted with, ordered by their variable
+    pub variables: Vec<Id<VariableValueMarker>>,
+    /// Everyone besides the submitter who ran a co-op run
+    pub participants: Vec<Participant>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub level: Option<Level>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub platform: Option<Platform>,
+    /// Set when the run was done on an emulator instead of the platform itself
+    pub emulated: bool,
+}
+
+/// A co-op runner, who is either a registered user or just a name
+#[derive(serde::Serialize, serde::Deserialize, Debug, Hash, PartialEq, Eq, Clone)]
+pub struct Participant {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user: Option<Id<UserMarker>>,
+    /// The username of registered users, or the guest's name
+    pub name: String,
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug)]
 pub enum SortBy {
-    Score,
-    Time,
+    Score { lower_wins: bool },
+    Time(TimingMethod),
     SubmissionDate(DateSort),
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug)]
 pub enum DateSort {
     Newest,
     Oldest,
 }
 
+impl SortBy {
+    /// The column runs in `table` are sorted by
+    fn column(self, table: &str) -> String {
+        match self {
+            Self::Score { .. } => format!("{table}.score"),
+            Self::Time(method) => format!("{table}.{}", method.column()),
+            Self::SubmissionDate(_) => format!("{table}.created_at"),
+        }
+    }
+
+    /// Whether higher values of the [column](Self::column) come first
+    fn descending(self) -> bool {
+        matches!(
+            self,
+            Self::Score { lower_wins: false } | Self::SubmissionDate(DateSort::Newest)
+        )
+    }
+
+    /// Whether runs with the same value go newest first. Ties on leaderboards
+    /// go to whoever submitted first, so this only applies to submission dates.
+    fn newest_first(self) -> bool {
+        matches!(self, Self::SubmissionDate(DateSort::Newest))
+    }
+
+    /// A condition which holds when the run in `table` has a value to be ranked by.
+    /// Times of 0 weren't recorded, so those runs can't be ranked by that timing method.
+    fn recorded(self, table: &str) -> Option<String> {
+        match self {
+            Self::Time(method) => Some(format!("{table}.{} <> 0", method.column())),
+            Self::Score { .. } | Self::SubmissionDate(_) => None,
+        }
+    }
+
+    /// The value `run` is ranked by, which submission dates don't have
+    pub fn run_value(self, run: &ResolvedRun) -> Option<i64> {
+        match self {
+            Self::Score { .. } => Some(run.score),
+            Self::Time(method) => Some(run.time_for(method)),
+            Self::SubmissionDate(_) => None,
+        }
+    }
+
+    /// A condition which holds when the run in `table` beats the run in `other`.
+    /// Submission dates don't make one run better than another, so they have none.
+    fn beats(self, table: &str, other: &str) -> Option<String> {
+        self.comparison(table, other, "<", ">")
+    }
+
+    /// Like [`beats`](Self::beats), but also holds when the runs tie
+    fn ties_or_beats(self, table: &str, other: &str) -> Option<String> {
+        self.comparison(table, other, "<=", ">=")
+    }
+
+    fn comparison(
+        self,
+        table: &str,
+        other: &str,
+        ascending: &str,
+        descending: &str,
+    ) -> Option<String> {
+        if let Self::SubmissionDate(_) = self {
+            return None;
+        }
+        let operator = if self.descending() {
+            descending
+        } else {
+            ascending
+        };
+        Some(format!(
+            "{} {operator} {}",
+            self.column(table),
+            self.column(other)
+        ))
+    }
+}
+
+/// The orders runs can be searched in
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SearchSort {
+    #[default]
+    Newest,
+    Oldest,
+    /// By the primary time of the category being searched, which has to be set.
+    /// Runs without a time by that method are left out.
+    Fastest,
+    Slowest,
+    /// Only includes runs on scoreboards
+    HighestScore,
+    LowestScore,
+}
+
+impl SearchSort {
+    pub const ALL: [Self; 6] = [
+        Self::Newest,
+        Self::Oldest,
+        Self::Fastest,
+        Self::Slowest,
+        Self::HighestScore,
+        Self::LowestScore,
+    ];
+
+    /// The column runs are sorted by, where times are by `timing`.
+    /// Time sorts have no column without a timing method.
+    fn column(self, timing: Option<TimingMethod>) -> Option<&'static str> {
+        match self {
+            Self::Newest | Self::Oldest => Some("created_at"),
+            Self::Fastest | Self::Slowest => timing.map(TimingMethod::column),
+            Self::HighestScore | Self::LowestScore => Some("score"),
+        }
+    }
+
+    fn descending(self) -> bool {
+        matches!(self, Self::Newest | Self::Slowest | Self::HighestScore)
+    }
+
+    /// Times and scores can't be compared, so each sort only applies to one kind of category
+    fn condition(self) -> Option<&'static str> {
+        match self {
+            Self::Newest | Self::Oldest => None,
+            Self::Fastest | Self::Slowest => Some("NOT category.scoreboard"),
+            Self::HighestScore | Self::LowestScore => Some("category.scoreboard"),
+        }
+    }
+}
+
+/// The time of the run in `runs` by the primary timing method of the category in `category`
+fn primary_time(runs: &str, category: &str) -> String {
+    format!(
+        "CASE {category}.primary_timing WHEN 1 THEN {runs}.igt \
+        WHEN 2 THEN {runs}.lrt ELSE {runs}.time END"
+    )
+}
+
+/// The runners of the run in `runs`, sorted so runs by the same team compare equal.
+/// Guests are named after a `~`, so they can't be mistaken for user ids.
+fn team(runs: &str) -> String {
+    format!(
+        "ARRAY(SELECT runner FROM (SELECT {runs}.submitter::TEXT AS runner UNION ALL \
+        SELECT COALESCE(run_participants.user_id::TEXT, '~' || run_participants.guest_name) \
+        FROM run_participants WHERE run_participants.run = {runs}.id) AS runners ORDER BY runner)"
+    )
+}
+
+/// The values the run in `runs` was submitted with, ordered by their variable
+fn variable_values(runs: &str) -> String {
+    format!(
+        "ARRAY(SELECT run_variables.value FROM run_variables \
+        WHERE run_variables.run = {runs}.id ORDER BY run_variables.variable)"
+    )
+}
+
+/// Narrows down which runs [`ResolvedRun::search`] returns, across every game
+#[derive(Clone, Debug, Default)]
+pub struct RunSearch {
+    pub game: Option<Id<GameMarker>>,
+    pub category: Option<Id<CategoryMarker>>,
+    /// The primary timing method of `category`, which time bounds and sorts go by
+    pub timing: Option<TimingMethod>,
+    /// Runs this user submitted or took part in
+    pub runner: Option<Id<UserMarker>>,
+    pub status: Option<RunStatus>,
+    pub submitted_after: Option<NaiveDateTime>,
+    pub submitted_before: Option<NaiveDateTime>,
+    /// Bounds on the primary time, in milliseconds. Scoreboards are left out when set.
+    pub min_time: Option<i64>,
+    pub max_time: Option<i64>,
+    pub sort: SearchSort,
+    /// Only return runs which come after this one in the sort order
+    pub after: Option<Id<RunMarker>>,
+}
+
+/// A run along with its position on the leaderboard it was fetched from
+#[derive(serde::Serialize, Debug, Clone)]
+pub struct RankedRun {
+    /// Standard competition ranking, so tied runs share a rank and
+    /// the run after them skips ahead (1, 2, 2, 4)
+    pub rank: i64,
+    #[serde(flatten)]
+    pub run: ResolvedRun,
+}
+
+/// Narrows down which runs [`ResolvedRun::fetch_leaderboard`] returns.
+/// The default applies no filtering at all.
+#[derive(Clone, Debug, Default)]
+pub struct LeaderboardFilter {
+    pub category: Option<Id<CategoryMarker>>,
+    pub level: Option<Id<LevelMarker>>,
+    /// Only runs submitted with every one of these values are returned
+    pub variables: Vec<Id<VariableValueMarker>>,
+    pub platform: Option<Id<PlatformMarker>>,
+    /// Only runs which were, or weren't, done on an emulator are returned
+    pub emulated: Option<bool>,
+    /// Only return each runner's personal best, which on boards for a platform
+    /// is their best run on that platform
+    pub personal_bests_only: bool,
+    /// Only return runs which beat every earlier run matching the same filter by this
+    /// ordering, which gives the progression of the record
+    pub records_by: Option<SortBy>,
+}
+
+/// Where a page of a leaderboard starts, relative to a run on the page next to it
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PageCursor {
+    /// The runs right after this one
+    After(Id<RunMarker>),
+    /// The runs right before this one
+    Before(Id<RunMarker>),
+}
+
+impl PageCursor {
+    /// Read a cursor from the `after` and `before` query parameters, preferring `after`
+    pub fn from_query(after: Option<Id<RunMarker>>, before: Option<Id<RunMarker>>) -> Option<Self> {
+        after.map(Self::After).or(before.map(Self::Before))
+    }
+}
+
 pub struct ResolvedRunResult {
-    resolveds: Vec<ResolvedRun>,
+    ranked: Vec<RankedRun>,
     has_next: bool,
+    has_previous: bool,
 }
 
 impl ResolvedRunResult {
-    pub fn has_next(&self) -> bool {
-        self.has_next
+    /// The run the next page starts after, if there is a next page
+    pub fn next_cursor(&self) -> Option<Id<RunMarker>> {
+        self.ranked
+            .last()
+            .filter(|_| self.has_next)
+            .map(|ranked| ranked.run.id)
+    }
+
+    /// The run the previous page ends before, if there is a previous page
+    pub fn previous_cursor(&self) -> Option<Id<RunMarker>> {
+        self.ranked
+            .first()
+            .filter(|_| self.has_previous)
+            .map(|ranked| ranked.run.id)
     }
 
     pub fn resolveds(self) -> Vec<ResolvedRun> {
-        self.resolveds
+        self.ranked.into_iter().map(|ranked| ranked.run).collect()
+    }
+
+    pub fn ranked(self) -> Vec<RankedRun> {
+        self.ranked
     }
 }
 
+#[derive(Clone)]
 struct ResolvedRunRequestMultiple {
     game: Arc<Game>,
     status: RunStatus,
-    maybe_category: Option<Id<CategoryMarker>>,
+    filter: LeaderboardFilter,
     sort_by: SortBy,
     limit: usize,
-    page: usize,
+    cursor: Option<PageCursor>,
+}
+
+/// Runs across every game which a user submitted or took part in
+struct ResolvedRunRequestRunner {
+    user: Id<UserMarker>,
+    status: RunStatus,
+    /// Hide obsoleted runs, and order by game and category instead of newest first
+    personal_bests_only: bool,
+    limit: usize,
 }
 
 enum ResolvedRunRequest {
     Single(Id<RunMarker>),
     Multiple(ResolvedRunRequestMultiple),
+    Runner(ResolvedRunRequestRunner),
+    Search(RunSearch, usize),
 }
 
 impl ResolvedRun {
+    /// This run's time by `method`, in milliseconds
+    pub fn time_for(&self, method: TimingMethod) -> i64 {
+        match method {
+            TimingMethod::Rta => self.time,
+            TimingMethod::Igt => self.igt,
+            TimingMethod::Lrt => self.lrt,
+        }
+    }
+
+    /// Index of the rank column when fetching a runner's runs, which comes after the game
+    const RUNNER_RANK_COLUMN: usize = 70;
+
     pub async fn from_db(
         state: &AppState,
         run_id: Id<RunMarker>,
@@ -174,43 +466,188 @@ impl ResolvedRun {
         if resolveds.len() > 1 {
             return Err(Error::TooManyRows(1, resolveds.len()));
         }
-        Ok(resolveds.into_iter().next())
+        Ok(resolveds.into_iter().next().map(|(run, _rank)| run))
     }
 
+    /// Fetch a page of the runs of `game` with `status` which match `filter`
     pub async fn fetch_leaderboard(
         state: &AppState,
         game: Arc<Game>,
         status: RunStatus,
-        maybe_category: Option<Id<CategoryMarker>>,
+        filter: LeaderboardFilter,
         sort_by: SortBy,
         limit: usize,
-        page: usize,
+        cursor: Option<PageCursor>,
     ) -> Result<ResolvedRunResult, Error> {
-        let request = ResolvedRunRequest::Multiple(ResolvedRunRequestMultiple {
+        let request = ResolvedRunRequestMultiple {
             game,
             status,
-            maybe_category,
+            filter,
             sort_by,
             limit,
-            page,
-        });
-        let mut resolveds = Self::run_fetcher(&state.postgres, request).await?;
-        let has_next = resolveds.len() > limit;
+            cursor,
+        };
+        Self::leaderboard_page(&state.postgres, request).await
+    }
+
+    async fn leaderboard_page(
+        pg: &PgPool,
+        request: ResolvedRunRequestMultiple,
+    ) -> Result<ResolvedRunResult, Error> {
+        let (limit, cursor) = (request.limit, request.cursor);
+        let mut resolveds: Vec<ResolvedRun> =
+            Self::run_fetcher(pg, ResolvedRunRequest::Multiple(request.clone()))
+                .await?
+                .into_iter()
+                .map(|(run, _rank)| run)
+                .collect();
+        let has_more = resolveds.len() > limit;
         resolveds.truncate(limit);
+        // pages before the cursor are fetched backwards, starting from the cursor
+        let (has_previous, has_next) = match cursor {
+            None => (false, has_more),
+            Some(PageCursor::After(_)) => (true, has_more),
+            Some(PageCursor::Before(_)) => {
+                resolveds.reverse();
+                (has_more, true)
+            }
+        };
+        let (beaten_by, preceded_by) = match resolveds.first() {
+            Some(first) => Self::count_ahead(pg, &request, first.id).await?,
+            None => (0, 0),
+        };
+        let sort_by = request.sort_by;
+        let mut previous: Option<(i64, i64)> = None;
+        let mut ranked = Vec::with_capacity(resolveds.len());
+        for (index, run) in resolveds.into_iter().enumerate() {
+            // runs which tie share the rank of the first of them, and the run after them is
+            // ranked by its position, so only the first run on the page has to be counted
+            let rank = match (sort_by.run_value(&run), previous) {
+                (None, _) => 0,
+                (Some(_), None) => beaten_by + 1,
+                (Some(value), Some((previous_value, previous_rank))) if value == previous_value => {
+                    previous_rank
+                }
+                (Some(_), Some(_)) => preceded_by + i64::try_from(index)? + 1,
+            };
+            previous = sort_by.run_value(&run).map(|value| (value, rank));
+            ranked.push(RankedRun { rank, run });
+        }
         Ok(ResolvedRunResult {
-            resolveds,
+            ranked,
             has_next,
+            has_previous,
         })
     }
 
+    /// How many runs on the leaderboard `request` asks for beat the run `first`,
+    /// and how many come before it, including the ones it ties with
+    async fn count_ahead(
+        pg: &PgPool,
+        request: &ResolvedRunRequestMultiple,
+        first: Id<RunMarker>,
+    ) -> Result<(i64, i64), Error> {
+        let sort_by = request.sort_by;
+        let (Some(beats), Some(ties_or_beats)) = (
+            sort_by.beats("better", "first"),
+            sort_by.ties_or_beats("better", "first"),
+        ) else {
+            return Ok((0, 0));
+        };
+        let mut query = sqlx::QueryBuilder::new(format!(
+            concat!(
+                "SELECT COUNT(*) FILTER (WHERE {beats}), COUNT(*) ",
+                "FROM runs AS first, runs AS better WHERE first.id = "
+            ),
+            beats = beats
+        ));
+        query.push_bind(first);
+        query.push(" AND ");
+        Self::push_leaderboard_filter(&mut query, "better", request);
+        query.push(format!(
+            concat!(
+                " AND {ties_or_beats} AND ({beats} OR (better.created_at, better.id) ",
+                "< (first.created_at, first.id))"
+            ),
+            ties_or_beats = ties_or_beats,
+            beats = beats
+        ));
+        let row = query.build().fetch_one(pg).await?;
+        Ok((row.try_get(0)?, row.try_get(1)?))
+    }
+
+    /// Every current personal best of `user` in every game, along with its rank on the
+    /// leaderboard for its category, level and variable values
+    pub async fn fetch_personal_bests(
+        state: &AppState,
+        user: Id<UserMarker>,
+        limit: usize,
+    ) -> Result<Vec<RankedRun>, Error> {
+        let request = ResolvedRunRequest::Runner(ResolvedRunRequestRunner {
+            user,
+            status: RunStatus::Verified,
+            personal_bests_only: true,
+            limit,
+        });
+        let resolveds = Self::run_fetcher(&state.postgres, request).await?;
+        Ok(resolveds
+            .into_iter()
+            .map(|(run, rank)| RankedRun {
+                rank: rank.unwrap_or_default(),
+                run,
+            })
+            .collect())
+    }
+
+    /// The newest runs with `status` which `user` submitted or took part in, in every game
+    pub async fn fetch_runner_submissions(
+        state: &AppState,
+        user: Id<UserMarker>,
+        status: RunStatus,
+        limit: usize,
+    ) -> Result<Vec<ResolvedRun>, Error> {
+        let request = ResolvedRunRequest::Runner(ResolvedRunRequestRunner {
+            user,
+            status,
+            personal_bests_only: false,
+            limit,
+        });
+        let resolveds = Self::run_fetcher(&state.postgres, request).await?;
+        Ok(resolveds.into_iter().map(|(run, _rank)| run).collect())
+    }
+
+    /// Runs matching `search`, along with the run the next page starts after if there is one
+    pub async fn search(
+        state: &AppState,
+        search: RunSearch,
+        limit: usize,
+    ) -> Result<(Vec<ResolvedRun>, Option<Id<RunMarker>>), Error> {
+        let request = ResolvedRunRequest::Search(search, limit);
+        let mut runs: Vec<ResolvedRun> = Self::run_fetcher(&state.postgres, request)
+            .await?
+            .into_iter()
+            .map(|(run, _rank)| run)
+            .collect();
+        let next = if runs.len() > limit {
+            runs.truncate(limit);
+            runs.last().map(|run| run.id)
+        } else {
+            None
+        };
+        Ok((runs, next))
+    }
+
+    /// Runs are returned with their rank when fetching a runner's runs
     async fn run_fetcher(
         pg: &PgPool,
         request: ResolvedRunRequest,
-    ) -> Result<Vec<ResolvedRun>, Error> {
+    ) -> Result<Vec<(ResolvedRun, Option<i64>)>, Error> {
         let mut query = sqlx::QueryBuilder::new(
             r#"SELECT runs.id, runs.game, runs.category, runs.video,
-            runs.description, runs.score, runs.time, runs.status,
-            runs.created_at, runs.edited_at, runs.verified_at, runs.flags,
+            runs.description, runs.score, runs.time, runs.igt, runs.lrt,
+            runs.status,
+            runs.created_at, runs.edited_at, runs.verified_at,
+            runs.obsoleted, runs.flags,
             verifier.id, verifier.username, verifier.stylesheet,
             verifier.biography, verifier.pfp, verifier.banner,
             verifier.admin, verifier.created_at, verifier.flags, 
@@ -220,10 +657,37 @@ impl ResolvedRun {
             submitter.admin, submitter.created_at, submitter.flags,
             submitter.language,
             category.game, category.name, category.description,
-            category.rules, category.scoreboard, category.flags "#,
+            category.rules, category.scoreboard, category.per_level,
+            category.timing_methods, category.primary_timing, category.players,
+            category.flags,
+            ARRAY(
+                SELECT run_variables.value FROM run_variables
+                WHERE run_variables.run = runs.id
+                ORDER BY run_variables.variable
+            ),
+            level.id, level.game, level.name, level.flags,
+            runs.review_note,
+            ARRAY(
+                SELECT run_participants.user_id FROM run_participants
+                WHERE run_participants.run = runs.id
+                ORDER BY run_participants.position
+            ),
+            ARRAY(
+                SELECT COALESCE(participant.username, run_participants.guest_name)
+                FROM run_participants
+                LEFT JOIN users AS participant ON run_participants.user_id = participant.id
+                WHERE run_participants.run = runs.id
+                ORDER BY run_participants.position
+            ),
+            platform.id, platform.game, platform.name, platform.flags,
+            runs.emulated, category.lower_score_wins, category.score_precision,
+            category.score_unit "#,
         );
-        // getting a single run requires us to get game data as well
-        if let ResolvedRunRequest::Single(_) = request {
+        // runs from more than one game require us to get game data as well
+        if let ResolvedRunRequest::Single(_)
+        | ResolvedRunRequest::Runner(_)
+        | ResolvedRunRequest::Search(..) = request
+        {
             query.push(concat!(
                 ',',
                 "game.id, game.name, game.description, game.slug, game.url,",
@@ -231,12 +695,68 @@ impl ResolvedRun {
                 "game.default_category, game.flags ",
             ));
         }
+        // a runner's runs are spread across leaderboards, so each is ranked on its own one,
+        // where the rank is one more than the number of personal bests which beat it
+        if let ResolvedRunRequest::Runner(_) = request {
+            query.push(concat!(
+                ", (SELECT COUNT(*) + 1 FROM runs AS better ",
+                "WHERE better.category = runs.category AND better.status = 1 ",
+                "AND NOT better.obsoleted ",
+                "AND better.level IS NOT DISTINCT FROM runs.level ",
+                "AND ARRAY(SELECT value FROM run_variables ",
+                "WHERE run = better.id ORDER BY variable) = ",
+                "ARRAY(SELECT value FROM run_variables ",
+                "WHERE run = runs.id ORDER BY variable) ",
+                "AND CASE WHEN category.scoreboard AND category.lower_score_wins ",
+                "THEN better.score ",
+                "WHEN category.scoreboard THEN -better.score ",
+                "WHEN category.primary_timing = 1 THEN better.igt ",
+                "WHEN category.primary_timing = 2 THEN better.lrt ",
+                "ELSE better.time END ",
+                "< CASE WHEN category.scoreboard AND category.lower_score_wins ",
+                "THEN runs.score ",
+                "WHEN category.scoreboard THEN -runs.score ",
+                "WHEN category.primary_timing = 1 THEN runs.igt ",
+                "WHEN category.primary_timing = 2 THEN runs.lrt ",
+                "ELSE runs.time END) ",
+            ));
+        }
         query.push(concat!(
             "FROM runs ",
             "LEFT JOIN users as verifier ON runs.verifier = verifier.id ",
             "JOIN users as submitter ON runs.submitter = submitter.id ",
             "JOIN categories as category ON runs.category = category.id ",
+            "LEFT JOIN levels as level ON runs.level = level.id ",
+            "LEFT JOIN platforms as platform ON runs.platform = platform.id ",
         ));
+        if let ResolvedRunRequest::Runner(runner_request) = &request {
+            query.push(concat!(
+                "JOIN games as game ON runs.game = game.id ",
+                "WHERE (runs.submitter = "
+            ));
+            query.push_bind(runner_request.user);
+            query.push(concat!(
+                " OR EXISTS (SELECT 1 FROM run_participants ",
+                "WHERE run_participants.run = runs.id AND run_participants.user_id = "
+            ));
+            query.push_bind(runner_request.user);
+            query.push(")) AND runs.status = ");
+            query.push_bind(runner_request.status as i16);
+            if runner_request.personal_bests_only {
+                query.push(concat!(
+                    " AND NOT runs.obsoleted ",
+                    "ORDER BY game.name ASC, category.name ASC, level.name ASC NULLS FIRST, ",
+                    "runs.id ASC"
+                ));
+            } else {
+                query.push(" ORDER BY runs.created_at DESC, runs.id DESC");
+            }
+            query.push(" LIMIT ");
+            query.push_bind(i64::try_from(runner_request.limit)?);
+        }
+        if let ResolvedRunRequest::Search(search, limit) = &request {
+            Self::push_search(&mut query, search, *limit)?;
+        }
         if let ResolvedRunRequest::Single(id) = request {
             query.push(concat!(
                 "JOIN games as game ON runs.game = game.id ",
@@ -246,42 +766,257 @@ impl ResolvedRun {
         }
         if let ResolvedRunRequest::Multiple(multi_request) = &request {
             let s_limit: i64 = multi_request.limit.try_into()?;
-            let page: i64 = multi_request.page.try_into()?;
-            query.push("WHERE runs.game = ");
-            query.push_bind(multi_request.game.id.get());
-            if let Some(category) = multi_request.maybe_category {
-                query.push(" AND category = ");
-                query.push_bind(category);
+            query.push("WHERE ");
+            Self::push_leaderboard_filter(&mut query, "runs", multi_request);
+            // pages before the cursor are fetched backwards, starting from the cursor
+            let sort_by = multi_request.sort_by;
+            let backwards = matches!(multi_request.cursor, Some(PageCursor::Before(_)));
+            let column_descending = sort_by.descending() != backwards;
+            let tie_descending = sort_by.newest_first() != backwards;
+            let column = sort_by.column("runs");
+            if let Some(PageCursor::After(cursor) | PageCursor::Before(cursor)) =
+                multi_request.cursor
+            {
+                let column_comparison = if column_descending { "<" } else { ">" };
+                let tie_comparison = if tie_descending { "<" } else { ">" };
+                let cursor_column = format!(
+                    "(SELECT {} FROM runs AS cursor WHERE cursor.id = ",
+                    sort_by.column("cursor")
+                );
+                // the first condition lets the sort column's index start from the cursor
+                query.push(format!(
+                    " AND {column} {column_comparison}= {cursor_column}"
+                ));
+                query.push_bind(cursor);
+                query.push(format!(
+                    ") AND ({column} {column_comparison} {cursor_column}"
+                ));
+                query.push_bind(cursor);
+                query.push(format!(
+                    concat!(
+                        ") OR (runs.created_at, runs.id) {} (SELECT cursor.created_at, ",
+                        "cursor.id FROM runs AS cursor WHERE cursor.id = "
+                    ),
+                    tie_comparison
+                ));
+                query.push_bind(cursor);
+                query.push("))");
             }
-            query.push(" AND status = ");
-            query.push_bind(multi_request.status as i16);
-            match multi_request.sort_by {
-                SortBy::Score => query.push(" ORDER BY score DESC "),
-                SortBy::Time => query.push(" ORDER BY time ASC "),
-                SortBy::SubmissionDate(DateSort::Newest) => {
-                    query.push(" ORDER BY runs.created_at DESC ")
-                }
-                SortBy::SubmissionDate(DateSort::Oldest) => {
-                    query.push(" ORDER BY runs.created_at ASC ")
-                }
-            };
+            // ties are broken by submission date, then by id
+            let direction = |descending| if descending { "DESC" } else { "ASC" };
+            query.push(format!(
+                " ORDER BY {column} {}, runs.created_at {tie}, runs.id {tie}",
+                direction(column_descending),
+                tie = direction(tie_descending),
+            ));
             query.push(" LIMIT ");
             query.push_bind(s_limit + 1);
-            query.push(" OFFSET ");
-            query.push_bind(s_limit * page);
         }
         let rows = query.build().fetch_all(pg).await?;
         let mut resolveds = Vec::with_capacity(rows.len());
-        let optional_game = match request {
-            ResolvedRunRequest::Multiple(request) => Some(request.game),
-            ResolvedRunRequest::Single(_) => None,
+        let (optional_game, rank_column) = match request {
+            ResolvedRunRequest::Multiple(request) => (Some(request.game), None),
+            ResolvedRunRequest::Runner(_) => (None, Some(Self::RUNNER_RANK_COLUMN)),
+            ResolvedRunRequest::Single(_) | ResolvedRunRequest::Search(..) => (None, None),
         };
         for row in rows {
-            resolveds.push(Self::row_to_rcat(&row, optional_game.clone())?);
+            let rank = rank_column.map(|column| row.try_get(column)).transpose()?;
+            resolveds.push((Self::row_to_rcat(&row, optional_game.clone())?, rank));
         }
         Ok(resolveds)
     }
 
+    /// Push the conditions runs in `table` have to meet to be on the leaderboard `request` asks for
+    fn push_leaderboard_filter(
+        query: &mut sqlx::QueryBuilder<'_, sqlx::Postgres>,
+        table: &str,
+        request: &ResolvedRunRequestMultiple,
+    ) {
+        let filter = &request.filter;
+        query.push(format!("{table}.game = "));
+        query.push_bind(request.game.id.get());
+        query.push(format!(" AND {table}.status = "));
+        query.push_bind(request.status as i16);
+        Self::push_board_conditions(query, table, filter);
+        for sort_by in std::iter::once(request.sort_by).chain(filter.records_by) {
+            if let Some(recorded) = sort_by.recorded(table) {
+                query.push(format!(" AND {recorded}"));
+            }
+        }
+        if filter.personal_bests_only {
+            Self::push_personal_best(query, table, request);
+        }
+        if let Some(condition) = filter
+            .records_by
+            .and_then(|records_by| records_by.ties_or_beats("earlier", table))
+        {
+            query.push(format!(
+                concat!(
+                    " AND NOT EXISTS (SELECT 1 FROM runs AS earlier ",
+                    "WHERE earlier.category = {table}.category ",
+                    "AND earlier.level IS NOT DISTINCT FROM {table}.level ",
+                    "AND (earlier.created_at, earlier.id) < ({table}.created_at, {table}.id) ",
+                    "AND earlier.status = "
+                ),
+                table = table
+            ));
+            query.push_bind(request.status as i16);
+            Self::push_board_conditions(query, "earlier", filter);
+            if let Some(recorded) = filter.records_by.and_then(|by| by.recorded("earlier")) {
+                query.push(format!(" AND {recorded}"));
+            }
+            query.push(format!(" AND {condition})"));
+        }
+    }
+
+    /// Push the condition that the run in `table` is its runners' best on the leaderboard
+    fn push_personal_best(
+        query: &mut sqlx::QueryBuilder<'_, sqlx::Postgres>,
+        table: &str,
+        request: &ResolvedRunRequestMultiple,
+    ) {
+        let filter = &request.filter;
+        let sort_by = request.sort_by;
+        let by_platform = filter.platform.is_some() || filter.emulated.is_some();
+        if !by_platform || sort_by.beats("pb", table).is_none() {
+            query.push(format!(" AND NOT {table}.obsoleted"));
+            return;
+        }
+        // runs are only obsoleted by their runners' best on any platform,
+        // so the best on this one is picked out of the runs on it
+        let partition = format!("{}, pb.level, {}", team("pb"), variable_values("pb"));
+        query.push(format!(
+            " AND {table}.id IN (SELECT DISTINCT ON ({partition}) pb.id FROM runs AS pb WHERE pb.game = "
+        ));
+        query.push_bind(request.game.id.get());
+        query.push(" AND pb.status = ");
+        query.push_bind(request.status as i16);
+        Self::push_board_conditions(query, "pb", filter);
+        if let Some(recorded) = sort_by.recorded("pb") {
+            query.push(format!(" AND {recorded}"));
+        }
+        let direction = if sort_by.descending() { "DESC" } else { "ASC" };
+        query.push(format!(
+            " ORDER BY {partition}, {} {direction}, pb.created_at ASC, pb.id ASC)",
+            sort_by.column("pb")
+        ));
+    }
+
+    /// Push the conditions of `filter` which pick out a board, applied to runs in `table`
+    fn push_board_conditions(
+        query: &mut sqlx::QueryBuilder<'_, sqlx::Postgres>,
+        table: &str,
+        filter: &LeaderboardFilter,
+    ) {
+        if let Some(category) = filter.category {
+            query.push(format!(" AND {table}.category = "));
+            query.push_bind(category);
+        }
+        if let Some(level) = filter.level {
+            query.push(format!(" AND {table}.level = "));
+            query.push_bind(level);
+        }
+        if let Some(platform) = filter.platform {
+            query.push(format!(" AND {table}.platform = "));
+            query.push_bind(platform);
+        }
+        if let Some(emulated) = filter.emulated {
+            query.push(format!(" AND {table}.emulated = "));
+            query.push_bind(emulated);
+        }
+        for value in &filter.variables {
+            query.push(format!(
+                concat!(
+                    " AND EXISTS (SELECT 1 FROM run_variables ",
+                    "WHERE run_variables.run = {}.id AND run_variables.value = "
+                ),
+                table
+            ));
+            query.push_bind(*value);
+            query.push(")");
+        }
+    }
+
+    fn push_search(
+        query: &mut sqlx::QueryBuilder<'_, sqlx::Postgres>,
+        search: &RunSearch,
+        limit: usize,
+    ) -> Result<(), Error> {
+        query.push("JOIN games as game ON runs.game = game.id WHERE TRUE");
+        if let Some(game) = search.game {
+            query.push(" AND runs.game = ");
+            query.push_bind(game);
+        }
+        if let Some(category) = search.category {
+            query.push(" AND runs.category = ");
+            query.push_bind(category);
+        }
+        if let Some(runner) = search.runner {
+            query.push(" AND (runs.submitter = ");
+            query.push_bind(runner);
+            query.push(concat!(
+                " OR EXISTS (SELECT 1 FROM run_participants ",
+                "WHERE run_participants.run = runs.id AND run_participants.user_id = "
+            ));
+            query.push_bind(runner);
+            query.push("))");
+        }
+        if let Some(status) = search.status {
+            query.push(" AND runs.status = ");
+            query.push_bind(status as i16);
+        }
+        if let Some(after) = search.submitted_after {
+            query.push(" AND runs.created_at >= ");
+            query.push_bind(after);
+        }
+        if let Some(before) = search.submitted_before {
+            query.push(" AND runs.created_at < ");
+            query.push_bind(before);
+        }
+        let sort = search.sort;
+        let column = sort.column(search.timing).ok_or_else(|| {
+            Error::CustomFormValidation("sorting by time needs a category".to_string())
+        })?;
+        let time = match search.timing {
+            Some(method) => format!("runs.{}", method.column()),
+            None => primary_time("runs", "category"),
+        };
+        let by_time = matches!(sort, SearchSort::Fastest | SearchSort::Slowest);
+        if by_time || search.min_time.is_some() || search.max_time.is_some() {
+            // 0 means the run has no time by that method
+            query.push(format!(" AND NOT category.scoreboard AND {time} <> 0"));
+        }
+        if let Some(min_time) = search.min_time {
+            query.push(format!(" AND {time} >= "));
+            query.push_bind(min_time);
+        }
+        if let Some(max_time) = search.max_time {
+            query.push(format!(" AND {time} <= "));
+            query.push_bind(max_time);
+        }
+        if let Some(condition) = sort.condition() {
+            query.push(format!(" AND {condition}"));
+        }
+        let (direction, comparison) = if sort.descending() {
+            ("DESC", "<")
+        } else {
+            ("ASC", ">")
+        };
+        if let Some(after) = search.after {
+            query.push(format!(
+                " AND (runs.{column}, runs.id) {comparison} \
+                (SELECT cursor.{column}, cursor.id FROM runs AS cursor WHERE cursor.id = "
+            ));
+            query.push_bind(after);
+            query.push(")");
+        }
+        query.push(format!(
+            " ORDER BY runs.{column} {direction}, runs.id {direction} LIMIT "
+        ));
+        query.push_bind(i64::try_from(limit)? + 1);
+        Ok(())
+    }
+
     /// If `optional_game` is set, it will use the passed-in game. Otherwise,
     /// it will try to get it from the `game.*` fields
     fn row_to_rcat(row: &PgRow, optional_game: Option<Arc<Game>>) -> Result<ResolvedRun, Error> {
@@ -296,45 +1031,74 @@ impl ResolvedRun {
         let description: String = row.try_get(4)?;
         let score: i64 = row.try_get(5)?;
         let time: i64 = row.try_get(6)?;
-        let status_num: i16 = row.try_get(7)?;
-        let created_at: NaiveDateTime = row.try_get(8)?;
-        let edited_at: Option<NaiveDateTime> = row.try_get(9)?;
-        let verified_at: Option<NaiveDateTime> = row.try_get(10)?;
-        let flags: i64 = row.try_get(11)?;
+        let igt: i64 = row.try_get(7)?;
+        let lrt: i64 = row.try_get(8)?;
+        let status_num: i16 = row.try_get(9)?;
+        let created_at: NaiveDateTime = row.try_get(10)?;
+        let edited_at: Option<NaiveDateTime> = row.try_get(11)?;
+        let verified_at: Option<NaiveDateTime> = row.try_get(12)?;
+        let obsoleted: bool = row.try_get(13)?;
+        let flags: i64 = row.try_get(14)?;
 
         let status = RunStatus::from(status_num);
         if game_id != game.id {
             return Err(Error::RowDoesNotMatchInputGame);
         }
 
-        let verifier_id: Option<Id<UserMarker>> = row.try_get(12)?;
-        let verifier_name: Option<String> = row.try_get(13)?;
-        let verifier_stylesheet: Option<bool> = row.try_get(14)?;
-        let verifier_bio: Option<String> = row.try_get(15)?;
-        let verifier_pfp: Option<bool> = row.try_get(16)?;
-        let verifier_banner: Option<bool> = row.try_get(17)?;
-        let verifier_admin: Option<bool> = row.try_get(18)?;
-        let verifier_created_at: Option<NaiveDateTime> = row.try_get(19)?;
-        let verifier_flags: Option<i64> = row.try_get(20)?;
-        let verifier_language: Option<String> = row.try_get(21)?;
-
-        let submitter_id: Id<UserMarker> = row.try_get(22)?;
-        let submitter_name: String = row.try_get(23)?;
-        let submitter_stylesheet: bool = row.try_get(24)?;
-        let submitter_bio: String = row.try_get(25)?;
-        let submitter_pfp: bool = row.try_get(26)?;
-        let submitter_banner: bool = row.try_get(27)?;
-        let submitter_admin: bool = row.try_get(28)?;
-        let submitter_created_at: NaiveDateTime = row.try_get(29)?;
-        let submitter_flags: i64 = row.try_get(30)?;
-        let submitter_language: Option<String> = row.try_get(31)?;
-
-        let category_game_id: Id<GameMarker> = row.try_get(32)?;
-        let category_name: String = row.try_get(33)?;
-        let category_description: String = row.try_get(34)?;
-        let category_rules: String = row.try_get(35)?;
-        let category_scoreboard: bool = row.try_get(36)?;
-        let category_flags: i64 = row.try_get(37)?;
+        let verifier_id: Option<Id<UserMarker>> = row.try_get(15)?;
+        let verifier_name: Option<String> = row.try_get(16)?;
+        let verifier_stylesheet: Option<bool> = row.try_get(17)?;
+        let verifier_bio: Option<String> = row.try_get(18)?;
+        let verifier_pfp: Option<bool> = row.try_get(19)?;
+        let verifier_banner: Option<bool> = row.try_get(20)?;
+        let verifier_admin: Option<bool> = row.try_get(21)?;
+        let verifier_created_at: Option<NaiveDateTime> = row.try_get(22)?;
+        let verifier_flags: Option<i64> = row.try_get(23)?;
+        let verifier_language: Option<String> = row.try_get(24)?;
+
+        let submitter_id: Id<UserMarker> = row.try_get(25)?;
+        let submitter_name: String = row.try_get(26)?;
+        let submitter_stylesheet: bool = row.try_get(27)?;
+        let submitter_bio: String = row.try_get(28)?;
+        let submitter_pfp: bool = row.try_get(29)?;
+        let submitter_banner: bool = row.try_get(30)?;
+        let submitter_admin: bool = row.try_get(31)?;
+        let submitter_created_at: NaiveDateTime = row.try_get(32)?;
+        let submitter_flags: i64 = row.try_get(33)?;
+        let submitter_language: Option<String> = row.try_get(34)?;
+
+        let category_game_id: Id<GameMarker> = row.try_get(35)?;
+        let category_name: String = row.try_get(36)?;
+        let category_description: String = row.try_get(37)?;
+        let category_rules: String = row.try_get(38)?;
+        let category_scoreboard: bool = row.try_get(39)?;
+        let category_per_level: bool = row.try_get(40)?;
+        let category_timing_methods: i16 = row.try_get(41)?;
+        let category_primary_timing: i16 = row.try_get(42)?;
+        let category_players: i16 = row.try_get(43)?;
+        let category_flags: i64 = row.try_get(44)?;
+
+        let variables: Vec<i64> = row.try_get(45)?;
+
+        let level_id: Option<Id<LevelMarker>> = row.try_get(46)?;
+        let level_game: Option<Id<GameMarker>> = row.try_get(47)?;
+        let level_name: Option<String> = row.try_get(48)?;
+        let level_flags: Option<i64> = row.try_get(49)?;
+
+        let review_note: Option<String> = row.try_get(50)?;
+
+        let participant_users: Vec<Option<i64>> = row.try_get(51)?;
+        let participant_names: Vec<String> = row.try_get(52)?;
+
+        let platform_id: Option<Id<PlatformMarker>> = row.try_get(53)?;
+        let platform_game: Option<Id<GameMarker>> = row.try_get(54)?;
+        let platform_name: Option<String> = row.try_get(55)?;
+        let platform_flags: Option<i64> = row.try_get(56)?;
+        let emulated: bool = row.try_get(57)?;
+
+        let category_lower_score_wins: bool = row.try_get(58)?;
+        let category_score_precision: i16 = row.try_get(59)?;
+        let category_score_unit: String = row.try_get(60)?;
 
         let verifier = User::collapse_optional(
             verifier_id,
@@ -371,8 +1135,18 @@ impl ResolvedRun {
             description: category_description,
             rules: category_rules,
             scoreboard: category_scoreboard,
+            per_level: category_per_level,
+            timing_methods: category_timing_methods.into(),
+            primary_timing: category_primary_timing.into(),
+            players: category_players,
+            lower_score_wins: category_lower_score_wins,
+            score_precision: category_score_precision,
+            score_unit: category_score_unit,
             flags: category_flags,
         };
+        let level = Level::collapse_optional(level_id, level_game, level_name, level_flags);
+        let platform =
+            Platform::collapse_optional(platform_id, platform_game, platform_name, platform_flags);
         let rr = ResolvedRun {
             id,
             game,
@@ -383,25 +1157,41 @@ impl ResolvedRun {
             description,
             score,
             time,
+            igt,
+            lrt,
             status,
             created_at,
             edited_at,
             verified_at,
+            obsoleted,
+            review_note,
             flags,
+            variables: variables.into_iter().map(Id::new).collect(),
+            participants: participant_users
+                .into_iter()
+                .zip(participant_names)
+                .map(|(user, name)| Participant {
+                    user: user.map(Id::new),
+                    name,
+                })
+                .collect(),
+            level,
+            platform,
+            emulated,
         };
         Ok(rr)
     }
 
     fn get_game_from_row(row: &PgRow) -> Result<Arc<Game>, Error> {
-        let id: Id<GameMarker> = row.try_get(38)?;
-        let name: String = row.try_get(39)?;
-        let description: String = row.try_get(40)?;
-        let slug: String = row.try_get(41)?;
-        let url: String = row.try_get(42)?;
-        let banner: bool = row.try_get(43)?;
-        let cover_art: bool = row.try_get(44)?;
-        let default_category: Id<CategoryMarker> = row.try_get(45)?;
-        let flags: i64 = row.try_get(46)?;
+        let id: Id<GameMarker> = row.try_get(61)?;
+        let name: String = row.try_get(62)?;
+        let description: String = row.try_get(63)?;
+        let slug: String = row.try_get(64)?;
+        let url: String = row.try_get(65)?;
+        let banner: bool = row.try_get(66)?;
+        let cover_art: bool = row.try_get(67)?;
+        let default_category: Id<CategoryMarker> = row.try_get(68)?;
+        let flags: i64 = row.try_get(69)?;
         Ok(Arc::new(Game {
             id,
             name,
@@ -421,7 +1211,7 @@ mod test {
     use sqlx::PgPool;
 
     use super::*;
-    use crate::test::util::*;
+    use crate::{model::VariableValue, test::util::*};
     #[sqlx::test(fixtures(path = "../fixtures", scripts("add_game", "add_user", "add_run")))]
     async fn get_run(db: PgPool) {
         let request = ResolvedRunRequest::Single(Id::new(1));
@@ -436,13 +1226,410 @@ mod test {
             description: "test run".to_string(),
             score: 0,
             time: 0,
+            igt: 0,
+            lrt: 0,
             status: RunStatus::Pending,
             created_at: NaiveDateTime::UNIX_EPOCH,
             edited_at: None,
             verified_at: None,
+            obsoleted: false,
+            review_note: None,
             flags: 0,
+            variables: Vec::new(),
+            participants: Vec::new(),
+            level: None,
+            platform: None,
+            emulated: false,
         };
         assert_eq!(runs.len(), 1);
-        assert_eq!(runs[0], expected_run);
+        assert_eq!(runs[0], (expected_run, None));
+    }
+
+    #[sqlx::test(fixtures(path = "../fixtures", scripts("add_game", "add_user")))]
+    async fn ranks_share_ties(db: PgPool) {
+        query!(
+            "INSERT INTO runs
+            (id, game, category, submitter, video, description, score, time,
+            verifier, status, created_at, verified_at)
+            VALUES
+            (10, 1, 1, 1, '', '', 0, 1000, 1, 1, to_timestamp(10), NOW()),
+            (11, 1, 1, 1, '', '', 0, 2000, 1, 1, to_timestamp(20), NOW()),
+            (12, 1, 1, 1, '', '', 0, 2000, 1, 1, to_timestamp(30), NOW()),
+            (13, 1, 1, 1, '', '', 0, 3000, 1, 1, to_timestamp(40), NOW())"
+        )
+        .execute(&db)
+        .await
+        .unwrap();
+        let page = |cursor| {
+            let request = ResolvedRunRequestMultiple {
+                game: Arc::new(test_game()),
+                status: RunStatus::Verified,
+                filter: LeaderboardFilter::default(),
+                sort_by: SortBy::Time(TimingMethod::Rta),
+                limit: 2,
+                cursor: Some(cursor),
+            };
+            let db = db.clone();
+            async move {
+                let page = ResolvedRun::leaderboard_page(&db, request).await.unwrap();
+                page.ranked()
+                    .into_iter()
+                    .map(|ranked| (ranked.run.id.get(), ranked.rank))
+                    .collect::<Vec<(i64, i64)>>()
+            }
+        };
+        // the tie is split across pages, and keeps its rank on both
+        assert_eq!(
+            page(PageCursor::After(Id::new(11))).await,
+            vec![(12, 2), (13, 4)]
+        );
+        assert_eq!(
+            page(PageCursor::Before(Id::new(12))).await,
+            vec![(10, 1), (11, 2)]
+        );
+    }
+
+    #[sqlx::test(fixtures(path = "../fixtures", scripts("add_game", "add_user")))]
+    async fn unrecorded_times_are_left_out(db: PgPool) {
+        // the category switched to in-game time after run 10 was verified without one
+        query!("UPDATE categories SET timing_methods = 3, primary_timing = 1 WHERE id = 1")
+            .execute(&db)
+            .await
+            .unwrap();
+        query!(
+            "INSERT INTO runs
+            (id, game, category, submitter, video, description, score, time, igt,
+            verifier, status, created_at, verified_at)
+            VALUES
+            (10, 1, 1, 1, '', '', 0, 1000, 0, 1, 1, to_timestamp(10), NOW()),
+            (11, 1, 1, 1, '', '', 0, 3000, 2500, 1, 1, to_timestamp(20), NOW())"
+        )
+        .execute(&db)
+        .await
+        .unwrap();
+        Category::update_obsoleted(&db, Id::new(1), None)
+            .await
+            .unwrap();
+        let board = |filter| {
+            let request = ResolvedRunRequestMultiple {
+                game: Arc::new(test_game()),
+                status: RunStatus::Verified,
+                filter,
+                sort_by: SortBy::Time(TimingMethod::Igt),
+                limit: 10,
+                cursor: None,
+            };
+            let db = db.clone();
+            async move {
+                let page = ResolvedRun::leaderboard_page(&db, request).await.unwrap();
+                page.ranked()
+                    .into_iter()
+                    .map(|ranked| (ranked.run.id.get(), ranked.rank))
+                    .collect::<Vec<(i64, i64)>>()
+            }
+        };
+        let personal_bests = LeaderboardFilter {
+            personal_bests_only: true,
+            ..Default::default()
+        };
+        assert_eq!(board(personal_bests).await, vec![(11, 1)]);
+        assert_eq!(board(LeaderboardFilter::default()).await, vec![(11, 1)]);
+        let records = LeaderboardFilter {
+            records_by: Some(SortBy::Time(TimingMethod::Igt)),
+            ..Default::default()
+        };
+        assert_eq!(board(records).await, vec![(11, 1)]);
+    }
+
+    #[sqlx::test(fixtures(path = "../fixtures", scripts("add_game", "add_user")))]
+    async fn personal_bests_per_platform(db: PgPool) {
+        query!("INSERT INTO platforms (id, game, name) VALUES (1, 1, 'a'), (2, 1, 'b')")
+            .execute(&db)
+            .await
+            .unwrap();
+        // the runner's best is on platform 1, but they have a slower run on platform 2 too
+        query!(
+            "INSERT INTO runs
+            (id, game, category, submitter, video, description, score, time,
+            verifier, status, created_at, verified_at, platform)
+            VALUES
+            (10, 1, 1, 1, '', '', 0, 1000, 1, 1, to_timestamp(10), NOW(), 1),
+            (11, 1, 1, 1, '', '', 0, 2000, 1, 1, to_timestamp(20), NOW(), 2),
+            (12, 1, 1, 1, '', '', 0, 3000, 1, 1, to_timestamp(30), NOW(), 2)"
+        )
+        .execute(&db)
+        .await
+        .unwrap();
+        Category::update_obsoleted(&db, Id::new(1), None)
+            .await
+            .unwrap();
+        let board = |platform| {
+            let request = ResolvedRunRequestMultiple {
+                game: Arc::new(test_game()),
+                status: RunStatus::Verified,
+                filter: LeaderboardFilter {
+                    platform,
+                    personal_bests_only: true,
+                    ..Default::default()
+                },
+                sort_by: SortBy::Time(TimingMethod::Rta),
+                limit: 10,
+                cursor: None,
+            };
+            let db = db.clone();
+            async move {
+                let page = ResolvedRun::leaderboard_page(&db, request).await.unwrap();
+                page.ranked()
+                    .into_iter()
+                    .map(|ranked| (ranked.run.id.get(), ranked.rank))
+                    .collect::<Vec<(i64, i64)>>()
+            }
+        };
+        assert_eq!(board(None).await, vec![(10, 1)]);
+        assert_eq!(board(Some(Id::new(1))).await, vec![(10, 1)]);
+        assert_eq!(board(Some(Id::new(2))).await, vec![(11, 1)]);
+    }
+
+    #[sqlx::test(fixtures(path = "../fixtures", scripts("add_game", "add_user")))]
+    async fn runs_stay_on_boards_of_new_variables(db: PgPool) {
+        query!(
+            "INSERT INTO runs
+            (id, game, category, submitter, video, description, score, time,
+            verifier, status, created_at, verified_at)
+            VALUES (10, 1, 1, 1, '', '', 0, 1000, 1, 1, NOW(), NOW())"
+        )
+        .execute(&db)
+        .await
+        .unwrap();
+        query!("INSERT INTO variables (id, game, category, name) VALUES (1, 1, 1, 'difficulty')")
+            .execute(&db)
+            .await
+            .unwrap();
+        let mut conn = db.acquire().await.unwrap();
+        for name in ["easy", "hard"] {
+            VariableValue::create(&mut conn, Id::new(1), Id::new(1), Id::new(1), name)
+                .await
+                .unwrap();
+        }
+        let values: Vec<Id<VariableValueMarker>> =
+            query!("SELECT id FROM variable_values ORDER BY id")
+                .fetch_all(&db)
+                .await
+                .unwrap()
+                .into_iter()
+                .map(|row| Id::new(row.id))
+                .collect();
+        let board = |value| {
+            let request = ResolvedRunRequestMultiple {
+                game: Arc::new(test_game()),
+                status: RunStatus::Verified,
+                filter: LeaderboardFilter {
+                    variables: vec![value],
+                    personal_bests_only: true,
+                    ..Default::default()
+                },
+                sort_by: SortBy::Time(TimingMethod::Rta),
+                limit: 10,
+                cursor: None,
+            };
+            let db = db.clone();
+            async move {
+                let page = ResolvedRun::leaderboard_page(&db, request).await.unwrap();
+                page.ranked()
+                    .into_iter()
+                    .map(|ranked| ranked.run.id.get())
+                    .collect::<Vec<i64>>()
+            }
+        };
+        // the run was submitted before the variable, so it's on the first value's board
+        assert_eq!(board(values[0]).await, vec![10]);
+        assert_eq!(board(values[1]).await, Vec::<i64>::new());
+        let deleted =
+            VariableValue::delete(&mut conn, Id::new(1), Id::new(1), Id::new(1), values[0]).await;
+        assert!(matches!(deleted, Err(Error::VariableValueInUse)));
+        VariableValue::delete(&mut conn, Id::new(1), Id::new(1), Id::new(1), values[1])
+            .await
+            .unwrap();
+    }
+
+    #[sqlx::test(fixtures(path = "../fixtures", scripts("add_game", "add_user")))]
+    async fn newest_pages_are_unranked(db: PgPool) {
+        query!(
+            "INSERT INTO runs
+            (id, game, category, submitter, video, description, score, time,
+            verifier, status, created_at, verified_at)
+            VALUES
+            (10, 1, 1, 1, '', '', 0, 1000, NULL, 0, to_timestamp(10), NULL),
+            (11, 1, 1, 1, '', '', 0, 2000, NULL, 0, to_timestamp(20), NULL),
+            (12, 1, 1, 1, '', '', 0, 3000, NULL, 0, to_timestamp(20), NULL),
+            (13, 1, 1, 1, '', '', 0, 4000, NULL, 0, to_timestamp(30), NULL)"
+        )
+        .execute(&db)
+        .await
+        .unwrap();
+        let request = ResolvedRunRequest::Multiple(ResolvedRunRequestMultiple {
+            game: Arc::new(test_game()),
+            status: RunStatus::Pending,
+            filter: LeaderboardFilter::default(),
+            sort_by: SortBy::SubmissionDate(DateSort::Newest),
+            limit: 10,
+            cursor: Some(PageCursor::After(Id::new(12))),
+        });
+        let runs = ResolvedRun::run_fetcher(&db, request).await.unwrap();
+        let ids: Vec<(i64, Option<i64>)> = runs
+            .into_iter()
+            .map(|(run, rank)| (run.id.get(), rank))
+            .collect();
+        assert_eq!(ids, vec![(11, None), (10, None)]);
+    }
+
+    #[sqlx::test(fixtures(path = "../fixtures", scripts("add_game", "add_user", "add_run")))]
+    async fn get_run_participants(db: PgPool) {
+        query!(
+            "INSERT INTO run_participants (run, position, user_id, guest_name)
+            VALUES (1, 2, NULL, 'guest'), (1, 1, 1, NULL)"
+        )
+        .execute(&db)
+        .await
+        .unwrap();
+        let request = ResolvedRunRequest::Single(Id::new(1));
+        let runs = ResolvedRun::run_fetcher(&db, request).await.unwrap();
+        assert_eq!(
+            runs[0].0.participants,
+            vec![
+                Participant {
+                    user: Some(Id::new(1)),
+                    name: "test".to_string(),
+                },
+                Participant {
+                    user: None,
+                    name: "guest".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[sqlx::test(fixtures(path = "../fixtures", scripts("add_game", "add_user")))]
+    async fn record_progression(db: PgPool) {
+        query!(
+            "INSERT INTO runs
+            (id, game, category, submitter, video, description, score, time,
+            verifier, status, created_at, verified_at)
+            VALUES
+            (10, 1, 1, 1, '', '', 0, 3000, 1, 1, to_timestamp(10), NOW()),
+            (11, 1, 1, 1, '', '', 0, 4000, 1, 1, to_timestamp(20), NOW()),
+            (12, 1, 1, 1, '', '', 0, 2000, 1, 1, to_timestamp(30), NOW()),
+            (13, 1, 1, 1, '', '', 0, 2000, 1, 1, to_timestamp(40), NOW()),
+            (14, 1, 1, 1, '', '', 0, 1000, NULL, 0, to_timestamp(50), NULL)"
+        )
+        .execute(&db)
+        .await
+        .unwrap();
+        let request = ResolvedRunRequest::Multiple(ResolvedRunRequestMultiple {
+            game: Arc::new(test_game()),
+            status: RunStatus::Verified,
+            filter: LeaderboardFilter {
+                records_by: Some(SortBy::Time(TimingMethod::Rta)),
+                ..Default::default()
+            },
+            sort_by: SortBy::SubmissionDate(DateSort::Oldest),
+            limit: 10,
+            cursor: None,
+        });
+        let runs = ResolvedRun::run_fetcher(&db, request).await.unwrap();
+        // slower runs, ties and runs which aren't verified never held the record
+        let ids: Vec<Id<RunMarker>> = runs.into_iter().map(|(run, _rank)| run.id).collect();
+        assert_eq!(ids, vec![Id::new(10), Id::new(12)]);
+    }
+
+    #[sqlx::test(fixtures(path = "../fixtures", scripts("add_game", "add_user")))]
+    async fn personal_best_ranks(db: PgPool) {
+        query!(
+            "INSERT INTO users
+            (id, email, username, password, biography, admin, stylesheet, banner, pfp,
+            flags, created_at)
+            VALUES (2, 'other@example.com', 'other', '', '', false, false, false, false, 0, NOW())"
+        )
+        .execute(&db)
+        .await
+        .unwrap();
+        query!(
+            "INSERT INTO runs
+            (id, game, category, submitter, video, description, score, time,
+            verifier, status, created_at, verified_at, obsoleted)
+            VALUES
+            (10, 1, 1, 2, '', '', 0, 1000, 1, 1, NOW(), NOW(), false),
+            (11, 1, 1, 1, '', '', 0, 2000, 1, 1, NOW(), NOW(), false),
+            (12, 1, 1, 1, '', '', 0, 3000, 1, 1, NOW(), NOW(), true)"
+        )
+        .execute(&db)
+        .await
+        .unwrap();
+        let request = ResolvedRunRequest::Runner(ResolvedRunRequestRunner {
+            user: Id::new(1),
+            status: RunStatus::Verified,
+            personal_bests_only: true,
+            limit: 10,
+        });
+        let runs = ResolvedRun::run_fetcher(&db, request).await.unwrap();
+        let ranks: Vec<(Id<RunMarker>, Option<i64>)> =
+            runs.into_iter().map(|(run, rank)| (run.id, rank)).collect();
+        assert_eq!(ranks, vec![(Id::new(11), Some(2))]);
+    }
+
+    #[sqlx::test(fixtures(path = "../fixtures", scripts("add_game", "add_user")))]
+    async fn search_pages(db: PgPool) {
+        query!(
+            "INSERT INTO runs
+            (id, game, category, submitter, video, description, score, time,
+            verifier, status, created_at, verified_at)
+            VALUES
+            (10, 1, 1, 1, '', '', 0, 3000, 1, 1, NOW(), NOW()),
+            (11, 1, 1, 1, '', '', 0, 1000, 1, 1, NOW(), NOW()),
+            (12, 1, 1, 1, '', '', 0, 2000, 1, 1, NOW(), NOW()),
+            (13, 1, 1, 1, '', '', 0, 1000, 1, 1, NOW(), NOW()),
+            (14, 1, 1, 1, '', '', 0, 500, NULL, 0, NOW(), NULL),
+            (15, 1, 1, 1, '', '', 0, 0, 1, 1, NOW(), NOW())"
+        )
+        .execute(&db)
+        .await
+        .unwrap();
+        let mut after = None;
+        let mut pages = Vec::new();
+        loop {
+            let request = ResolvedRunRequest::Search(
+                RunSearch {
+                    status: Some(RunStatus::Verified),
+                    max_time: Some(2500),
+                    timing: Some(TimingMethod::Rta),
+                    sort: SearchSort::Fastest,
+                    after,
+                    ..Default::default()
+                },
+                2,
+            );
+            let runs = ResolvedRun::run_fetcher(&db, request).await.unwrap();
+            let ids: Vec<Id<RunMarker>> = runs.iter().take(2).map(|(run, _)| run.id).collect();
+            after = ids.last().copied();
+            pages.push(ids);
+            if runs.len() <= 2 {
+                break;
+            }
+        }
+        // ties are broken by id, each page picks up right after the last one,
+        // and the run without a time isn't the fastest
+        assert_eq!(
+            pages,
+            vec![vec![Id::new(11), Id::new(13)], vec![Id::new(12)]]
+        );
+        // without a category there's no one time to sort by
+        let request = ResolvedRunRequest::Search(
+            RunSearch {
+                sort: SearchSort::Fastest,
+                ..Default::default()
+            },
+            2,
+        );
+        assert!(ResolvedRun::run_fetcher(&db, request).await.is_err());
     }
 }
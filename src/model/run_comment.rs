@@ -0,0 +1,87 @@
+use super::User;
+use crate::{
+    id::{GameMarker, Id, RunCommentMarker, RunMarker},
+    language::Language,
+    AppState, Error,
+};
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Hash, PartialEq, Eq, Clone)]
+pub struct RunComment {
+    pub id: Id<RunCommentMarker>,
+    pub parent: Id<RunMarker>,
+    pub game: Id<GameMarker>,
+    pub author: User,
+    pub content: String,
+    pub created_at: chrono::NaiveDateTime,
+    pub edited_at: Option<chrono::NaiveDateTime>,
+    /// Only shown to the submitter of the run and the game's team
+    pub private: bool,
+    pub flags: i64,
+}
+
+impl RunComment {
+    /// Fetch the comments on `run`, oldest first. Private comments are left out
+    /// unless `include_private` is set.
+    pub async fn from_db_run(
+        state: &AppState,
+        run: Id<RunMarker>,
+        include_private: bool,
+    ) -> Result<Vec<Self>, Error> {
+        let rows = query!(
+            "SELECT run_comments.id as run_comment_id,
+            run_comments.game as run_comment_game,
+            run_comments.content as run_comment_content,
+            run_comments.flags as run_comment_flags,
+            run_comments.created_at as run_comment_created_at,
+            run_comments.edited_at as run_comment_edited_at,
+            run_comments.private as run_comment_private,
+            users.id as user_id,
+            users.username as user_username,
+            users.biography as user_biography,
+            users.admin as user_admin,
+            users.stylesheet as user_stylesheet,
+            users.banner as user_banner,
+            users.pfp as user_pfp,
+            users.flags as user_flags,
+            users.created_at as user_created_at,
+            users.language as user_language
+            FROM run_comments
+            JOIN users ON run_comments.author = users.id
+            WHERE run_comments.parent = $1 AND ($2 OR NOT run_comments.private)
+            ORDER BY run_comments.created_at, run_comments.id",
+            run.get(),
+            include_private
+        )
+        .fetch_all(&state.postgres)
+        .await?;
+        let comments = rows
+            .into_iter()
+            .map(|row| Self {
+                id: Id::new(row.run_comment_id),
+                parent: run,
+                game: Id::new(row.run_comment_game),
+                author: User {
+                    id: Id::new(row.user_id),
+                    username: row.user_username,
+                    stylesheet: row.user_stylesheet,
+                    biography: row.user_biography,
+                    pfp: row.user_pfp,
+                    banner: row.user_banner,
+                    admin: row.user_admin,
+                    created_at: row.user_created_at,
+                    flags: row.user_flags,
+                    language: row
+                        .user_language
+                        .map(|v| Language::from_lang_code(&v))
+                        .unwrap_or_default(),
+                },
+                content: row.run_comment_content,
+                created_at: row.run_comment_created_at,
+                edited_at: row.run_comment_edited_at,
+                private: row.run_comment_private,
+                flags: row.run_comment_flags,
+            })
+            .collect();
+        Ok(comments)
+    }
+}
@@ -0,0 +1,309 @@
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use axum::{
+    extract::{ConnectInfo, FromRequestParts},
+    http::{header::USER_AGENT, request::Parts},
+};
+use rand::distributions::{Alphanumeric, DistString};
+use redis::AsyncCommands;
+
+use super::User;
+use crate::{
+    id::{Id, UserMarker},
+    util::AUTHTOKEN_TTL,
+    AppState, Error,
+};
+
+/// Logins are opaque tokens, stored at `token:user:{token}` and pointing at the user's id.
+/// Each user's tokens are also kept in a set, so they can all be ended at once, and what's
+/// known about each login is kept in a hash at `session:{token}`.
+pub struct Session;
+
+/// A login, as shown to the user it belongs to
+#[derive(serde::Serialize, Debug, Clone)]
+pub struct SessionInfo {
+    /// A hash of the token, so sessions can be told apart without showing it
+    pub id: String,
+    pub created_at: Option<chrono::NaiveDateTime>,
+    pub last_seen: Option<chrono::NaiveDateTime>,
+    pub user_agent: Option<String>,
+    pub ip: Option<String>,
+    /// Whether this is the session looking at the list
+    pub current: bool,
+}
+
+/// Where a request came from, to help users recognize their sessions
+#[derive(Debug, Clone, Default)]
+pub struct ClientInfo {
+    pub ip: Option<String>,
+    pub user_agent: Option<String>,
+}
+
+impl ClientInfo {
+    const MAX_USER_AGENT_LEN: usize = 256;
+
+    /// The client's address in a forwarding header. Each proxy appends the address it got
+    /// the request from, so anything left of what our `trusted_hops` proxies added could
+    /// have been sent by the client itself.
+    fn forwarded_ip(header: &str, trusted_hops: usize) -> Option<String> {
+        header
+            .rsplit(',')
+            .map(str::trim)
+            .take(trusted_hops + 1)
+            .last()
+            .filter(|ip| !ip.is_empty())
+            .map(str::to_string)
+    }
+}
+
+#[axum::async_trait]
+impl FromRequestParts<AppState> for ClientInfo {
+    type Rejection = Error;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        let header = |name: &str| {
+            parts
+                .headers
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_string)
+        };
+        let forwarded = state
+            .config
+            .real_ip_header
+            .as_deref()
+            .and_then(header)
+            .and_then(|value| Self::forwarded_ip(&value, state.config.trusted_proxy_hops));
+        let ip = forwarded.or_else(|| {
+            parts
+                .extensions
+                .get::<ConnectInfo<SocketAddr>>()
+                .map(|ConnectInfo(addr)| addr.ip().to_string())
+        });
+        let user_agent = header(USER_AGENT.as_str())
+            .map(|agent| agent.chars().take(Self::MAX_USER_AGENT_LEN).collect());
+        Ok(Self { ip, user_agent })
+    }
+}
+
+impl Session {
+    /// How out of date [`SessionInfo::last_seen`] can get, in seconds
+    const TOUCH_INTERVAL: u64 = 5 * 60;
+
+    fn token_key(token: &str) -> String {
+        format!("token:user:{token}")
+    }
+
+    fn user_key(user: Id<UserMarker>) -> String {
+        format!("sessions:user:{user}")
+    }
+
+    fn info_key(token: &str) -> String {
+        format!("session:{token}")
+    }
+
+    pub fn public_id(token: &str) -> String {
+        blake3::hash(token.as_bytes()).to_hex()[..32].to_string()
+    }
+
+    /// Log `user` in, returning the token for their auth cookie
+    pub async fn start(
+        state: &AppState,
+        user: &User,
+        client: &ClientInfo,
+    ) -> Result<String, Error> {
+        let token = Alphanumeric.sample_string(&mut rand::thread_rng(), 64);
+        let mut redis = state.redis.get().await?;
+        redis
+            .set_ex(Self::token_key(&token), user.id.get(), AUTHTOKEN_TTL)
+            .await?;
+        redis.sadd(Self::user_key(user.id), &token).await?;
+        redis
+            .expire(Self::user_key(user.id), AUTHTOKEN_TTL.try_into()?)
+            .await?;
+        let now = now().to_string();
+        let mut info = vec![("created_at", now.clone()), ("last_seen", now)];
+        if let Some(ip) = &client.ip {
+            info.push(("ip", ip.clone()));
+        }
+        if let Some(user_agent) = &client.user_agent {
+            info.push(("user_agent", user_agent.clone()));
+        }
+        redis.hset_multiple(Self::info_key(&token), &info).await?;
+        redis
+            .expire(Self::info_key(&token), AUTHTOKEN_TTL.try_into()?)
+            .await?;
+        redis
+            .set_ex(
+                format!("user:{}", user.id),
+                serde_json::to_string(user)?,
+                AUTHTOKEN_TTL,
+            )
+            .await?;
+        Ok(token)
+    }
+
+    /// Note that the session for `token` was just used. Returns `false` for logins from
+    /// before sessions were kept, which couldn't be listed or ended with the others.
+    pub async fn touch(state: &AppState, token: &str) -> Result<bool, Error> {
+        let mut redis = state.redis.get().await?;
+        let last_seen: Option<u64> = redis.hget(Self::info_key(token), "last_seen").await?;
+        let Some(last_seen) = last_seen else {
+            return Ok(false);
+        };
+        // only every few minutes, so every request doesn't write to redis
+        let now = now();
+        if now.saturating_sub(last_seen) >= Self::TOUCH_INTERVAL {
+            let _: () = redis.hset(Self::info_key(token), "last_seen", now).await?;
+        }
+        Ok(true)
+    }
+
+    /// The logins of `user`, most recently used first. `current` is the token of whoever's asking.
+    pub async fn list(
+        state: &AppState,
+        user: Id<UserMarker>,
+        current: Option<&str>,
+    ) -> Result<Vec<SessionInfo>, Error> {
+        let mut redis = state.redis.get().await?;
+        let tokens: Vec<String> = redis.smembers(Self::user_key(user)).await?;
+        let mut sessions = Vec::with_capacity(tokens.len());
+        for token in tokens {
+            let active: bool = redis.exists(Self::token_key(&token)).await?;
+            if !active {
+                redis.srem(Self::user_key(user), &token).await?;
+                redis.del(Self::info_key(&token)).await?;
+                continue;
+            }
+            let mut info: HashMap<String, String> = redis.hgetall(Self::info_key(&token)).await?;
+            sessions.push(SessionInfo {
+                id: Self::public_id(&token),
+                created_at: info.get("created_at").and_then(|time| timestamp(time)),
+                last_seen: info.get("last_seen").and_then(|time| timestamp(time)),
+                user_agent: info.remove("user_agent"),
+                ip: info.remove("ip"),
+                current: current == Some(token.as_str()),
+            });
+        }
+        sessions.sort_by_key(|session| std::cmp::Reverse(session.last_seen));
+        Ok(sessions)
+    }
+
+    /// End the session for `token`, returning who it belonged to
+    pub async fn end(state: &AppState, token: &str) -> Result<Option<String>, Error> {
+        let mut redis = state.redis.get().await?;
+        let maybe_id: Option<String> = redis.get_del(Self::token_key(token)).await?;
+        if let Some(id) = &maybe_id {
+            redis.srem(format!("sessions:user:{id}"), token).await?;
+        }
+        redis.del(Self::info_key(token)).await?;
+        Ok(maybe_id)
+    }
+
+    /// End the session of `user` whose [`SessionInfo::id`] is `id`, if there is one
+    pub async fn revoke(state: &AppState, user: Id<UserMarker>, id: &str) -> Result<(), Error> {
+        let tokens: Vec<String> = state
+            .redis
+            .get()
+            .await?
+            .smembers(Self::user_key(user))
+            .await?;
+        if let Some(token) = tokens.iter().find(|token| Self::public_id(token) == id) {
+            Self::end(state, token).await?;
+        }
+        Ok(())
+    }
+
+    /// Log `user` out everywhere except the session for `keep`
+    pub async fn end_others(
+        state: &AppState,
+        user: Id<UserMarker>,
+        keep: Option<&str>,
+    ) -> Result<(), Error> {
+        let mut redis = state.redis.get().await?;
+        let tokens: Vec<String> = redis.smembers(Self::user_key(user)).await?;
+        let ending: Vec<&String> = tokens
+            .iter()
+            .filter(|token| Some(token.as_str()) != keep)
+            .collect();
+        if ending.is_empty() {
+            return Ok(());
+        }
+        let mut keys = Vec::with_capacity(ending.len() * 2);
+        for token in &ending {
+            keys.push(Self::token_key(token));
+            keys.push(Self::info_key(token));
+        }
+        redis.del(keys).await?;
+        redis.srem(Self::user_key(user), ending).await?;
+        Ok(())
+    }
+
+    /// Log `user` out everywhere
+    pub async fn end_all(state: &AppState, user: Id<UserMarker>) -> Result<(), Error> {
+        let mut redis = state.redis.get().await?;
+        let tokens: Vec<String> = redis.smembers(Self::user_key(user)).await?;
+        let mut keys: Vec<String> = tokens
+            .iter()
+            .flat_map(|token| [Self::token_key(token), Self::info_key(token)])
+            .collect();
+        keys.push(Self::user_key(user));
+        keys.push(format!("user:{user}"));
+        redis.del(keys).await?;
+        Ok(())
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+fn timestamp(seconds: &str) -> Option<chrono::NaiveDateTime> {
+    let seconds = seconds.parse().ok()?;
+    chrono::DateTime::from_timestamp(seconds, 0).map(|time| time.naive_utc())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn session_info_fields() {
+        let id = Session::public_id("token");
+        assert_eq!(id.len(), 32);
+        assert_ne!(id, Session::public_id("other token"));
+        assert_eq!(
+            timestamp("0"),
+            chrono::DateTime::from_timestamp(0, 0).map(|time| time.naive_utc())
+        );
+        assert_eq!(timestamp("yesterday"), None);
+    }
+
+    #[test]
+    fn forwarded_ip_skips_spoofed_addresses() {
+        let header = "6.6.6.6, 1.1.1.1, 2.2.2.2";
+        assert_eq!(
+            ClientInfo::forwarded_ip(header, 0).as_deref(),
+            Some("2.2.2.2")
+        );
+        assert_eq!(
+            ClientInfo::forwarded_ip(header, 1).as_deref(),
+            Some("1.1.1.1")
+        );
+        assert_eq!(
+            ClientInfo::forwarded_ip("1.1.1.1", 2).as_deref(),
+            Some("1.1.1.1")
+        );
+        assert_eq!(ClientInfo::forwarded_ip("", 0), None);
+    }
+}
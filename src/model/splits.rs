@@ -0,0 +1,451 @@
+use sqlx::{PgConnection, PgExecutor};
+
+use super::TimingMethod;
+use crate::{
+    id::{Id, RunMarker},
+    util::MAX_SPLITS_LEN,
+    AppState, Error,
+};
+
+/// The name LiveSplit gives the comparison holding the splits of the run itself
+const PERSONAL_BEST: &str = "Personal Best";
+
+/// Segment data of a run, read from a LiveSplit `.lss` file
+#[derive(serde::Serialize, serde::Deserialize, Debug, Hash, PartialEq, Eq, Clone)]
+pub struct Splits {
+    /// Names of the other comparisons in the file, in the order they appear
+    pub comparisons: Vec<String>,
+    pub segments: Vec<Segment>,
+}
+
+/// All times are in milliseconds, where 0 means the time was not recorded
+#[derive(serde::Serialize, serde::Deserialize, Debug, Hash, PartialEq, Eq, Clone)]
+pub struct Segment {
+    pub name: String,
+    /// Time since the start of the run
+    pub split_time: i64,
+    /// Time since the previous split
+    pub segment_time: i64,
+    pub best_segment: i64,
+    /// Set when this segment was at least as fast as the best segment
+    pub gold: bool,
+    /// Split times of each comparison, lined up with [`Splits::comparisons`]
+    pub comparisons: Vec<i64>,
+}
+
+/// A segment as stored, before working out its segment time
+struct RawSegment {
+    name: String,
+    split_time: i64,
+    best_segment: i64,
+    comparisons: Vec<i64>,
+}
+
+impl Splits {
+    /// Parse an uploaded LiveSplit `.lss` file, see [`Self::from_lss`]
+    pub fn from_upload(bytes: &[u8], method: TimingMethod) -> Result<Self, Error> {
+        if bytes.len() > MAX_SPLITS_LEN {
+            return Err(Error::CustomFormValidation(format!(
+                "splits file too large (must be less then {MAX_SPLITS_LEN} bytes)"
+            )));
+        }
+        let lss = std::str::from_utf8(bytes)
+            .map_err(|_| Error::CustomFormValidation("splits file is not UTF-8".to_string()))?;
+        Self::from_lss(lss, method)
+    }
+
+    /// Parse a LiveSplit `.lss` file. Splits from LiveSplit's game time are used
+    /// for in-game and load-removed timing, and its real time otherwise.
+    pub fn from_lss(lss: &str, method: TimingMethod) -> Result<Self, Error> {
+        let run: LssRun = quick_xml::de::from_str(lss)?;
+        let pick = |real_time: &Option<String>, game_time: &Option<String>| {
+            let raw = match method {
+                TimingMethod::Rta => real_time,
+                TimingMethod::Igt | TimingMethod::Lrt => game_time,
+            };
+            raw.as_deref().map_or(Ok(0), parse_lss_time)
+        };
+        let lss_segments = run.segments.segments;
+        if lss_segments.is_empty() {
+            return Err(Error::CustomFormValidation(
+                "splits file has no segments".to_string(),
+            ));
+        }
+        if lss_segments.len() > crate::util::MAX_SPLITS_SEGMENTS {
+            return Err(Error::CustomFormValidation(format!(
+                "splits file has too many segments (must be at most {})",
+                crate::util::MAX_SPLITS_SEGMENTS
+            )));
+        }
+        let mut comparisons: Vec<String> = Vec::new();
+        for segment in &lss_segments {
+            if segment.name.chars().count() > crate::util::MAX_SEGMENT_NAME_LEN {
+                return Err(Error::CustomFormValidation(format!(
+                    "splits segment name too long (must be at most {} characters)",
+                    crate::util::MAX_SEGMENT_NAME_LEN
+                )));
+            }
+            for split in &segment.split_times.split_times {
+                if split.name != PERSONAL_BEST && !comparisons.contains(&split.name) {
+                    comparisons.push(split.name.clone());
+                }
+            }
+        }
+        if comparisons.len() > crate::util::MAX_SPLITS_COMPARISONS {
+            return Err(Error::CustomFormValidation(format!(
+                "splits file has too many comparisons (must be at most {})",
+                crate::util::MAX_SPLITS_COMPARISONS
+            )));
+        }
+        if comparisons
+            .iter()
+            .any(|name| name.chars().count() > crate::util::MAX_COMPARISON_NAME_LEN)
+        {
+            return Err(Error::CustomFormValidation(format!(
+                "splits comparison name too long (must be at most {} characters)",
+                crate::util::MAX_COMPARISON_NAME_LEN
+            )));
+        }
+        let mut raw_segments = Vec::with_capacity(lss_segments.len());
+        for segment in lss_segments {
+            let mut split_time = 0;
+            let mut comparison_times = vec![0; comparisons.len()];
+            for split in &segment.split_times.split_times {
+                let time = pick(&split.real_time, &split.game_time)?;
+                if split.name == PERSONAL_BEST {
+                    split_time = time;
+                } else if let Some(index) = comparisons.iter().position(|c| *c == split.name) {
+                    comparison_times[index] = time;
+                }
+            }
+            raw_segments.push(RawSegment {
+                name: segment.name,
+                split_time,
+                best_segment: pick(
+                    &segment.best_segment_time.real_time,
+                    &segment.best_segment_time.game_time,
+                )?,
+                comparisons: comparison_times,
+            });
+        }
+        Ok(Self::from_raw(comparisons, raw_segments))
+    }
+
+    fn from_raw(comparisons: Vec<String>, raw_segments: Vec<RawSegment>) -> Self {
+        let mut segments = Vec::with_capacity(raw_segments.len());
+        let mut previous_split = 0;
+        for raw in raw_segments {
+            // skipped splits roll their time into the next segment
+            let segment_time = if raw.split_time == 0 {
+                0
+            } else {
+                raw.split_time - previous_split
+            };
+            if raw.split_time != 0 {
+                previous_split = raw.split_time;
+            }
+            segments.push(Segment {
+                name: raw.name,
+                split_time: raw.split_time,
+                segment_time,
+                best_segment: raw.best_segment,
+                gold: segment_time != 0
+                    && raw.best_segment != 0
+                    && segment_time <= raw.best_segment,
+                comparisons: raw.comparisons,
+            });
+        }
+        Self {
+            comparisons,
+            segments,
+        }
+    }
+
+    pub async fn from_db_run(state: &AppState, run: Id<RunMarker>) -> Result<Option<Self>, Error> {
+        let Some(comparisons) = query!(
+            "SELECT comparisons FROM run_splits WHERE run = $1",
+            run.get()
+        )
+        .fetch_optional(&state.postgres)
+        .await?
+        .map(|row| row.comparisons) else {
+            return Ok(None);
+        };
+        let raw_segments = query!(
+            "SELECT name, split_time, best_segment, comparison_times
+            FROM run_segments WHERE run = $1 ORDER BY position",
+            run.get()
+        )
+        .fetch_all(&state.postgres)
+        .await?
+        .into_iter()
+        .map(|row| RawSegment {
+            name: row.name,
+            split_time: row.split_time,
+            best_segment: row.best_segment,
+            comparisons: row.comparison_times,
+        })
+        .collect();
+        Ok(Some(Self::from_raw(comparisons, raw_segments)))
+    }
+
+    /// Replace the stored splits of `run` with these
+    pub async fn save(&self, conn: &mut PgConnection, run: Id<RunMarker>) -> Result<(), Error> {
+        Self::delete(&mut *conn, run).await?;
+        query!(
+            "INSERT INTO run_splits (run, comparisons) VALUES ($1, $2)",
+            run.get(),
+            &self.comparisons
+        )
+        .execute(&mut *conn)
+        .await?;
+        let names: Vec<&str> = self.segments.iter().map(|seg| seg.name.as_str()).collect();
+        let split_times: Vec<i64> = self.segments.iter().map(|seg| seg.split_time).collect();
+        let best_segments: Vec<i64> = self.segments.iter().map(|seg| seg.best_segment).collect();
+        // every segment has a time for each comparison, so they're sent as one flat array
+        let comparison_times: Vec<i64> = self
+            .segments
+            .iter()
+            .flat_map(|seg| seg.comparisons.iter().copied())
+            .collect();
+        query!(
+            "INSERT INTO run_segments
+            (run, position, name, split_time, best_segment, comparison_times)
+            SELECT $1, (position - 1)::SMALLINT, name, split_time, best_segment,
+            ($5::BIGINT[])[(position - 1) * $6 + 1 : position * $6]
+            FROM UNNEST($2::TEXT[], $3::BIGINT[], $4::BIGINT[])
+            WITH ORDINALITY AS segments(name, split_time, best_segment, position)",
+            run.get(),
+            &names as &[&str],
+            &split_times,
+            &best_segments,
+            &comparison_times,
+            i64::try_from(self.comparisons.len())?
+        )
+        .execute(&mut *conn)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn delete(executor: impl PgExecutor<'_>, run: Id<RunMarker>) -> Result<(), Error> {
+        query!("DELETE FROM run_splits WHERE run = $1", run.get())
+            .execute(executor)
+            .await?;
+        Ok(())
+    }
+}
+
+/// Parse a .NET `TimeSpan`, like `1.02:03:04.5670000`, into milliseconds
+fn parse_lss_time(input: &str) -> Result<i64, Error> {
+    let invalid = || Error::CustomFormValidation(format!("invalid splits time {input}"));
+    // only the whole time can be negative, so every part has to be plain digits
+    let number = |part: &str| {
+        if part.is_empty() || !part.bytes().all(|byte| byte.is_ascii_digit()) {
+            return Err(invalid());
+        }
+        part.parse::<i64>().map_err(|_| invalid())
+    };
+    let trimmed = input.trim();
+    let (negative, unsigned) = trimmed
+        .strip_prefix('-')
+        .map_or((false, trimmed), |rest| (true, rest));
+    let mut parts = unsigned.split(':');
+    let (Some(days_hours), Some(minutes), Some(seconds), None) =
+        (parts.next(), parts.next(), parts.next(), parts.next())
+    else {
+        return Err(invalid());
+    };
+    let (days, hours) = days_hours.split_once('.').unwrap_or(("0", days_hours));
+    let (seconds, fraction) = seconds.split_once('.').unwrap_or((seconds, "0"));
+    number(fraction)?;
+    // only the first three digits of the fraction are milliseconds
+    let millis = number(&format!("{fraction:0<3.3}"))?;
+    let mut total = number(days)?;
+    for (scale, part) in [
+        (24, number(hours)?),
+        (60, number(minutes)?),
+        (60, number(seconds)?),
+        (1000, millis),
+    ] {
+        total = total
+            .checked_mul(scale)
+            .and_then(|total| total.checked_add(part))
+            .ok_or_else(invalid)?;
+    }
+    Ok(if negative { -total } else { total })
+}
+
+#[derive(serde::Deserialize, Debug)]
+struct LssRun {
+    #[serde(rename = "Segments")]
+    segments: LssSegments,
+}
+
+#[derive(serde::Deserialize, Debug)]
+struct LssSegments {
+    #[serde(rename = "Segment", default)]
+    segments: Vec<LssSegment>,
+}
+
+#[derive(serde::Deserialize, Debug)]
+struct LssSegment {
+    #[serde(rename = "Name")]
+    name: String,
+    #[serde(rename = "SplitTimes", default)]
+    split_times: LssSplitTimes,
+    #[serde(rename = "BestSegmentTime", default)]
+    best_segment_time: LssTime,
+}
+
+#[derive(serde::Deserialize, Debug, Default)]
+struct LssSplitTimes {
+    #[serde(rename = "SplitTime", default)]
+    split_times: Vec<LssSplitTime>,
+}
+
+#[derive(serde::Deserialize, Debug)]
+struct LssSplitTime {
+    #[serde(rename = "@name")]
+    name: String,
+    #[serde(rename = "RealTime", default)]
+    real_time: Option<String>,
+    #[serde(rename = "GameTime", default)]
+    game_time: Option<String>,
+}
+
+#[derive(serde::Deserialize, Debug, Default)]
+struct LssTime {
+    #[serde(rename = "RealTime", default)]
+    real_time: Option<String>,
+    #[serde(rename = "GameTime", default)]
+    game_time: Option<String>,
+}
+
+#[cfg(test)]
+mod test {
+    use sqlx::PgPool;
+
+    use super::*;
+
+    const TEST_LSS: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<Run version="1.7.0">
+  <GameIcon />
+  <GameName>Test game</GameName>
+  <CategoryName>Any%</CategoryName>
+  <Segments>
+    <Segment>
+      <Name>First</Name>
+      <Icon />
+      <SplitTimes>
+        <SplitTime name="Personal Best">
+          <RealTime>00:01:00.5000000</RealTime>
+          <GameTime>00:00:58.0000000</GameTime>
+        </SplitTime>
+        <SplitTime name="Goal">
+          <RealTime>00:00:59.0000000</RealTime>
+        </SplitTime>
+      </SplitTimes>
+      <BestSegmentTime>
+        <RealTime>00:01:00.5000000</RealTime>
+      </BestSegmentTime>
+      <SegmentHistory />
+    </Segment>
+    <Segment>
+      <Name>Second</Name>
+      <Icon />
+      <SplitTimes>
+        <SplitTime name="Personal Best">
+          <RealTime>1.00:00:01</RealTime>
+        </SplitTime>
+      </SplitTimes>
+      <BestSegmentTime />
+      <SegmentHistory />
+    </Segment>
+  </Segments>
+</Run>"#;
+
+    #[test]
+    fn parse_times() {
+        assert_eq!(parse_lss_time("00:01:00.5000000").unwrap(), 60_500);
+        assert_eq!(parse_lss_time("01:02:03").unwrap(), 3_723_000);
+        assert_eq!(parse_lss_time("1.00:00:01.25").unwrap(), 86_401_250);
+        assert_eq!(parse_lss_time("-00:00:01.5").unwrap(), -1500);
+        assert!(parse_lss_time("not a time").is_err());
+        assert!(parse_lss_time("00:-5:00").is_err());
+        assert!(parse_lss_time("00:+5:00").is_err());
+        assert!(parse_lss_time("00:00:01.-5").is_err());
+        assert!(parse_lss_time("9223372036854775807.00:00:00").is_err());
+    }
+
+    #[test]
+    fn parse_lss() {
+        let splits = Splits::from_lss(TEST_LSS, TimingMethod::Rta).unwrap();
+        assert_eq!(splits.comparisons, vec!["Goal".to_string()]);
+        assert_eq!(
+            splits.segments,
+            vec![
+                Segment {
+                    name: "First".to_string(),
+                    split_time: 60_500,
+                    segment_time: 60_500,
+                    best_segment: 60_500,
+                    gold: true,
+                    comparisons: vec![59_000],
+                },
+                Segment {
+                    name: "Second".to_string(),
+                    split_time: 86_401_000,
+                    segment_time: 86_340_500,
+                    best_segment: 0,
+                    gold: false,
+                    comparisons: vec![0],
+                },
+            ]
+        );
+        let game_time = Splits::from_lss(TEST_LSS, TimingMethod::Igt).unwrap();
+        assert_eq!(game_time.segments[0].split_time, 58_000);
+        assert_eq!(game_time.segments[1].split_time, 0);
+    }
+
+    #[test]
+    fn long_names() {
+        let long_name = "a".repeat(crate::util::MAX_SEGMENT_NAME_LEN + 1);
+        let lss = TEST_LSS.replace("<Name>First</Name>", &format!("<Name>{long_name}</Name>"));
+        assert!(Splits::from_lss(&lss, TimingMethod::Rta).is_err());
+        let long_name = "a".repeat(crate::util::MAX_COMPARISON_NAME_LEN + 1);
+        let lss = TEST_LSS.replace("name=\"Goal\"", &format!("name=\"{long_name}\""));
+        assert!(Splits::from_lss(&lss, TimingMethod::Rta).is_err());
+    }
+
+    #[sqlx::test(fixtures(path = "../fixtures", scripts("add_game", "add_user")))]
+    async fn save_segments(db: PgPool) -> Result<(), Error> {
+        query!(
+            "INSERT INTO runs
+            (id, game, category, submitter, video, description, score, time, status, created_at)
+            VALUES (10, 1, 1, 1, 'https://example.com', '', 0, 86401000, 0, NOW())"
+        )
+        .execute(&db)
+        .await?;
+        let splits = Splits::from_lss(TEST_LSS, TimingMethod::Rta)?;
+        let mut conn = db.acquire().await?;
+        splits.save(&mut conn, Id::new(10)).await?;
+        let segments = query!(
+            "SELECT position, name, split_time, comparison_times
+            FROM run_segments WHERE run = 10 ORDER BY position"
+        )
+        .fetch_all(&db)
+        .await?;
+        let segments: Vec<_> = segments
+            .into_iter()
+            .map(|row| (row.position, row.name, row.split_time, row.comparison_times))
+            .collect();
+        assert_eq!(
+            segments,
+            [
+                (0, "First".to_string(), 60_500, vec![59_000]),
+                (1, "Second".to_string(), 86_401_000, vec![0]),
+            ]
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,238 @@
+use std::ops::{BitOr, BitOrAssign};
+
+#[derive(
+    serde::Serialize,
+    serde::Deserialize,
+    Debug,
+    Default,
+    Hash,
+    PartialEq,
+    Eq,
+    Clone,
+    Copy,
+    sqlx::Type,
+)]
+#[serde(rename_all = "lowercase")]
+#[repr(i16)]
+pub enum TimingMethod {
+    /// real time, stored in `runs.time`
+    #[default]
+    Rta = 0,
+    /// in-game time, stored in `runs.igt`
+    Igt = 1,
+    /// load-removed time, stored in `runs.lrt`
+    Lrt = 2,
+}
+
+impl TimingMethod {
+    pub const ALL: [Self; 3] = [Self::Rta, Self::Igt, Self::Lrt];
+
+    /// The `runs` column this timing method is stored in
+    pub fn column(self) -> &'static str {
+        match self {
+            Self::Rta => "time",
+            Self::Igt => "igt",
+            Self::Lrt => "lrt",
+        }
+    }
+
+    pub fn abbreviation(self) -> &'static str {
+        match self {
+            Self::Rta => "RTA",
+            Self::Igt => "IGT",
+            Self::Lrt => "LRT",
+        }
+    }
+}
+
+impl From<i16> for TimingMethod {
+    fn from(value: i16) -> Self {
+        match value {
+            1 => Self::Igt,
+            2 => Self::Lrt,
+            _ => Self::Rta,
+        }
+    }
+}
+
+impl From<TimingMethod> for i16 {
+    fn from(value: TimingMethod) -> Self {
+        match value {
+            TimingMethod::Rta => 0,
+            TimingMethod::Igt => 1,
+            TimingMethod::Lrt => 2,
+        }
+    }
+}
+
+/// Parse a time like `1:02:03.5`, `2:03` or `59.25` into milliseconds
+pub fn parse_duration(input: &str) -> Option<i64> {
+    let (rest, fraction) = input.trim().split_once('.').unwrap_or((input.trim(), ""));
+    if fraction.len() > 3 || !fraction.bytes().all(|byte| byte.is_ascii_digit()) {
+        return None;
+    }
+    let millis: i64 = format!("{fraction:0<3}").parse().ok()?;
+    let parts: Vec<&str> = rest.split(':').collect();
+    if parts.len() > 3 {
+        return None;
+    }
+    let mut seconds: i64 = 0;
+    for part in parts {
+        if part.is_empty() || !part.bytes().all(|byte| byte.is_ascii_digit()) {
+            return None;
+        }
+        seconds = seconds.checked_mul(60)?.checked_add(part.parse().ok()?)?;
+    }
+    seconds.checked_mul(1000)?.checked_add(millis)
+}
+
+/// The set of timing methods a category accepts
+#[derive(Debug, Hash, PartialEq, Eq, Clone, Copy, sqlx::Type)]
+#[sqlx(transparent)]
+pub struct TimingMethods(i16);
+
+#[rustfmt::skip]
+impl TimingMethods {
+    pub const EMPTY: Self = Self(0b0);
+    pub const RTA: Self = Self(0b1 << 0);
+    pub const IGT: Self = Self(0b1 << 1);
+    pub const LRT: Self = Self(0b1 << 2);
+}
+
+impl TimingMethods {
+    pub fn new(input: i16) -> Self {
+        Self(input)
+    }
+
+    #[inline]
+    pub fn get(self) -> i16 {
+        self.0
+    }
+
+    #[inline]
+    pub fn is_empty(self) -> bool {
+        self == Self::EMPTY
+    }
+
+    #[inline]
+    pub fn contains(self, method: TimingMethod) -> bool {
+        let check = Self::from(method);
+        (self.0 & check.0) == check.0
+    }
+
+    /// Every accepted method, in display order
+    pub fn methods(self) -> impl Iterator<Item = TimingMethod> {
+        TimingMethod::ALL
+            .into_iter()
+            .filter(move |method| self.contains(*method))
+    }
+
+    #[inline]
+    fn expand(self) -> TimingMethodsSerde {
+        TimingMethodsSerde {
+            rta: self.contains(TimingMethod::Rta),
+            igt: self.contains(TimingMethod::Igt),
+            lrt: self.contains(TimingMethod::Lrt),
+        }
+    }
+}
+
+impl From<TimingMethod> for TimingMethods {
+    fn from(value: TimingMethod) -> Self {
+        match value {
+            TimingMethod::Rta => Self::RTA,
+            TimingMethod::Igt => Self::IGT,
+            TimingMethod::Lrt => Self::LRT,
+        }
+    }
+}
+
+impl From<i16> for TimingMethods {
+    fn from(value: i16) -> Self {
+        Self::new(value)
+    }
+}
+
+impl BitOr for TimingMethods {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self::Output {
+        Self(self.0 | rhs.0)
+    }
+}
+
+impl BitOrAssign for TimingMethods {
+    fn bitor_assign(&mut self, rhs: Self) {
+        *self = *self | rhs;
+    }
+}
+
+#[derive(serde::Deserialize, serde::Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+struct TimingMethodsSerde {
+    rta: bool,
+    igt: bool,
+    lrt: bool,
+}
+
+impl TimingMethodsSerde {
+    fn compress(self) -> TimingMethods {
+        let mut methods = TimingMethods::EMPTY;
+        if self.rta {
+            methods |= TimingMethods::RTA;
+        }
+        if self.igt {
+            methods |= TimingMethods::IGT;
+        }
+        if self.lrt {
+            methods |= TimingMethods::LRT;
+        }
+        methods
+    }
+}
+
+impl serde::Serialize for TimingMethods {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        self.expand().serialize(serializer)
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for TimingMethods {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        Ok(TimingMethodsSerde::deserialize(deserializer)?.compress())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn methods_round_trip() {
+        let methods = TimingMethods::RTA | TimingMethods::LRT;
+        assert_eq!(
+            methods.methods().collect::<Vec<_>>(),
+            vec![TimingMethod::Rta, TimingMethod::Lrt]
+        );
+        assert_eq!(methods.expand().compress(), methods);
+        for method in TimingMethod::ALL {
+            assert_eq!(TimingMethod::from(i16::from(method)), method);
+        }
+    }
+
+    #[test]
+    fn durations() {
+        assert_eq!(parse_duration("1:02:03.5"), Some(3_723_500));
+        assert_eq!(parse_duration("2:03"), Some(123_000));
+        assert_eq!(parse_duration(" 59.25 "), Some(59_250));
+        assert_eq!(parse_duration("1:2:3:4"), None);
+        assert_eq!(parse_duration("1:.5"), None);
+        assert_eq!(parse_duration("5.1234"), None);
+        assert_eq!(parse_duration(""), None);
+    }
+}
@@ -0,0 +1,443 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use rand::{
+    distributions::{Alphanumeric, DistString},
+    Rng,
+};
+use redis::AsyncCommands;
+use totp_rs::{Algorithm, TOTP};
+
+use super::User;
+use crate::{
+    id::{Id, UserMarker},
+    AppState, Error,
+};
+
+/// Time-based one-time passwords from an authenticator app, asked for after a user's password.
+/// New secrets wait at `totp:pending:{user}` until the user proves their app has them.
+#[derive(Debug, Clone)]
+pub struct TwoFactor {
+    pub user: Id<UserMarker>,
+    secret: Vec<u8>,
+    /// The newest time step a code was accepted for
+    last_step: i64,
+}
+
+/// What a user needs to add a new secret to their authenticator app
+#[derive(serde::Serialize, Debug, Clone)]
+pub struct Enrolment {
+    /// The secret in base32, for typing in by hand
+    pub secret: String,
+    /// An `otpauth://` URL, for the QR code
+    pub url: String,
+}
+
+/// What happened when someone entered a code to finish logging in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LoginAttempt {
+    Success(Id<UserMarker>),
+    Incorrect,
+    /// The login timed out or had too many wrong codes, and has to start over
+    Expired,
+}
+
+impl TwoFactor {
+    const ISSUER: &'static str = "speederboard";
+    /// Seconds each code is valid for
+    const STEP: u64 = 30;
+    /// Seconds users have to confirm a new secret
+    const PENDING_TTL: u64 = 15 * 60;
+    /// Seconds users have between entering their password and their code
+    const LOGIN_TTL: u64 = 5 * 60;
+    /// Codes allowed per login, so they can't be guessed
+    const LOGIN_ATTEMPTS: i64 = 5;
+    /// Codes allowed per user until one is right, across all their logins,
+    /// since entering the password again starts a new login
+    const USER_ATTEMPTS: i64 = 10;
+    /// Seconds a user's wrong codes count against them
+    const USER_ATTEMPTS_TTL: u64 = 15 * 60;
+    const RECOVERY_CODES: usize = 10;
+    const RECOVERY_CODE_CHARS: &'static [u8] = b"abcdefghjkmnpqrstuvwxyz23456789";
+
+    fn totp(secret: Vec<u8>, account_name: String) -> TOTP {
+        // steps are checked one at a time, so the skew is handled by `matching_step`
+        TOTP::new_unchecked(
+            Algorithm::SHA1,
+            6,
+            0,
+            Self::STEP,
+            secret,
+            Some(Self::ISSUER.to_string()),
+            account_name,
+        )
+    }
+
+    fn pending_key(user: Id<UserMarker>) -> String {
+        format!("totp:pending:{user}")
+    }
+
+    fn login_key(token: &str) -> String {
+        format!("token:2fa:{token}")
+    }
+
+    fn attempts_key(token: &str) -> String {
+        format!("attempts:2fa:{token}")
+    }
+
+    fn user_attempts_key(user: Id<UserMarker>) -> String {
+        format!("attempts:2fa:user:{user}")
+    }
+
+    pub async fn from_db(state: &AppState, user: Id<UserMarker>) -> Result<Option<Self>, Error> {
+        let two_factor = query!(
+            "SELECT secret, last_step FROM user_totp WHERE user_id = $1",
+            user.get()
+        )
+        .fetch_optional(&state.postgres)
+        .await?
+        .map(|row| Self {
+            user,
+            secret: row.secret,
+            last_step: row.last_step,
+        });
+        Ok(two_factor)
+    }
+
+    pub async fn is_enabled(state: &AppState, user: Id<UserMarker>) -> Result<bool, Error> {
+        let enabled = query!(
+            r#"SELECT EXISTS(SELECT 1 FROM user_totp WHERE user_id = $1) AS "enabled!""#,
+            user.get()
+        )
+        .fetch_one(&state.postgres)
+        .await?
+        .enabled;
+        Ok(enabled)
+    }
+
+    /// The time step `code` was generated for, if it's valid at `now`, give or take a step,
+    /// and newer than the last code that was accepted
+    fn matching_step(&self, code: &str, now: u64) -> Option<i64> {
+        let totp = Self::totp(self.secret.clone(), String::new());
+        let current = now / Self::STEP;
+        (current.saturating_sub(1)..=current + 1)
+            .filter_map(|step| Some((step, i64::try_from(step).ok()?)))
+            .filter(|(_, step)| *step > self.last_step)
+            .find(|(step, _)| totp.check(code, step * Self::STEP))
+            .map(|(_, step)| step)
+    }
+
+    /// Check a code from the user's app, or one of their recovery codes, using it up
+    pub async fn verify(&self, state: &AppState, code: &str) -> Result<bool, Error> {
+        let code: String = code.split_whitespace().collect();
+        if let Some(step) = self.matching_step(&code, now()) {
+            // another request may have used the same code since this was loaded
+            let updated = query!(
+                "UPDATE user_totp SET last_step = $2 WHERE user_id = $1 AND last_step < $2",
+                self.user.get(),
+                step
+            )
+            .execute(&state.postgres)
+            .await?
+            .rows_affected();
+            return Ok(updated == 1);
+        }
+        let used = query!(
+            "DELETE FROM totp_recovery_codes WHERE user_id = $1 AND code_hash = $2",
+            self.user.get(),
+            hash_recovery_code(self.user, &code)
+        )
+        .execute(&state.postgres)
+        .await?
+        .rows_affected();
+        Ok(used == 1)
+    }
+
+    /// Start enrolling `user`, or carry on with the enrolment they already started
+    pub async fn enrol(state: &AppState, user: &User) -> Result<Enrolment, Error> {
+        let mut redis = state.redis.get().await?;
+        let pending: Option<Vec<u8>> = redis.get(Self::pending_key(user.id)).await?;
+        let secret = pending.unwrap_or_else(|| rand::thread_rng().gen::<[u8; 20]>().to_vec());
+        redis
+            .set_ex(Self::pending_key(user.id), &secret, Self::PENDING_TTL)
+            .await?;
+        let totp = Self::totp(secret, user.username.clone());
+        Ok(Enrolment {
+            secret: totp.get_secret_base32(),
+            url: totp.get_url(),
+        })
+    }
+
+    /// Turn on two-factor authentication, if `code` shows the user's app has the secret
+    /// from [`Self::enrol`]. Returns the user's recovery codes.
+    pub async fn confirm(
+        state: &AppState,
+        user: Id<UserMarker>,
+        code: &str,
+    ) -> Result<Vec<String>, Error> {
+        let mut redis = state.redis.get().await?;
+        let pending: Option<Vec<u8>> = redis.get(Self::pending_key(user)).await?;
+        let pending = Self {
+            user,
+            secret: pending.ok_or(Error::InvalidTwoFactorCode)?,
+            last_step: 0,
+        };
+        let code: String = code.split_whitespace().collect();
+        let step = pending
+            .matching_step(&code, now())
+            .ok_or(Error::InvalidTwoFactorCode)?;
+        let mut trans = state.postgres.begin().await?;
+        query!(
+            "INSERT INTO user_totp (user_id, secret, last_step) VALUES ($1, $2, $3)
+            ON CONFLICT (user_id) DO UPDATE
+            SET secret = excluded.secret, last_step = excluded.last_step",
+            user.get(),
+            pending.secret,
+            step
+        )
+        .execute(trans.as_mut())
+        .await?;
+        let codes = Self::replace_recovery_codes(trans.as_mut(), user).await?;
+        trans.commit().await?;
+        redis.del(Self::pending_key(user)).await?;
+        Ok(codes)
+    }
+
+    pub async fn disable(state: &AppState, user: Id<UserMarker>) -> Result<(), Error> {
+        let mut trans = state.postgres.begin().await?;
+        query!("DELETE FROM user_totp WHERE user_id = $1", user.get())
+            .execute(trans.as_mut())
+            .await?;
+        query!(
+            "DELETE FROM totp_recovery_codes WHERE user_id = $1",
+            user.get()
+        )
+        .execute(trans.as_mut())
+        .await?;
+        trans.commit().await?;
+        Ok(())
+    }
+
+    /// Replace the user's recovery codes with new ones, returning them
+    pub async fn regenerate_recovery_codes(
+        state: &AppState,
+        user: Id<UserMarker>,
+    ) -> Result<Vec<String>, Error> {
+        let mut trans = state.postgres.begin().await?;
+        let codes = Self::replace_recovery_codes(trans.as_mut(), user).await?;
+        trans.commit().await?;
+        Ok(codes)
+    }
+
+    async fn replace_recovery_codes(
+        conn: &mut sqlx::PgConnection,
+        user: Id<UserMarker>,
+    ) -> Result<Vec<String>, Error> {
+        let codes: Vec<String> = (0..Self::RECOVERY_CODES)
+            .map(|_| new_recovery_code())
+            .collect();
+        let hashes: Vec<String> = codes
+            .iter()
+            .map(|code| hash_recovery_code(user, code))
+            .collect();
+        query!(
+            "DELETE FROM totp_recovery_codes WHERE user_id = $1",
+            user.get()
+        )
+        .execute(&mut *conn)
+        .await?;
+        query!(
+            "INSERT INTO totp_recovery_codes (user_id, code_hash)
+            SELECT $1, * FROM UNNEST($2::text[])",
+            user.get(),
+            &hashes
+        )
+        .execute(&mut *conn)
+        .await?;
+        Ok(codes)
+    }
+
+    pub async fn recovery_codes_left(state: &AppState, user: Id<UserMarker>) -> Result<i64, Error> {
+        let count = query!(
+            r#"SELECT COUNT(*) AS "count!" FROM totp_recovery_codes WHERE user_id = $1"#,
+            user.get()
+        )
+        .fetch_one(&state.postgres)
+        .await?
+        .count;
+        Ok(count)
+    }
+
+    /// Remember that `user` got their password right, returning a token for the code form
+    pub async fn start_login(state: &AppState, user: Id<UserMarker>) -> Result<String, Error> {
+        let token = Alphanumeric.sample_string(&mut rand::thread_rng(), 64);
+        state
+            .redis
+            .get()
+            .await?
+            .set_ex(Self::login_key(&token), user.get(), Self::LOGIN_TTL)
+            .await?;
+        Ok(token)
+    }
+
+    /// Check the code entered for the login started with `token`
+    pub async fn finish_login(
+        state: &AppState,
+        token: &str,
+        code: &str,
+    ) -> Result<LoginAttempt, Error> {
+        let mut redis = state.redis.get().await?;
+        let maybe_id: Option<i64> = redis.get(Self::login_key(token)).await?;
+        let Some(user) = maybe_id.map(Id::new) else {
+            return Ok(LoginAttempt::Expired);
+        };
+        // counted before checking, so attempts made at the same time still count
+        let user_attempts = count_attempt(
+            &mut redis,
+            &Self::user_attempts_key(user),
+            Self::USER_ATTEMPTS_TTL,
+        )
+        .await?;
+        if user_attempts > Self::USER_ATTEMPTS {
+            return Err(Error::TwoFactorLockedOut);
+        }
+        let attempts =
+            count_attempt(&mut redis, &Self::attempts_key(token), Self::LOGIN_TTL).await?;
+        let keys = [
+            Self::login_key(token),
+            Self::attempts_key(token),
+            Self::user_attempts_key(user),
+        ];
+        if attempts > Self::LOGIN_ATTEMPTS {
+            let _: () = redis.del(&keys[..2]).await?;
+            return Ok(LoginAttempt::Expired);
+        }
+        let verified = match Self::from_db(state, user).await? {
+            Some(two_factor) => two_factor.verify(state, code).await?,
+            // they turned it off since entering their password
+            None => true,
+        };
+        if !verified {
+            return Ok(LoginAttempt::Incorrect);
+        }
+        let _: () = redis.del(&keys).await?;
+        Ok(LoginAttempt::Success(user))
+    }
+}
+
+/// Count another attempt at `key`, which is forgotten `ttl` seconds after the first
+async fn count_attempt(
+    redis: &mut deadpool_redis::Connection,
+    key: &str,
+    ttl: u64,
+) -> Result<i64, Error> {
+    let attempts: i64 = redis.incr(key, 1).await?;
+    if attempts == 1 {
+        let _: () = redis.expire(key, ttl.try_into()?).await?;
+    }
+    Ok(attempts)
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+/// Codes look like `abcd-efgh-jkmn-pqrs`
+fn new_recovery_code() -> String {
+    let mut rng = rand::thread_rng();
+    let chars = TwoFactor::RECOVERY_CODE_CHARS;
+    (0..4)
+        .map(|_| {
+            (0..4)
+                .map(|_| char::from(chars[rng.gen_range(0..chars.len())]))
+                .collect::<String>()
+        })
+        .collect::<Vec<String>>()
+        .join("-")
+}
+
+/// Recovery codes are random enough that a fast hash is fine.
+/// Dashes, spaces and case are ignored, so codes can be typed however.
+fn hash_recovery_code(user: Id<UserMarker>, code: &str) -> String {
+    let normalized: String = code
+        .chars()
+        .filter(char::is_ascii_alphanumeric)
+        .map(|character| character.to_ascii_lowercase())
+        .collect();
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(&user.get().to_le_bytes());
+    hasher.update(normalized.as_bytes());
+    hasher.finalize().to_hex().to_string()
+}
+
+#[cfg(test)]
+mod test {
+    use sqlx::PgPool;
+
+    use super::*;
+
+    #[test]
+    fn codes_match_nearby_steps_once() {
+        // the RFC 6238 test secret, which gives 287082 at 59 seconds
+        let mut two_factor = TwoFactor {
+            user: Id::new(1),
+            secret: b"12345678901234567890".to_vec(),
+            last_step: 0,
+        };
+        assert_eq!(two_factor.matching_step("287082", 59), Some(1));
+        assert_eq!(two_factor.matching_step("287082", 89), Some(1));
+        assert_eq!(two_factor.matching_step("287082", 150), None);
+        assert_eq!(two_factor.matching_step("000000", 59), None);
+        two_factor.last_step = 1;
+        assert_eq!(two_factor.matching_step("287082", 59), None);
+    }
+
+    #[test]
+    fn recovery_codes() {
+        let code = new_recovery_code();
+        assert_eq!(code.len(), 19);
+        assert!(code
+            .bytes()
+            .all(|byte| byte == b'-' || TwoFactor::RECOVERY_CODE_CHARS.contains(&byte)));
+        let user = Id::new(1);
+        assert_eq!(
+            hash_recovery_code(user, "abcd-efgh-jkmn-pqrs"),
+            hash_recovery_code(user, " ABCD EFGH jkmn pqrs")
+        );
+        assert_ne!(
+            hash_recovery_code(user, "abcd-efgh-jkmn-pqrs"),
+            hash_recovery_code(Id::new(2), "abcd-efgh-jkmn-pqrs")
+        );
+    }
+
+    #[sqlx::test(fixtures(path = "../fixtures", scripts("add_user")))]
+    async fn attempts_add_up_across_logins(db: PgPool) -> Result<(), Error> {
+        let state = AppState::test(db).await;
+        let user = Id::new(1);
+        let _: () = state
+            .redis
+            .get()
+            .await?
+            .del(TwoFactor::user_attempts_key(user))
+            .await?;
+        query!(
+            "INSERT INTO user_totp (user_id, secret) VALUES ($1, $2)",
+            user.get(),
+            b"12345678901234567890".as_slice()
+        )
+        .execute(&state.postgres)
+        .await?;
+        // each login stays under its own limit, but they add up
+        for _ in 0..TwoFactor::USER_ATTEMPTS {
+            let token = TwoFactor::start_login(&state, user).await?;
+            let attempt = TwoFactor::finish_login(&state, &token, "not a code").await?;
+            assert_eq!(attempt, LoginAttempt::Incorrect);
+        }
+        let token = TwoFactor::start_login(&state, user).await?;
+        let attempt = TwoFactor::finish_login(&state, &token, "not a code").await;
+        assert!(matches!(attempt, Err(Error::TwoFactorLockedOut)));
+        Ok(())
+    }
+}
@@ -67,6 +67,10 @@ impl User {
         else {
             return Ok(Err(()));
         };
+        // accounts made through another site may not have a password
+        let Some(phc_string) = record.password else {
+            return Ok(Err(()));
+        };
         let password_result = state
             .spawn_rayon(
                 |state,
@@ -75,7 +79,7 @@ impl User {
                     let hash = PasswordHash::new(&phc_string)?;
                     Ok(state.argon.verify_password(password.as_ref(), &hash))
                 },
-                (record.password, password),
+                (phc_string, password),
             )
             .await??;
         let user = User {
@@ -189,6 +193,10 @@ impl FromRequestParts<AppState> for User {
             redis.get(format!("token:user:{}", cookie.value())).await?;
         let user_id =
             maybe_user_id.ok_or_else(|| Error::NeedsLogin(parts.uri.path().to_owned()))?;
+        if !super::Session::touch(state, cookie.value()).await? {
+            super::Session::end(state, cookie.value()).await?;
+            return Err(Error::NeedsLogin(parts.uri.path().to_owned()));
+        }
 
         let maybe_user: Option<String> = redis.get(format!("user:{user_id}")).await?;
         let user = maybe_user.ok_or(Error::TokenHasIdButIdIsUnkown)?;
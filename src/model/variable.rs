@@ -0,0 +1,265 @@
+use sqlx::PgConnection;
+
+use super::Category;
+use crate::{
+    id::{CategoryMarker, GameMarker, Id, VariableMarker, VariableValueMarker},
+    AppState, Error,
+};
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Hash, PartialEq, Eq, Clone)]
+pub struct Variable {
+    pub id: Id<VariableMarker>,
+    pub game: Id<GameMarker>,
+    pub category: Id<CategoryMarker>,
+    pub name: String,
+    pub flags: i64,
+    pub values: Vec<VariableValue>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Hash, PartialEq, Eq, Clone)]
+pub struct VariableValue {
+    pub id: Id<VariableValueMarker>,
+    pub variable: Id<VariableMarker>,
+    pub name: String,
+    pub flags: i64,
+}
+
+impl Variable {
+    /// Fetch every variable of a category, along with all of their values.
+    /// Both variables and values are ordered by creation.
+    pub async fn from_db_category(
+        state: &AppState,
+        category: Id<CategoryMarker>,
+    ) -> Result<Vec<Self>, Error> {
+        let variable_rows = query!(
+            "SELECT id, game, category, name, flags
+            FROM variables WHERE category = $1
+            ORDER BY id",
+            category.get()
+        )
+        .fetch_all(&state.postgres)
+        .await?;
+        let value_rows = query!(
+            "SELECT variable_values.id, variable_values.variable,
+            variable_values.name, variable_values.flags
+            FROM variable_values
+            JOIN variables ON variable_values.variable = variables.id
+            WHERE variables.category = $1
+            ORDER BY variable_values.id",
+            category.get()
+        )
+        .fetch_all(&state.postgres)
+        .await?;
+        let mut variables: Vec<Self> = variable_rows
+            .into_iter()
+            .map(|row| Self {
+                id: Id::new(row.id),
+                game: Id::new(row.game),
+                category: Id::new(row.category),
+                name: row.name,
+                flags: row.flags,
+                values: Vec::new(),
+            })
+            .collect();
+        for row in value_rows {
+            let value = VariableValue {
+                id: Id::new(row.id),
+                variable: Id::new(row.variable),
+                name: row.name,
+                flags: row.flags,
+            };
+            if let Some(variable) = variables.iter_mut().find(|v| v.id == value.variable) {
+                variable.values.push(value);
+            }
+        }
+        Ok(variables)
+    }
+
+    /// Pick one value for every variable which has any values. Requested values
+    /// are used where they belong to a variable, and every other variable falls
+    /// back to its first value. Unknown requested values are ignored.
+    pub fn resolve_selection(
+        variables: &[Self],
+        requested: &[Id<VariableValueMarker>],
+    ) -> Vec<Id<VariableValueMarker>> {
+        variables
+            .iter()
+            .filter_map(|variable| {
+                variable
+                    .values
+                    .iter()
+                    .find(|value| requested.contains(&value.id))
+                    .or_else(|| variable.values.first())
+                    .map(|value| value.id)
+            })
+            .collect()
+    }
+
+    /// Check that `submitted` contains exactly one value for every variable
+    /// which has values, and nothing else. Returns the chosen values.
+    pub fn validate_submission<'a>(
+        variables: &'a [Self],
+        submitted: &[Id<VariableValueMarker>],
+    ) -> Result<Vec<&'a VariableValue>, Error> {
+        let mut chosen_values = Vec::with_capacity(variables.len());
+        for variable in variables.iter().filter(|v| !v.values.is_empty()) {
+            let mut chosen = variable
+                .values
+                .iter()
+                .filter(|value| submitted.contains(&value.id));
+            let Some(value) = chosen.next() else {
+                return Err(Error::CustomFormValidation(format!(
+                    "a value must be chosen for {}",
+                    variable.name
+                )));
+            };
+            if chosen.next().is_some() {
+                return Err(Error::CustomFormValidation(format!(
+                    "only one value can be chosen for {}",
+                    variable.name
+                )));
+            }
+            chosen_values.push(value);
+        }
+        if chosen_values.len() != submitted.len() {
+            return Err(Error::InvalidVariableValue);
+        }
+        Ok(chosen_values)
+    }
+}
+
+impl VariableValue {
+    /// Add a value to `variable`. Runs without a value for the variable, which were
+    /// submitted before it had any, get this one so they stay on its first board.
+    pub async fn create(
+        conn: &mut PgConnection,
+        game: Id<GameMarker>,
+        category: Id<CategoryMarker>,
+        variable: Id<VariableMarker>,
+        name: &str,
+    ) -> Result<(), Error> {
+        let value = query!(
+            "INSERT INTO variable_values (variable, name)
+            SELECT id, $4 FROM variables
+            WHERE id = $1 AND category = $2 AND game = $3
+            RETURNING id",
+            variable.get(),
+            category.get(),
+            game.get(),
+            name
+        )
+        .fetch_optional(&mut *conn)
+        .await?
+        .ok_or(Error::NotFound)?
+        .id;
+        let filled = query!(
+            "INSERT INTO run_variables (run, variable, value)
+            SELECT runs.id, $2, $3 FROM runs
+            WHERE runs.category = $1 AND NOT EXISTS (
+                SELECT 1 FROM run_variables
+                WHERE run_variables.run = runs.id AND run_variables.variable = $2
+            )",
+            category.get(),
+            variable.get(),
+            value
+        )
+        .execute(&mut *conn)
+        .await?
+        .rows_affected();
+        if filled > 0 {
+            Category::update_obsoleted(&mut *conn, category, None).await?;
+        }
+        Ok(())
+    }
+
+    /// Delete a value of `variable`, as long as no runs were submitted with it
+    pub async fn delete(
+        conn: &mut PgConnection,
+        game: Id<GameMarker>,
+        category: Id<CategoryMarker>,
+        variable: Id<VariableMarker>,
+        value: Id<VariableValueMarker>,
+    ) -> Result<(), Error> {
+        let in_use = query!(
+            r#"SELECT EXISTS(SELECT 1 FROM run_variables WHERE value = $1) AS "in_use!""#,
+            value.get()
+        )
+        .fetch_one(&mut *conn)
+        .await?
+        .in_use;
+        if in_use {
+            return Err(Error::VariableValueInUse);
+        }
+        query!(
+            "DELETE FROM variable_values
+            USING variables
+            WHERE variable_values.variable = variables.id
+            AND variable_values.id = $1 AND variables.id = $2
+            AND variables.category = $3 AND variables.game = $4",
+            value.get(),
+            variable.get(),
+            category.get(),
+            game.get()
+        )
+        .execute(&mut *conn)
+        .await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn variable(id: i64, value_ids: &[i64]) -> Variable {
+        Variable {
+            id: Id::new(id),
+            game: Id::new(1),
+            category: Id::new(1),
+            name: format!("variable {id}"),
+            flags: 0,
+            values: value_ids
+                .iter()
+                .map(|value_id| VariableValue {
+                    id: Id::new(*value_id),
+                    variable: Id::new(id),
+                    name: format!("value {value_id}"),
+                    flags: 0,
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn selection_defaults_to_first_value() {
+        let variables = [
+            variable(1, &[10, 11]),
+            variable(2, &[]),
+            variable(3, &[30, 31]),
+        ];
+        let selection = Variable::resolve_selection(&variables, &[Id::new(31), Id::new(99)]);
+        assert_eq!(selection, vec![Id::new(10), Id::new(31)]);
+    }
+
+    #[test]
+    fn submission_needs_one_value_per_variable() {
+        let variables = [variable(1, &[10, 11]), variable(2, &[20])];
+        let chosen =
+            Variable::validate_submission(&variables, &[Id::new(11), Id::new(20)]).unwrap();
+        assert_eq!(
+            chosen,
+            vec![&variables[0].values[1], &variables[1].values[0]]
+        );
+        assert!(Variable::validate_submission(&variables, &[Id::new(11)]).is_err());
+        assert!(Variable::validate_submission(
+            &variables,
+            &[Id::new(10), Id::new(11), Id::new(20)]
+        )
+        .is_err());
+        assert!(Variable::validate_submission(
+            &variables,
+            &[Id::new(11), Id::new(20), Id::new(99)]
+        )
+        .is_err());
+    }
+}
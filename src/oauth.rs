@@ -0,0 +1,493 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use rand::distributions::{Alphanumeric, DistString};
+use redis::AsyncCommands;
+use reqwest::header::ACCEPT;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use url::Url;
+
+use crate::{
+    id::{Id, UserMarker},
+    AppState, Error,
+};
+
+/// An OpenID Connect or OAuth2 provider users can log in with.
+/// Each is configured with `OAUTH_{NAME}_*` variables, see `docs/oauth.md`.
+#[derive(serde::Deserialize, Debug, Clone)]
+pub struct OAuthProvider {
+    /// What the provider is called in URLs and the database
+    #[serde(skip)]
+    pub name: String,
+    /// What the provider is called on buttons
+    pub display_name: Option<String>,
+    pub client_id: String,
+    pub client_secret: String,
+    /// OpenID Connect providers have their endpoints discovered from their issuer
+    pub issuer: Option<String>,
+    pub authorize_url: Option<String>,
+    pub token_url: Option<String>,
+    pub userinfo_url: Option<String>,
+    #[serde(default = "defaults::scopes")]
+    pub scopes: String,
+    /// Plain OAuth2 providers name their userinfo fields differently,
+    /// like `id` and `verified` for Discord
+    #[serde(default = "defaults::id_field")]
+    pub id_field: String,
+    #[serde(default = "defaults::email_field")]
+    pub email_field: String,
+    #[serde(default = "defaults::email_verified_field")]
+    pub email_verified_field: String,
+    #[serde(default = "defaults::name_field")]
+    pub name_field: String,
+}
+
+mod defaults {
+    pub(super) fn scopes() -> String {
+        String::from("openid email profile")
+    }
+
+    pub(super) fn id_field() -> String {
+        String::from("sub")
+    }
+
+    pub(super) fn email_field() -> String {
+        String::from("email")
+    }
+
+    pub(super) fn email_verified_field() -> String {
+        String::from("email_verified")
+    }
+
+    pub(super) fn name_field() -> String {
+        String::from("preferred_username")
+    }
+}
+
+/// Read the providers named by `OAUTH_PROVIDERS`, a comma-separated list
+pub fn deserialize_providers<'de, D>(input: D) -> Result<Vec<OAuthProvider>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let names: Vec<String> = serde::Deserialize::deserialize(input)?;
+    names
+        .into_iter()
+        .map(|name| {
+            let prefix = format!("OAUTH_{}_", name.to_uppercase());
+            let provider = envy::prefixed(&prefix)
+                .from_env::<OAuthProvider>()
+                .map_err(serde::de::Error::custom)?;
+            let explicit = provider.authorize_url.is_some()
+                && provider.token_url.is_some()
+                && provider.userinfo_url.is_some();
+            if provider.issuer.is_none() && !explicit {
+                return Err(serde::de::Error::custom(format!(
+                    "{prefix}ISSUER, or all of {prefix}AUTHORIZE_URL, {prefix}TOKEN_URL \
+                    and {prefix}USERINFO_URL, must be set"
+                )));
+            }
+            Ok(OAuthProvider { name, ..provider })
+        })
+        .collect()
+}
+
+/// Where a provider's users are sent and asked about. The names match OpenID Connect discovery.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Endpoints {
+    pub authorization_endpoint: String,
+    pub token_endpoint: String,
+    pub userinfo_endpoint: String,
+}
+
+/// A provider, as shown on login and settings pages
+#[derive(serde::Serialize, Debug, Clone)]
+pub struct ProviderInfo {
+    pub name: String,
+    pub display_name: String,
+}
+
+/// Who a provider says logged in
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Identity {
+    /// The provider's id for the account
+    pub subject: String,
+    pub email: Option<String>,
+    pub email_verified: bool,
+    pub name: Option<String>,
+}
+
+/// A login that's been sent off to a provider, stored at `oauth:state:{state}` until they come back
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct PendingAuthorization {
+    pub provider: String,
+    /// The PKCE code verifier, which proves the code is being redeemed by whoever asked for it
+    verifier: String,
+    pub return_to: String,
+    /// Who's linking the account, if it isn't being used to log in
+    pub link_user: Option<Id<UserMarker>>,
+}
+
+#[derive(serde::Deserialize, Debug, Clone)]
+struct TokenResponse {
+    access_token: String,
+}
+
+impl OAuthProvider {
+    /// Seconds users have to log in with the provider
+    pub const STATE_TTL: u64 = 10 * 60;
+    /// Discovered endpoints are cached for this many seconds
+    const DISCOVERY_TTL: u64 = 60 * 60;
+
+    pub fn info(&self) -> ProviderInfo {
+        ProviderInfo {
+            name: self.name.clone(),
+            display_name: self
+                .display_name
+                .clone()
+                .unwrap_or_else(|| self.name.clone()),
+        }
+    }
+
+    pub fn redirect_uri(&self, root_url: &str) -> String {
+        format!("{root_url}/login/oauth/{}/callback", self.name)
+    }
+
+    pub async fn endpoints(&self, state: &AppState) -> Result<Endpoints, Error> {
+        if let (Some(authorize), Some(token), Some(userinfo)) =
+            (&self.authorize_url, &self.token_url, &self.userinfo_url)
+        {
+            return Ok(Endpoints {
+                authorization_endpoint: authorize.clone(),
+                token_endpoint: token.clone(),
+                userinfo_endpoint: userinfo.clone(),
+            });
+        }
+        let key = format!("oauth:discovery:{}", self.name);
+        if let Some(endpoints) = state.get_redis_object(&key).await? {
+            return Ok(endpoints);
+        }
+        let issuer = self.issuer.as_deref().unwrap_or_default();
+        let endpoints = discover(&state.http, issuer).await?;
+        state
+            .set_redis_object(&key, &endpoints, Self::DISCOVERY_TTL)
+            .await?;
+        Ok(endpoints)
+    }
+
+    /// Start logging in, returning where to send the user and the state to expect back
+    pub async fn authorize(
+        &self,
+        state: &AppState,
+        return_to: String,
+        link_user: Option<Id<UserMarker>>,
+    ) -> Result<(String, String), Error> {
+        let endpoints = self.endpoints(state).await?;
+        let csrf_state = Alphanumeric.sample_string(&mut rand::thread_rng(), 32);
+        let pending = PendingAuthorization {
+            provider: self.name.clone(),
+            verifier: Alphanumeric.sample_string(&mut rand::thread_rng(), 64),
+            return_to,
+            link_user,
+        };
+        let url = self.authorization_url(
+            &endpoints,
+            &self.redirect_uri(&state.config.root_url),
+            &csrf_state,
+            &pending.verifier,
+        )?;
+        state
+            .redis
+            .get()
+            .await?
+            .set_ex(
+                format!("oauth:state:{csrf_state}"),
+                serde_json::to_string(&pending)?,
+                Self::STATE_TTL,
+            )
+            .await?;
+        Ok((url, csrf_state))
+    }
+
+    fn authorization_url(
+        &self,
+        endpoints: &Endpoints,
+        redirect_uri: &str,
+        csrf_state: &str,
+        verifier: &str,
+    ) -> Result<String, Error> {
+        let mut url = Url::parse(&endpoints.authorization_endpoint)?;
+        url.query_pairs_mut()
+            .append_pair("response_type", "code")
+            .append_pair("client_id", &self.client_id)
+            .append_pair("redirect_uri", redirect_uri)
+            .append_pair("scope", &self.scopes)
+            .append_pair("state", csrf_state)
+            .append_pair("code_challenge", &code_challenge(verifier))
+            .append_pair("code_challenge_method", "S256");
+        Ok(url.into())
+    }
+
+    /// Trade the code the user came back with for who they are
+    pub async fn exchange(
+        &self,
+        http: &reqwest::Client,
+        endpoints: &Endpoints,
+        redirect_uri: &str,
+        code: &str,
+        pending: &PendingAuthorization,
+    ) -> Result<Identity, Error> {
+        let response = http
+            .post(&endpoints.token_endpoint)
+            .header(ACCEPT, "application/json")
+            .form(&[
+                ("grant_type", "authorization_code"),
+                ("code", code),
+                ("redirect_uri", redirect_uri),
+                ("client_id", &self.client_id),
+                ("client_secret", &self.client_secret),
+                ("code_verifier", &pending.verifier),
+            ])
+            .send()
+            .await?
+            .error_for_status()?
+            .bytes()
+            .await?;
+        let token: TokenResponse = serde_json::from_slice(&response)?;
+        let userinfo = http
+            .get(&endpoints.userinfo_endpoint)
+            .header(ACCEPT, "application/json")
+            .bearer_auth(token.access_token)
+            .send()
+            .await?
+            .error_for_status()?
+            .bytes()
+            .await?;
+        self.identity(&serde_json::from_slice(&userinfo)?)
+    }
+
+    fn identity(&self, userinfo: &Value) -> Result<Identity, Error> {
+        let subject = match userinfo.get(&self.id_field) {
+            Some(Value::String(id)) => id.clone(),
+            Some(Value::Number(id)) => id.to_string(),
+            _ => return Err(Error::OAuthMissingField(self.id_field.clone())),
+        };
+        let string_field = |name: &str| {
+            userinfo
+                .get(name)
+                .and_then(Value::as_str)
+                .map(str::to_string)
+        };
+        Ok(Identity {
+            subject,
+            email: string_field(&self.email_field),
+            email_verified: userinfo
+                .get(&self.email_verified_field)
+                .and_then(Value::as_bool)
+                .unwrap_or(false),
+            name: string_field(&self.name_field),
+        })
+    }
+}
+
+/// The provider called `name`, if one is configured
+pub fn find_provider<'a>(
+    providers: &'a [OAuthProvider],
+    name: &str,
+) -> Result<&'a OAuthProvider, Error> {
+    providers
+        .iter()
+        .find(|provider| provider.name == name)
+        .ok_or(Error::NotFound)
+}
+
+pub fn provider_infos(providers: &[OAuthProvider]) -> Vec<ProviderInfo> {
+    providers.iter().map(OAuthProvider::info).collect()
+}
+
+/// Look up the endpoints of an OpenID Connect issuer
+pub async fn discover(http: &reqwest::Client, issuer: &str) -> Result<Endpoints, Error> {
+    let url = format!(
+        "{}/.well-known/openid-configuration",
+        issuer.trim_end_matches('/')
+    );
+    let response = http
+        .get(url)
+        .header(ACCEPT, "application/json")
+        .send()
+        .await?
+        .error_for_status()?
+        .bytes()
+        .await?;
+    Ok(serde_json::from_slice(&response)?)
+}
+
+/// Use up the login started with `csrf_state`, so it can only be finished once
+pub async fn take_pending(
+    state: &AppState,
+    csrf_state: &str,
+) -> Result<PendingAuthorization, Error> {
+    let pending: Option<String> = state
+        .redis
+        .get()
+        .await?
+        .get_del(format!("oauth:state:{csrf_state}"))
+        .await?;
+    let pending = pending.ok_or(Error::InvalidOAuthState)?;
+    Ok(serde_json::from_str(&pending)?)
+}
+
+/// The PKCE S256 challenge for `verifier`
+fn code_challenge(verifier: &str) -> String {
+    URL_SAFE_NO_PAD.encode(Sha256::digest(verifier.as_bytes()))
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use axum::{
+        extract::State,
+        http::{HeaderMap, StatusCode},
+        routing::{get, post},
+        Form, Json, Router,
+    };
+
+    use super::*;
+
+    /// Just enough of an identity provider to log in with
+    async fn mock_provider() -> String {
+        async fn discovery(State(issuer): State<String>) -> Json<Endpoints> {
+            Json(Endpoints {
+                authorization_endpoint: format!("{issuer}/authorize"),
+                token_endpoint: format!("{issuer}/token"),
+                userinfo_endpoint: format!("{issuer}/userinfo"),
+            })
+        }
+
+        async fn token(
+            Form(form): Form<HashMap<String, String>>,
+        ) -> Result<Json<Value>, StatusCode> {
+            let field = |name: &str| form.get(name).map(String::as_str);
+            if field("grant_type") != Some("authorization_code")
+                || field("code") != Some("good code")
+                || field("client_secret") != Some("secret")
+                || field("code_verifier").is_none()
+            {
+                return Err(StatusCode::BAD_REQUEST);
+            }
+            Ok(Json(serde_json::json!({
+                "access_token": "access",
+                "token_type": "Bearer",
+            })))
+        }
+
+        async fn userinfo(headers: HeaderMap) -> Result<Json<Value>, StatusCode> {
+            if headers.get("authorization").and_then(|v| v.to_str().ok()) != Some("Bearer access") {
+                return Err(StatusCode::UNAUTHORIZED);
+            }
+            Ok(Json(serde_json::json!({
+                "sub": "1234",
+                "email": "runner@example.com",
+                "email_verified": true,
+                "preferred_username": "Runner",
+            })))
+        }
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let issuer = format!("http://{}", listener.local_addr().unwrap());
+        let app = Router::new()
+            .route("/.well-known/openid-configuration", get(discovery))
+            .route("/token", post(token))
+            .route("/userinfo", get(userinfo))
+            .with_state(issuer.clone());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        issuer
+    }
+
+    fn provider(issuer: &str) -> OAuthProvider {
+        OAuthProvider {
+            name: "mock".to_string(),
+            display_name: None,
+            client_id: "client".to_string(),
+            client_secret: "secret".to_string(),
+            issuer: Some(issuer.to_string()),
+            authorize_url: None,
+            token_url: None,
+            userinfo_url: None,
+            scopes: defaults::scopes(),
+            id_field: defaults::id_field(),
+            email_field: defaults::email_field(),
+            email_verified_field: defaults::email_verified_field(),
+            name_field: defaults::name_field(),
+        }
+    }
+
+    #[tokio::test]
+    async fn mock_provider_login() {
+        let issuer = mock_provider().await;
+        let provider = provider(&issuer);
+        let http = reqwest::Client::new();
+        let endpoints = discover(&http, &issuer).await.unwrap();
+        assert_eq!(endpoints.token_endpoint, format!("{issuer}/token"));
+        let pending = PendingAuthorization {
+            provider: "mock".to_string(),
+            verifier: "verifier".to_string(),
+            return_to: "/".to_string(),
+            link_user: None,
+        };
+        let redirect_uri = provider.redirect_uri("http://localhost:8080");
+        let identity = provider
+            .exchange(&http, &endpoints, &redirect_uri, "good code", &pending)
+            .await
+            .unwrap();
+        assert_eq!(
+            identity,
+            Identity {
+                subject: "1234".to_string(),
+                email: Some("runner@example.com".to_string()),
+                email_verified: true,
+                name: Some("Runner".to_string()),
+            }
+        );
+        assert!(provider
+            .exchange(&http, &endpoints, &redirect_uri, "bad code", &pending)
+            .await
+            .is_err());
+    }
+
+    #[test]
+    fn authorization_urls() {
+        let provider = provider("https://id.example.com");
+        let endpoints = Endpoints {
+            authorization_endpoint: "https://id.example.com/authorize?prompt=consent".to_string(),
+            token_endpoint: String::new(),
+            userinfo_endpoint: String::new(),
+        };
+        let url = provider
+            .authorization_url(&endpoints, "http://localhost/callback", "state", "verifier")
+            .unwrap();
+        let url = Url::parse(&url).unwrap();
+        let pairs: HashMap<String, String> = url.query_pairs().into_owned().collect();
+        assert_eq!(pairs["prompt"], "consent");
+        assert_eq!(pairs["client_id"], "client");
+        assert_eq!(pairs["redirect_uri"], "http://localhost/callback");
+        assert_eq!(pairs["state"], "state");
+        assert_eq!(pairs["code_challenge"], code_challenge("verifier"));
+        // the unpadded base64url SHA-256 of the verifier
+        assert_eq!(
+            code_challenge("dBjftJeZ4CVP-mB92K27uhbUJU2p1r_wW1gFWFOEjXk"),
+            "q4kBDKNrgzS8bDCkGXg71yviKVsRgSVuTTyTHQPhLYM"
+        );
+    }
+
+    #[test]
+    fn numeric_ids() {
+        let provider = OAuthProvider {
+            id_field: "id".to_string(),
+            ..provider("https://id.example.com")
+        };
+        let identity = provider.identity(&serde_json::json!({ "id": 42 })).unwrap();
+        assert_eq!(identity.subject, "42");
+        assert_eq!(identity.email, None);
+        assert!(provider.identity(&serde_json::json!({})).is_err());
+    }
+}
@@ -20,12 +20,28 @@ pub fn build(state: AppState) -> Router {
     Router::new()
         .route("/", get(routes::index::get))
         .route_with_tsr("/login", get(routes::login::get).post(routes::login::post))
+        .route_with_tsr("/login/two-factor", post(routes::login::two_factor))
+        .route_with_tsr("/login/oauth/:provider", get(routes::oauth::start))
+        .route_with_tsr(
+            "/login/oauth/:provider/callback",
+            get(routes::oauth::callback),
+        )
         .route_with_tsr("/logout", get(routes::login::logout))
+        .route_with_tsr(
+            "/forgot-password",
+            get(routes::reset_password::forgot).post(routes::reset_password::request),
+        )
+        .route_with_tsr(
+            "/reset-password",
+            get(routes::reset_password::get).post(routes::reset_password::post),
+        )
         .route_with_tsr(
             "/signup",
             get(routes::signup::get).post(routes::signup::post),
         )
         .route_with_tsr("/user/:username", get(routes::user::get))
+        .route_with_tsr("/verify-email", get(routes::verify_email::get))
+        .route_with_tsr("/search", get(routes::search::get))
         .merge(settings_router(state.clone()))
         .merge(game_router(state.clone()))
         .merge(forum_router(state.clone()))
@@ -75,6 +91,38 @@ pub fn settings_router(state: AppState) -> Router<AppState> {
             "/settings/email",
             post(routes::settings::credentials::update_email),
         )
+        .route(
+            "/settings/email/verify",
+            post(routes::settings::credentials::resend_verification),
+        )
+        .route(
+            "/settings/sessions/revoke",
+            post(routes::settings::sessions::revoke),
+        )
+        .route(
+            "/settings/sessions/revoke-others",
+            post(routes::settings::sessions::revoke_others),
+        )
+        .route(
+            "/settings/two-factor",
+            get(routes::settings::two_factor::setup).post(routes::settings::two_factor::enable),
+        )
+        .route(
+            "/settings/two-factor/disable",
+            post(routes::settings::two_factor::disable),
+        )
+        .route(
+            "/settings/two-factor/recovery",
+            post(routes::settings::two_factor::recovery_codes),
+        )
+        .route(
+            "/settings/linked-accounts/:provider",
+            post(routes::oauth::link),
+        )
+        .route(
+            "/settings/linked-accounts/:provider/unlink",
+            post(routes::oauth::unlink),
+        )
         .route(
             "/settings/password",
             post(routes::settings::credentials::update_password),
@@ -96,6 +144,40 @@ pub fn game_router(state: AppState) -> Router<AppState> {
             "/game/:gameslug/edit/new-category",
             post(routes::game::settings::category::new),
         )
+        .route_with_tsr(
+            "/game/:gameslug/edit/level/new",
+            post(routes::game::settings::level::new),
+        )
+        .route_with_tsr(
+            "/game/:gameslug/edit/level/:levelid/delete",
+            get(routes::game::settings::level::confirm_delete)
+                .post(routes::game::settings::level::delete),
+        )
+        .route_with_tsr(
+            "/game/:gameslug/edit/platform/new",
+            post(routes::game::settings::platform::new),
+        )
+        .route_with_tsr(
+            "/game/:gameslug/edit/platform/:platformid/delete",
+            get(routes::game::settings::platform::confirm_delete)
+                .post(routes::game::settings::platform::delete),
+        )
+        .route_with_tsr(
+            "/game/:gameslug/edit/ranking",
+            post(routes::game::settings::game::edit_ranking),
+        )
+        .route_with_tsr(
+            "/game/:gameslug/edit/two-factor",
+            post(routes::game::settings::game::edit_two_factor),
+        )
+        .route_with_tsr(
+            "/game/:gameslug/edit/reason/new",
+            post(routes::game::settings::canned_reason::new),
+        )
+        .route_with_tsr(
+            "/game/:gameslug/edit/reason/:reasonid/delete",
+            post(routes::game::settings::canned_reason::delete),
+        )
         .route_with_tsr(
             "/game/:gameslug/category/:catid/edit/makedefault",
             any(routes::game::settings::game::set_default_category),
@@ -109,6 +191,11 @@ pub fn game_router(state: AppState) -> Router<AppState> {
             "/game/:gameslug/feed",
             get(routes::game::modtools::feed::game_feed),
         )
+        .route_with_tsr(
+            "/game/:gameslug/feed/bulk",
+            post(routes::game::modtools::run::bulk_review_game),
+        )
+        .route_with_tsr("/game/:gameslug/ranking", get(routes::game::ranking::get))
         .route_with_tsr(
             "/game/:gameslug/team",
             get(routes::game::modtools::team::get).post(routes::game::modtools::team::post),
@@ -117,14 +204,38 @@ pub fn game_router(state: AppState) -> Router<AppState> {
             "/game/:gameslug/category/:catid",
             get(routes::game::category::specific_category),
         )
+        .route_with_tsr(
+            "/game/:gameslug/category/:catid/history",
+            get(routes::game::history::get),
+        )
         .route_with_tsr(
             "/game/:gameslug/category/:catid/feed",
             get(routes::game::modtools::feed::category_feed),
         )
+        .route_with_tsr(
+            "/game/:gameslug/category/:catid/feed/bulk",
+            post(routes::game::modtools::run::bulk_review_category),
+        )
         .route_with_tsr(
             "/game/:gameslug/category/:catid/edit",
             get(routes::game::settings::category::get).post(routes::game::settings::category::edit),
         )
+        .route_with_tsr(
+            "/game/:gameslug/category/:catid/edit/variable/new",
+            post(routes::game::settings::variable::new),
+        )
+        .route_with_tsr(
+            "/game/:gameslug/category/:catid/edit/variable/:varid/delete",
+            post(routes::game::settings::variable::delete),
+        )
+        .route_with_tsr(
+            "/game/:gameslug/category/:catid/edit/variable/:varid/value/new",
+            post(routes::game::settings::variable::new_value),
+        )
+        .route_with_tsr(
+            "/game/:gameslug/category/:catid/edit/variable/:varid/value/:valueid/delete",
+            post(routes::game::settings::variable::delete_value),
+        )
         .route_with_tsr(
             "/game/:gameslug/category/:catid/run/new",
             get(routes::game::new_run::get).post(routes::game::new_run::create),
@@ -133,6 +244,26 @@ pub fn game_router(state: AppState) -> Router<AppState> {
             "/game/:gameslug/category/:catid/run/:runid",
             get(routes::game::run::get),
         )
+        .route_with_tsr(
+            "/game/:gameslug/category/:catid/run/:runid/edit",
+            get(routes::game::edit_run::get).post(routes::game::edit_run::post),
+        )
+        .route_with_tsr(
+            "/game/:gameslug/category/:catid/run/:runid/splits",
+            post(routes::game::splits::upload),
+        )
+        .route_with_tsr(
+            "/game/:gameslug/category/:catid/run/:runid/splits/delete",
+            post(routes::game::splits::delete),
+        )
+        .route_with_tsr(
+            "/game/:gameslug/category/:catid/run/:runid/comment",
+            post(routes::game::run::comment),
+        )
+        .route_with_tsr(
+            "/game/:gameslug/category/:catid/run/:runid/comment/:commentid/delete",
+            post(routes::game::run::delete_comment),
+        )
         .route_with_tsr(
             "/game/:gameslug/category/:catid/run/:runid/delete",
             any(routes::game::run::delete),
@@ -149,6 +280,14 @@ pub fn game_router(state: AppState) -> Router<AppState> {
             "/game/:gameslug/category/:catid/run/:runid/reject",
             post(routes::game::modtools::run::reject_run),
         )
+        .route_with_tsr(
+            "/game/:gameslug/category/:catid/run/:runid/claim",
+            post(routes::game::modtools::run::claim_run),
+        )
+        .route_with_tsr(
+            "/game/:gameslug/category/:catid/run/:runid/release",
+            post(routes::game::modtools::run::release_run),
+        )
         .with_state(state)
 }
 
@@ -1,18 +1,27 @@
 use std::sync::Arc;
 
-use axum::extract::{Path, Query, State};
+use axum::extract::{Path, State};
+use axum_extra::extract::Query;
 
 use crate::{
-    id::{CategoryMarker, Id},
-    model::{Category, Game, MiniCategory, ResolvedRun, RunStatus, SortBy},
+    id::{CategoryMarker, Id, LevelMarker, PlatformMarker, RunMarker, VariableValueMarker},
+    model::{
+        Category, Game, LeaderboardFilter, Level, MiniCategory, PageCursor, Platform, RankedRun,
+        ResolvedRun, RunStatus, Variable, VariableValue,
+    },
     template::BaseRenderInfo,
     AppState, Error, HandlerResult,
 };
 
 #[derive(serde::Deserialize, Debug, Clone)]
 pub struct GetCategoryQuery {
-    #[serde(default = "crate::util::return_0_usize")]
-    page: usize,
+    after: Option<Id<RunMarker>>,
+    before: Option<Id<RunMarker>>,
+    #[serde(default)]
+    values: Vec<Id<VariableValueMarker>>,
+    level: Option<Id<LevelMarker>>,
+    platform: Option<Id<PlatformMarker>>,
+    emulated: Option<bool>,
 }
 
 #[derive(serde::Serialize, Debug, Clone)]
@@ -21,18 +30,53 @@ pub struct GamePage {
     base: BaseRenderInfo,
     categories: Vec<MiniCategory>,
     category: Category,
-    has_next: bool,
-    runs: Vec<ResolvedRun>,
+    variables: Vec<VariableSelector>,
+    /// query string which keeps the current variable selection
+    selection_query: String,
+    levels: Vec<Level>,
+    level: Option<Level>,
+    platforms: Vec<FilterOption<Option<Platform>>>,
+    emulated: Vec<FilterOption<Option<bool>>>,
+    /// query string which keeps the current platform and emulator filters
+    platform_query: String,
+    /// the run the next page starts after
+    next_cursor: Option<Id<RunMarker>>,
+    /// the run the previous page ends before
+    previous_cursor: Option<Id<RunMarker>>,
+    runs: Vec<RankedRun>,
     game: Arc<Game>,
 }
 
+#[derive(serde::Serialize, Debug, Clone)]
+pub struct VariableSelector {
+    name: String,
+    options: Vec<VariableOption>,
+}
+
+#[derive(serde::Serialize, Debug, Clone)]
+pub struct VariableOption {
+    value: VariableValue,
+    selected: bool,
+    /// query string which selects this value, keeping the other variables as-is
+    query: String,
+}
+
+/// One choice of a filter, which is either "any" (`None`) or a specific value
+#[derive(serde::Serialize, Debug, Clone)]
+pub struct FilterOption<T> {
+    value: T,
+    selected: bool,
+    /// query string which selects this option, keeping the other platform filter as-is
+    query: String,
+}
+
 pub async fn default_category(
     State(state): State<AppState>,
     Path(game_slug): Path<String>,
     Query(query): Query<GetCategoryQuery>,
     base: BaseRenderInfo,
 ) -> HandlerResult {
-    get_game_category(&state, base, game_slug, None, query.page).await
+    get_game_category(&state, base, game_slug, None, query).await
 }
 
 pub async fn specific_category(
@@ -41,7 +85,7 @@ pub async fn specific_category(
     Query(query): Query<GetCategoryQuery>,
     base: BaseRenderInfo,
 ) -> HandlerResult {
-    get_game_category(&state, base, game_slug, Some(category_id), query.page).await
+    get_game_category(&state, base, game_slug, Some(category_id), query).await
 }
 
 const RUNS_PER_PAGE: usize = 50;
@@ -51,7 +95,7 @@ pub(super) async fn get_game_category(
     base: BaseRenderInfo,
     game_slug: String,
     maybe_category_id: Option<Id<CategoryMarker>>,
-    page: usize,
+    query: GetCategoryQuery,
 ) -> HandlerResult {
     let game = Arc::new(Game::from_db_slug(state, &game_slug).await?);
     let category_id = maybe_category_id.unwrap_or(game.default_category);
@@ -60,7 +104,7 @@ pub(super) async fn get_game_category(
     let spawned_getcats = tokio::spawn(async move {
         query_as!(
             MiniCategory,
-            "SELECT name, id, game, scoreboard, flags
+            "SELECT name, id, game, scoreboard, per_level, flags
             FROM categories WHERE game = $1",
             game_id
         )
@@ -75,19 +119,39 @@ pub(super) async fn get_game_category(
     .fetch_optional(&state.postgres)
     .await?
     .ok_or(Error::NotFound)?;
-    let sort_by = if category.scoreboard {
-        SortBy::Score
+    let variables = Variable::from_db_category(state, category.id).await?;
+    let selection = Variable::resolve_selection(&variables, &query.values);
+    let levels = if category.per_level {
+        Level::from_db_game(state, game.id).await?
     } else {
-        SortBy::Time
+        Vec::new()
     };
+    let level = query
+        .level
+        .and_then(|id| levels.iter().find(|level| level.id == id))
+        .or_else(|| levels.first())
+        .cloned();
+    let platforms = Platform::from_db_game(state, game.id).await?;
+    let platform = query
+        .platform
+        .filter(|id| platforms.iter().any(|platform| platform.id == *id));
+    let sort_by = category.sort_by();
     let resolution = ResolvedRun::fetch_leaderboard(
         state,
         game.clone(),
         RunStatus::Verified,
-        Some(category.id),
+        LeaderboardFilter {
+            category: Some(category.id),
+            level: level.as_ref().map(|level| level.id),
+            variables: selection.clone(),
+            platform,
+            emulated: query.emulated,
+            personal_bests_only: true,
+            records_by: None,
+        },
         sort_by,
         RUNS_PER_PAGE,
-        page,
+        PageCursor::from_query(query.after, query.before),
     )
     .await?;
     let categories = spawned_getcats.await??;
@@ -95,9 +159,103 @@ pub(super) async fn get_game_category(
         base,
         categories,
         category,
-        has_next: resolution.has_next(),
-        runs: resolution.resolveds(),
+        selection_query: selection_query(&selection),
+        variables: variable_selectors(variables, &selection),
+        levels,
+        level,
+        platforms: platform_options(platforms, platform, query.emulated),
+        emulated: emulated_options(platform, query.emulated),
+        platform_query: platform_query(platform, query.emulated),
+        next_cursor: resolution.next_cursor(),
+        previous_cursor: resolution.previous_cursor(),
+        runs: resolution.ranked(),
         game,
     };
     state.render("category.jinja", ctx)
 }
+
+pub(super) fn variable_selectors(
+    variables: Vec<Variable>,
+    selection: &[Id<VariableValueMarker>],
+) -> Vec<VariableSelector> {
+    let mut selectors = Vec::with_capacity(variables.len());
+    for variable in variables {
+        let mut others: Vec<Id<VariableValueMarker>> = selection
+            .iter()
+            .copied()
+            .filter(|selected| !variable.values.iter().any(|value| value.id == *selected))
+            .collect();
+        let options = variable
+            .values
+            .into_iter()
+            .map(|value| {
+                others.push(value.id);
+                let query = selection_query(&others);
+                others.pop();
+                VariableOption {
+                    selected: selection.contains(&value.id),
+                    value,
+                    query,
+                }
+            })
+            .collect();
+        selectors.push(VariableSelector {
+            name: variable.name,
+            options,
+        });
+    }
+    selectors
+}
+
+pub(super) fn selection_query(selection: &[Id<VariableValueMarker>]) -> String {
+    selection
+        .iter()
+        .map(|id| format!("values={id}"))
+        .collect::<Vec<String>>()
+        .join("&")
+}
+
+fn platform_options(
+    platforms: Vec<Platform>,
+    selected: Option<Id<PlatformMarker>>,
+    emulated: Option<bool>,
+) -> Vec<FilterOption<Option<Platform>>> {
+    std::iter::once(None)
+        .chain(platforms.into_iter().map(Some))
+        .map(|platform| {
+            let id = platform.as_ref().map(|platform| platform.id);
+            FilterOption {
+                selected: id == selected,
+                query: platform_query(id, emulated),
+                value: platform,
+            }
+        })
+        .collect()
+}
+
+fn emulated_options(
+    platform: Option<Id<PlatformMarker>>,
+    selected: Option<bool>,
+) -> Vec<FilterOption<Option<bool>>> {
+    [None, Some(false), Some(true)]
+        .into_iter()
+        .map(|emulated| FilterOption {
+            value: emulated,
+            selected: emulated == selected,
+            query: platform_query(platform, emulated),
+        })
+        .collect()
+}
+
+pub(super) fn platform_query(
+    platform: Option<Id<PlatformMarker>>,
+    emulated: Option<bool>,
+) -> String {
+    let platform = platform.map(|id| format!("platform={id}"));
+    let emulated = emulated.map(|emulated| format!("emulated={emulated}"));
+    platform
+        .into_iter()
+        .chain(emulated)
+        .collect::<Vec<String>>()
+        .join("&")
+}
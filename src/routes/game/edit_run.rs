@@ -0,0 +1,196 @@
+use std::collections::HashMap;
+
+use axum::{
+    extract::{Path, State},
+    response::Redirect,
+};
+
+use super::new_run::{RunCreateForm, RunSubmission, TimeParts};
+use crate::{
+    id::{CategoryMarker, Id, RunMarker},
+    model::{
+        Category, GameRanking, Level, Member, Permissions, Platform, ResolvedRun, RunRevision,
+        RunStatus, TimingMethod, User, Variable,
+    },
+    template::BaseRenderInfo,
+    util::{game_n_member, ValidatedForm},
+    AppState, Error, HandlerResult,
+};
+
+#[derive(serde::Serialize, Debug, Clone)]
+pub struct RunEditPage {
+    #[serde(flatten)]
+    base: BaseRenderInfo,
+    run: ResolvedRun,
+    variables: Vec<Variable>,
+    levels: Vec<Level>,
+    platforms: Vec<Platform>,
+    /// the current times of the run, keyed by timing method
+    times: HashMap<TimingMethod, TimeParts>,
+}
+
+/// Only the submitter and leaderboard moderators can edit a run
+pub fn check_can_edit(member: &Member, run: &ResolvedRun) -> Result<(), Error> {
+    if member.perms.contains(Permissions::LEADERBOARD_MODERATOR)
+        || member.user.id == run.submitter.id
+    {
+        Ok(())
+    } else {
+        Err(Error::InsufficientPermissions)
+    }
+}
+
+pub async fn get(
+    State(state): State<AppState>,
+    Path((game_slug, category_id, run_id)): Path<(String, Id<CategoryMarker>, Id<RunMarker>)>,
+    user: User,
+    base: BaseRenderInfo,
+) -> HandlerResult {
+    let (_game, member) = game_n_member(&state, user, &game_slug).await?;
+    let run = ResolvedRun::from_db(&state, run_id)
+        .await?
+        .ok_or(Error::NotFound)?;
+    if run.game.slug != game_slug || run.category.id != category_id {
+        return Err(Error::NotFound);
+    }
+    check_can_edit(&member, &run)?;
+    let variables = Variable::from_db_category(&state, run.category.id).await?;
+    let levels = if run.category.per_level {
+        Level::from_db_game(&state, run.game.id).await?
+    } else {
+        Vec::new()
+    };
+    let platforms = Platform::from_db_game(&state, run.game.id).await?;
+    let times = HashMap::from([
+        (TimingMethod::Rta, TimeParts::from_millis(run.time)),
+        (TimingMethod::Igt, TimeParts::from_millis(run.igt)),
+        (TimingMethod::Lrt, TimeParts::from_millis(run.lrt)),
+    ]);
+    let ctx = RunEditPage {
+        base,
+        run,
+        variables,
+        levels,
+        platforms,
+        times,
+    };
+    state.render("edit_run.jinja", ctx)
+}
+
+pub async fn post(
+    State(state): State<AppState>,
+    Path((game_slug, category_id, run_id)): Path<(String, Id<CategoryMarker>, Id<RunMarker>)>,
+    user: User,
+    ValidatedForm(form): ValidatedForm<RunCreateForm>,
+) -> Result<Redirect, Error> {
+    let (game, member) = game_n_member(&state, user, &game_slug).await?;
+    let run = ResolvedRun::from_db(&state, run_id)
+        .await?
+        .ok_or(Error::NotFound)?;
+    if run.game.id != game.id || run.category.id != category_id {
+        return Err(Error::NotFound);
+    }
+    check_can_edit(&member, &run)?;
+    let category = Category::from_db(&state, category_id).await?;
+    let submission = form
+        .check(&state, &game, &category, run.submitter.id)
+        .await?;
+    // verified runs have to be verified again if anything the verifier checked changed
+    let needs_review =
+        run.status == RunStatus::Verified && reviewed_fields_changed(&run, &form, &submission);
+    let mut trans = state.postgres.begin().await?;
+    RunRevision::save(trans.as_mut(), run_id, member.user.id).await?;
+    query!(
+        "UPDATE runs SET video = $2, description = $3,
+        score = $4, time = $5, igt = $6, lrt = $7, level = $8,
+        platform = $10, emulated = $11, edited_at = NOW(),
+        status = CASE WHEN $9 THEN 0 ELSE status END,
+        verifier = CASE WHEN $9 THEN NULL ELSE verifier END,
+        verified_at = CASE WHEN $9 THEN NULL ELSE verified_at END,
+        review_note = CASE WHEN $9 THEN NULL ELSE review_note END
+        WHERE id = $1",
+        run_id.get(),
+        form.video,
+        form.description,
+        submission.score,
+        submission.time,
+        submission.igt,
+        submission.lrt,
+        submission.level,
+        needs_review,
+        submission.platform,
+        form.emulated
+    )
+    .execute(trans.as_mut())
+    .await?;
+    query!("DELETE FROM run_variables WHERE run = $1", run_id.get())
+        .execute(trans.as_mut())
+        .await?;
+    query!(
+        "INSERT INTO run_variables (run, variable, value)
+        SELECT $1, * FROM UNNEST($2::BIGINT[], $3::BIGINT[])",
+        run_id.get(),
+        &submission.variable_ids,
+        &submission.value_ids
+    )
+    .execute(trans.as_mut())
+    .await?;
+    query!("DELETE FROM run_participants WHERE run = $1", run_id.get())
+        .execute(trans.as_mut())
+        .await?;
+    submission
+        .insert_participants(trans.as_mut(), run_id)
+        .await?;
+    // the edit can change which of the runners' runs is their best
+    Category::update_obsoleted(trans.as_mut(), category_id, Some(run.submitter.id)).await?;
+    trans.commit().await?;
+    GameRanking::invalidate(&state, game.id).await?;
+    Ok(state.redirect(format!(
+        "/game/{game_slug}/category/{category_id}/run/{run_id}"
+    )))
+}
+
+/// Whether an edit changes what the run's verifier looked at: its result, its video,
+/// the board it's on or who ran it. Descriptions can change freely.
+fn reviewed_fields_changed(
+    run: &ResolvedRun,
+    form: &RunCreateForm,
+    submission: &RunSubmission,
+) -> bool {
+    let timing_changed = (run.score, run.time, run.igt, run.lrt)
+        != (
+            submission.score,
+            submission.time,
+            submission.igt,
+            submission.lrt,
+        );
+    let mut old_values: Vec<i64> = run.variables.iter().map(|value| value.get()).collect();
+    let mut new_values = submission.value_ids.clone();
+    old_values.sort_unstable();
+    new_values.sort_unstable();
+    let old_runners = run
+        .participants
+        .iter()
+        .map(|participant| (participant.user.map(Id::get), participant.name.as_str()));
+    let new_runners = submission
+        .participant_users
+        .iter()
+        .zip(&submission.participant_guests)
+        .map(|(user, guest)| (*user, guest.as_deref().unwrap_or_default()));
+    // registered runners are compared by id, since the old ones are named by username
+    let runners_changed = old_runners.len() != new_runners.len()
+        || old_runners
+            .zip(new_runners)
+            .any(|(old, new)| match (old.0, new.0) {
+                (Some(old_user), Some(new_user)) => old_user != new_user,
+                (None, None) => old.1 != new.1,
+                _ => true,
+            });
+    timing_changed
+        || runners_changed
+        || old_values != new_values
+        || run.video != form.video
+        || run.level.as_ref().map(|level| level.id.get()) != submission.level
+        || run.platform.as_ref().map(|platform| platform.id.get()) != submission.platform
+        || run.emulated != form.emulated
+}
@@ -0,0 +1,159 @@
+use std::{
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use axum::extract::{Path, State};
+use axum_extra::extract::Query;
+use chrono::{DateTime, NaiveDateTime};
+
+use super::category::{platform_query, selection_query, variable_selectors, VariableSelector};
+use crate::{
+    id::{CategoryMarker, Id, LevelMarker, PlatformMarker, VariableValueMarker},
+    model::{
+        Category, DateSort, Game, LeaderboardFilter, Level, ResolvedRun, RunStatus, SortBy,
+        Variable,
+    },
+    template::BaseRenderInfo,
+    AppState, Error, HandlerResult,
+};
+
+/// Categories with more records than this only show the oldest ones
+const MAX_RECORDS: usize = 500;
+
+#[derive(serde::Deserialize, Debug, Clone)]
+pub struct RecordHistoryQuery {
+    #[serde(default)]
+    values: Vec<Id<VariableValueMarker>>,
+    level: Option<Id<LevelMarker>>,
+    platform: Option<Id<PlatformMarker>>,
+    emulated: Option<bool>,
+}
+
+#[derive(serde::Serialize, Debug, Clone)]
+pub struct RecordHistoryPage {
+    #[serde(flatten)]
+    base: BaseRenderInfo,
+    game: Arc<Game>,
+    category: Category,
+    variables: Vec<VariableSelector>,
+    /// query string which keeps the current variable selection
+    selection_query: String,
+    levels: Vec<Level>,
+    level: Option<Level>,
+    /// query string which keeps the platform and emulator filters of the leaderboard
+    platform_query: String,
+    /// newest record first
+    records: Vec<WorldRecord>,
+}
+
+/// A run which was the world record at some point
+#[derive(serde::Serialize, Debug, Clone)]
+pub struct WorldRecord {
+    /// How many whole days the record stood, up to now for the current record
+    days_held: i64,
+    /// How much this run beat the previous record by, in milliseconds or points.
+    /// The first record has nothing to improve upon.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    improvement: Option<i64>,
+    current: bool,
+    #[serde(flatten)]
+    run: ResolvedRun,
+}
+
+pub async fn get(
+    State(state): State<AppState>,
+    Path((game_slug, category_id)): Path<(String, Id<CategoryMarker>)>,
+    Query(query): Query<RecordHistoryQuery>,
+    base: BaseRenderInfo,
+) -> HandlerResult {
+    let game = Arc::new(Game::from_db_slug(&state, &game_slug).await?);
+    let category = Category::from_db(&state, category_id).await?;
+    if category.game != game.id {
+        return Err(Error::NotFound);
+    }
+    let variables = Variable::from_db_category(&state, category.id).await?;
+    let selection = Variable::resolve_selection(&variables, &query.values);
+    let levels = if category.per_level {
+        Level::from_db_game(&state, game.id).await?
+    } else {
+        Vec::new()
+    };
+    let level = query
+        .level
+        .and_then(|id| levels.iter().find(|level| level.id == id))
+        .or_else(|| levels.first())
+        .cloned();
+    let records_by = category.sort_by();
+    let runs = ResolvedRun::fetch_leaderboard(
+        &state,
+        game.clone(),
+        RunStatus::Verified,
+        LeaderboardFilter {
+            category: Some(category.id),
+            level: level.as_ref().map(|level| level.id),
+            variables: selection.clone(),
+            platform: query.platform,
+            emulated: query.emulated,
+            personal_bests_only: false,
+            records_by: Some(records_by),
+        },
+        SortBy::SubmissionDate(DateSort::Oldest),
+        MAX_RECORDS,
+        None,
+    )
+    .await?
+    .resolveds();
+    let mut records = progression(runs, records_by, now());
+    records.reverse();
+    let ctx = RecordHistoryPage {
+        base,
+        game,
+        category,
+        selection_query: selection_query(&selection),
+        variables: variable_selectors(variables, &selection),
+        levels,
+        level,
+        platform_query: platform_query(query.platform, query.emulated),
+        records,
+    };
+    state.render("record_history.jinja", ctx)
+}
+
+fn now() -> NaiveDateTime {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    DateTime::from_timestamp(i64::try_from(secs).unwrap_or(i64::MAX), 0)
+        .unwrap_or_default()
+        .naive_utc()
+}
+
+/// Work out how long each of `runs`, oldest first, held the record and how much it improved it
+fn progression(runs: Vec<ResolvedRun>, records_by: SortBy, now: NaiveDateTime) -> Vec<WorldRecord> {
+    let metric = |run: &ResolvedRun| records_by.run_value(run).unwrap_or_default();
+    let ends: Vec<NaiveDateTime> = runs
+        .iter()
+        .skip(1)
+        .map(|run| run.created_at)
+        .chain(std::iter::once(now))
+        .collect();
+    let mut previous: Option<i64> = None;
+    let count = runs.len();
+    runs.into_iter()
+        .zip(ends)
+        .enumerate()
+        .map(|(index, (run, end))| {
+            let value = metric(&run);
+            let improvement = previous.map(|previous| (previous - value).abs());
+            previous = Some(value);
+            WorldRecord {
+                days_held: (end - run.created_at).num_days(),
+                improvement,
+                current: index + 1 == count,
+                run,
+            }
+        })
+        .collect()
+}
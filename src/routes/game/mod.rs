@@ -1,5 +1,9 @@
 pub mod category;
+pub mod edit_run;
+pub mod history;
 pub mod modtools;
 pub mod new_run;
+pub mod ranking;
 pub mod run;
 pub mod settings;
+pub mod splits;
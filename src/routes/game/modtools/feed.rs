@@ -3,8 +3,11 @@ use std::sync::Arc;
 use axum::extract::{Path, Query, State};
 
 use crate::{
-    id::{CategoryMarker, Id},
-    model::{Category, DateSort, Game, Permissions, ResolvedRun, RunStatus, User},
+    id::{CategoryMarker, Id, RunMarker},
+    model::{
+        CannedReason, Category, ClaimedRun, DateSort, Game, LeaderboardFilter, PageCursor,
+        Permissions, ResolvedRun, RunClaim, RunStatus, User,
+    },
     template::BaseRenderInfo,
     util::game_n_member,
     AppState, Error, HandlerResult,
@@ -14,17 +17,22 @@ const MOD_FEED_PER_PAGE: usize = 100;
 
 #[derive(serde::Deserialize, Debug, Clone)]
 pub struct ModFeedQuery {
-    #[serde(default = "crate::util::return_0_usize")]
-    pub page: usize,
+    pub after: Option<Id<RunMarker>>,
+    pub before: Option<Id<RunMarker>>,
     #[serde(default = "crate::util::return_false")]
     pub newest: bool,
 }
 
 #[derive(serde::Serialize, Debug, Clone)]
 pub struct ModFeedPage {
-    has_next: bool,
-    submissions: Vec<ResolvedRun>,
+    /// the run the next page starts after
+    next_cursor: Option<Id<RunMarker>>,
+    /// the run the previous page ends before
+    previous_cursor: Option<Id<RunMarker>>,
+    newest: bool,
+    submissions: Vec<ClaimedRun>,
     category: Option<Category>,
+    canned_reasons: Vec<CannedReason>,
     game: Arc<Game>,
     #[serde(flatten)]
     base: BaseRenderInfo,
@@ -81,10 +89,13 @@ async fn feed_maybe_cat(
         state,
         game.clone(),
         RunStatus::Pending,
-        maybe_category_id,
+        LeaderboardFilter {
+            category: maybe_category_id,
+            ..Default::default()
+        },
         crate::model::SortBy::SubmissionDate(sort_direction),
         MOD_FEED_PER_PAGE,
-        query.page,
+        PageCursor::from_query(query.after, query.before),
     )
     .await?;
     let category = if let Some(task) = maybe_task {
@@ -92,11 +103,18 @@ async fn feed_maybe_cat(
     } else {
         None
     };
+    let next_cursor = leaderboard.next_cursor();
+    let previous_cursor = leaderboard.previous_cursor();
+    let submissions = RunClaim::claimed_runs(state, leaderboard.resolveds()).await?;
+    let canned_reasons = CannedReason::from_db_game(state, game.id).await?;
     let ctx = ModFeedPage {
         base,
-        has_next: leaderboard.has_next(),
+        next_cursor,
+        previous_cursor,
+        newest: query.newest,
         category,
-        submissions: leaderboard.resolveds(),
+        canned_reasons,
+        submissions,
         game,
     };
     state.render("moderation_feed.jinja", ctx)
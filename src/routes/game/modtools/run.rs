@@ -4,14 +4,95 @@ use axum::{
 };
 
 use crate::{
-    id::{CategoryMarker, Id, RunMarker},
-    model::{Member, Permissions, ResolvedRun, User},
+    id::{CannedReasonMarker, CategoryMarker, GameMarker, Id, RunMarker},
+    model::{
+        CannedReason, Category, GameRanking, Member, Permissions, ResolvedRun, RunClaim,
+        RunComment, RunRevision, RunStatus, Splits, User, Variable,
+    },
     routes::game::run::RunPage,
     template::BaseRenderInfo,
-    util::game_n_member,
+    util::{game_n_member, ValidatedForm},
     AppState, Error, HandlerResult,
 };
 
+#[derive(serde::Serialize, Debug, Clone)]
+pub struct RunReviewPage<'a> {
+    #[serde(flatten)]
+    page: RunPage<'a>,
+    canned_reasons: Vec<CannedReason>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    claim: Option<RunClaim>,
+    claim_stale: bool,
+}
+
+#[derive(serde::Deserialize, garde::Validate, Clone, Debug)]
+pub struct RunReviewForm {
+    #[serde(default)]
+    #[garde(length(min = crate::util::MIN_REVIEW_NOTE_LEN, max = crate::util::MAX_REVIEW_NOTE_LEN))]
+    note: String,
+    #[serde(default)]
+    #[garde(skip)]
+    reason: Option<Id<CannedReasonMarker>>,
+}
+
+#[derive(serde::Deserialize, Clone, Copy, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum ReviewDecision {
+    Verify,
+    Reject,
+}
+
+impl ReviewDecision {
+    fn status(self) -> RunStatus {
+        match self {
+            Self::Verify => RunStatus::Verified,
+            Self::Reject => RunStatus::Rejected,
+        }
+    }
+}
+
+#[derive(serde::Deserialize, garde::Validate, Clone, Debug)]
+pub struct BulkReviewForm {
+    #[serde(default)]
+    #[garde(length(min = 1, max = crate::util::MAX_BULK_REVIEW_RUNS))]
+    runs: Vec<Id<RunMarker>>,
+    #[garde(skip)]
+    decision: ReviewDecision,
+    #[serde(default)]
+    #[garde(length(min = crate::util::MIN_REVIEW_NOTE_LEN, max = crate::util::MAX_REVIEW_NOTE_LEN))]
+    note: String,
+    #[serde(default)]
+    #[garde(skip)]
+    reason: Option<Id<CannedReasonMarker>>,
+}
+
+/// The note saved with a run: the canned reason, followed by
+/// whatever the verifier wrote themselves
+async fn review_note(
+    state: &AppState,
+    game: Id<GameMarker>,
+    reason: Option<Id<CannedReasonMarker>>,
+    note: &str,
+) -> Result<Option<String>, Error> {
+    let mut parts = Vec::with_capacity(2);
+    if let Some(reason_id) = reason {
+        let reason = CannedReason::from_db(state, reason_id).await?;
+        if reason.game != game {
+            return Err(Error::InvalidGameCannedReasonPair);
+        }
+        parts.push(reason.reason);
+    }
+    let note = note.trim();
+    if !note.is_empty() {
+        parts.push(note.to_string());
+    }
+    if parts.is_empty() {
+        Ok(None)
+    } else {
+        Ok(Some(parts.join("\n\n")))
+    }
+}
+
 pub async fn fetch_review(
     State(state): State<AppState>,
     Path((game_slug, category_id, run_id)): Path<(String, Id<CategoryMarker>, Id<RunMarker>)>,
@@ -24,20 +105,37 @@ pub async fn fetch_review(
     if run.game.slug != game_slug || run.category.id != category_id {
         return Err(Error::NotFound);
     }
-    let member = Member::from_db(&state, user.id, run.game.id)
+    let member = Member::from_db(&state, user.id, &run.game)
         .await?
         .ok_or(Error::InsufficientPermissions)?;
     drop(user);
     if !member.perms.contains(Permissions::VERIFY_RUNS) {
         return Err(Error::InsufficientPermissions);
     }
-    let ctx = RunPage {
-        user: &run.submitter,
-        game: &run.game,
-        category: &run.category,
-        verifier: &run.verifier,
-        run: &run,
-        base,
+    let variables = Variable::from_db_category(&state, run.category.id).await?;
+    let revisions = RunRevision::from_db_run(&state, run.id).await?;
+    let splits = Splits::from_db_run(&state, run.id).await?;
+    let comments = RunComment::from_db_run(&state, run.id, true).await?;
+    let canned_reasons = CannedReason::from_db_game(&state, run.game.id).await?;
+    let claim = RunClaim::from_redis(&state, run.id).await?;
+    let claim_stale = claim.as_ref().is_some_and(RunClaim::is_stale);
+    let ctx = RunReviewPage {
+        page: RunPage {
+            user: &run.submitter,
+            game: &run.game,
+            category: &run.category,
+            verifier: &run.verifier,
+            run: &run,
+            variables: &variables,
+            revisions: &revisions,
+            splits: &splits,
+            comments: &comments,
+            private_comments: true,
+            base,
+        },
+        canned_reasons,
+        claim,
+        claim_stale,
     };
     state.render("review_run.jinja", ctx)
 }
@@ -46,16 +144,63 @@ pub async fn verify_run(
     State(state): State<AppState>,
     Path((game_slug, category_id, run_id)): Path<(String, Id<CategoryMarker>, Id<RunMarker>)>,
     user: User,
+    ValidatedForm(form): ValidatedForm<RunReviewForm>,
 ) -> Result<Redirect, Error> {
-    set_verify(&state, game_slug, category_id, run_id, user, 1).await
+    set_verify(&state, game_slug, category_id, run_id, user, 1, form).await
 }
 
 pub async fn reject_run(
     State(state): State<AppState>,
     Path((game_slug, category_id, run_id)): Path<(String, Id<CategoryMarker>, Id<RunMarker>)>,
     user: User,
+    ValidatedForm(form): ValidatedForm<RunReviewForm>,
+) -> Result<Redirect, Error> {
+    set_verify(&state, game_slug, category_id, run_id, user, -1, form).await
+}
+
+pub async fn claim_run(
+    State(state): State<AppState>,
+    Path((game_slug, category_id, run_id)): Path<(String, Id<CategoryMarker>, Id<RunMarker>)>,
+    user: User,
+) -> Result<Redirect, Error> {
+    let member = pending_run_reviewer(&state, user, &game_slug, category_id, run_id).await?;
+    RunClaim::claim(&state, run_id, &member.user).await?;
+    Ok(state.redirect(format!(
+        "/game/{game_slug}/category/{category_id}/run/{run_id}/review"
+    )))
+}
+
+pub async fn release_run(
+    State(state): State<AppState>,
+    Path((game_slug, category_id, run_id)): Path<(String, Id<CategoryMarker>, Id<RunMarker>)>,
+    user: User,
 ) -> Result<Redirect, Error> {
-    set_verify(&state, game_slug, category_id, run_id, user, -1).await
+    let member = pending_run_reviewer(&state, user, &game_slug, category_id, run_id).await?;
+    RunClaim::release(&state, run_id, member.user.id).await?;
+    Ok(state.redirect(format!("/game/{game_slug}/feed")))
+}
+
+/// Checks that `user` can verify runs of the game in the path, and that `run_id` is
+/// a pending run of that game and category, so claims can't be put on other games' runs
+async fn pending_run_reviewer(
+    state: &AppState,
+    user: User,
+    game_slug: &str,
+    category_id: Id<CategoryMarker>,
+    run_id: Id<RunMarker>,
+) -> Result<Member, Error> {
+    let (_game, member) = game_n_member(state, user, game_slug).await?;
+    member.perms.check(Permissions::VERIFY_RUNS)?;
+    let run = ResolvedRun::from_db(state, run_id)
+        .await?
+        .ok_or(Error::NotFound)?;
+    if run.game.slug != game_slug || run.category.id != category_id {
+        return Err(Error::NotFound);
+    }
+    if run.status != RunStatus::Pending {
+        return Err(Error::RunAlreadyReviewed);
+    }
+    Ok(member)
 }
 
 async fn set_verify(
@@ -65,17 +210,24 @@ async fn set_verify(
     run_id: Id<RunMarker>,
     user: User,
     value: i16,
+    form: RunReviewForm,
 ) -> Result<Redirect, Error> {
     let (game, member) = game_n_member(state, user, &game_slug).await?;
     if !member.perms.contains(Permissions::VERIFY_RUNS) {
         return Err(Error::InsufficientPermissions);
     }
+    // someone else might be halfway through reviewing this run
+    RunClaim::check_unclaimed(state, run_id, member.user.id).await?;
+    let review_note = review_note(state, game.id, form.reason, &form.note).await?;
     let mut trans = state.postgres.begin().await?;
     let run = query!(
-        "UPDATE runs SET status = $1, verifier = $2 WHERE id = $3 RETURNING game, category",
+        "UPDATE runs SET status = $1, verifier = $2, verified_at = NOW(),
+        review_note = $4
+        WHERE id = $3 RETURNING game, category, submitter",
         value,
         member.user.id.get(),
-        run_id.get()
+        run_id.get(),
+        review_note
     )
     .fetch_one(trans.as_mut())
     .await?;
@@ -83,8 +235,89 @@ async fn set_verify(
         trans.rollback().await?;
         return Err(Error::NotFound);
     }
+    Category::update_obsoleted(trans.as_mut(), category_id, Some(Id::new(run.submitter))).await?;
     trans.commit().await?;
+    GameRanking::invalidate(state, game.id).await?;
+    RunClaim::clear(state, run_id).await?;
     Ok(state.redirect(format!(
         "/game/{game_slug}/category/{category_id}/run/{run_id}"
     )))
 }
+
+pub async fn bulk_review_game(
+    State(state): State<AppState>,
+    Path(game_slug): Path<String>,
+    user: User,
+    ValidatedForm(form): ValidatedForm<BulkReviewForm>,
+) -> Result<Redirect, Error> {
+    bulk_review(&state, game_slug, None, user, form).await
+}
+
+pub async fn bulk_review_category(
+    State(state): State<AppState>,
+    Path((game_slug, category_id)): Path<(String, Id<CategoryMarker>)>,
+    user: User,
+    ValidatedForm(form): ValidatedForm<BulkReviewForm>,
+) -> Result<Redirect, Error> {
+    bulk_review(&state, game_slug, Some(category_id), user, form).await
+}
+
+/// Verify or reject every run in `form` at once. If any of them isn't part of
+/// the game (or category, when given), or has already been reviewed, none of them are changed.
+async fn bulk_review(
+    state: &AppState,
+    game_slug: String,
+    maybe_category_id: Option<Id<CategoryMarker>>,
+    user: User,
+    form: BulkReviewForm,
+) -> Result<Redirect, Error> {
+    let (game, member) = game_n_member(state, user, &game_slug).await?;
+    member.perms.check(Permissions::VERIFY_RUNS)?;
+    let mut run_ids: Vec<i64> = form.runs.iter().map(|id| id.get()).collect();
+    run_ids.sort_unstable();
+    run_ids.dedup();
+    for run_id in &run_ids {
+        RunClaim::check_unclaimed(state, Id::new(*run_id), member.user.id).await?;
+    }
+    let review_note = review_note(state, game.id, form.reason, &form.note).await?;
+    let mut trans = state.postgres.begin().await?;
+    let updated = query!(
+        "UPDATE runs SET status = $1, verifier = $2, verified_at = NOW(),
+        review_note = $3
+        WHERE id = ANY($4) AND game = $5 AND ($6::BIGINT IS NULL OR category = $6)
+        AND status = 0
+        RETURNING category, submitter",
+        i16::from(form.decision.status()),
+        member.user.id.get(),
+        review_note,
+        &run_ids,
+        game.id.get(),
+        maybe_category_id.map(Id::get)
+    )
+    .fetch_all(trans.as_mut())
+    .await?;
+    if updated.len() != run_ids.len() {
+        trans.rollback().await?;
+        return Err(Error::NotFound);
+    }
+    let mut runners: Vec<(i64, i64)> = updated
+        .into_iter()
+        .map(|row| (row.category, row.submitter))
+        .collect();
+    runners.sort_unstable();
+    runners.dedup();
+    for (category, submitter) in runners {
+        Category::update_obsoleted(trans.as_mut(), Id::new(category), Some(Id::new(submitter)))
+            .await?;
+    }
+    trans.commit().await?;
+    GameRanking::invalidate(state, game.id).await?;
+    for run_id in run_ids {
+        RunClaim::clear(state, Id::new(run_id)).await?;
+    }
+    let feed_url = match maybe_category_id {
+        Some(category_id) => format!("/game/{game_slug}/category/{category_id}/feed"),
+        None => format!("/game/{game_slug}/feed"),
+    };
+    Ok(state.redirect(feed_url))
+}
@@ -37,7 +37,7 @@ pub async fn get(
     base: BaseRenderInfo,
 ) -> HandlerResult {
     let game = Game::from_db_slug(&state, &game_slug).await?;
-    let member = Member::from_db(&state, user.id, game.id)
+    let member = Member::from_db(&state, user.id, &game)
         .await?
         .ok_or(Error::InsufficientPermissions)?;
     if !member.perms.contains(Permissions::ADMINISTRATOR) {
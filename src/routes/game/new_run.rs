@@ -1,13 +1,21 @@
+use std::collections::{HashMap, HashSet};
+
 use axum::{
     extract::{Path, State},
     response::Redirect,
 };
+use sqlx::PgExecutor;
 
 use crate::{
-    id::{CategoryMarker, Id},
-    model::{Category, Game, User},
+    id::{
+        CategoryMarker, Id, LevelMarker, PlatformMarker, RunMarker, UserMarker, VariableValueMarker,
+    },
+    model::{
+        parse_score, Category, EmailVerification, Game, Level, Platform, Splits, TimingMethod,
+        User, Variable,
+    },
     template::BaseRenderInfo,
-    util::ValidatedForm,
+    util::ValidatedMultipartForm,
     AppState, Error, HandlerResult,
 };
 
@@ -18,19 +26,23 @@ pub struct RunCreatePage {
     user: User,
     game: Game,
     category: Category,
+    variables: Vec<Variable>,
+    levels: Vec<Level>,
+    platforms: Vec<Platform>,
 }
 
 #[derive(serde::Deserialize, garde::Validate, Clone, Debug)]
 pub struct RunCreateForm {
     #[garde(length(min = crate::util::MIN_RUN_VIDEO_LEN, max = crate::util::MAX_RUN_VIDEO_LEN))]
-    video: String,
+    pub video: String,
     #[garde(length(min = crate::util::MIN_RUN_DESCRIPTION_LEN, max = crate::util::MAX_RUN_DESCRIPTION_LEN))]
-    description: String,
-    #[serde(default = "crate::util::return_0_i64")]
-    #[garde(range(min = 0))]
-    score: i64,
+    pub description: String,
+    /// Scores are typed with the category's decimal places, like `12.5`
+    #[serde(default)]
+    #[garde(length(max = crate::util::MAX_SCORE_INPUT_LEN))]
+    score: String,
     #[serde(default = "crate::util::return_0_i64")]
-    #[garde(range(min = 0))]
+    #[garde(range(min = 0, max = crate::util::MAX_RUN_HOURS))]
     hours: i64,
     #[serde(default = "crate::util::return_0_i64")]
     #[garde(range(min = 0, max = 60))]
@@ -41,6 +53,47 @@ pub struct RunCreateForm {
     #[serde(default = "crate::util::return_0_i64")]
     #[garde(range(min = 0, max = 1000))]
     milliseconds: i64,
+    #[serde(default = "crate::util::return_0_i64")]
+    #[garde(range(min = 0, max = crate::util::MAX_RUN_HOURS))]
+    igt_hours: i64,
+    #[serde(default = "crate::util::return_0_i64")]
+    #[garde(range(min = 0, max = 60))]
+    igt_minutes: i64,
+    #[serde(default = "crate::util::return_0_i64")]
+    #[garde(range(min = 0, max = 60))]
+    igt_seconds: i64,
+    #[serde(default = "crate::util::return_0_i64")]
+    #[garde(range(min = 0, max = 1000))]
+    igt_milliseconds: i64,
+    #[serde(default = "crate::util::return_0_i64")]
+    #[garde(range(min = 0, max = crate::util::MAX_RUN_HOURS))]
+    lrt_hours: i64,
+    #[serde(default = "crate::util::return_0_i64")]
+    #[garde(range(min = 0, max = 60))]
+    lrt_minutes: i64,
+    #[serde(default = "crate::util::return_0_i64")]
+    #[garde(range(min = 0, max = 60))]
+    lrt_seconds: i64,
+    #[serde(default = "crate::util::return_0_i64")]
+    #[garde(range(min = 0, max = 1000))]
+    lrt_milliseconds: i64,
+    #[serde(default)]
+    #[garde(skip)]
+    values: Vec<Id<VariableValueMarker>>,
+    #[garde(skip)]
+    level: Option<Id<LevelMarker>>,
+    #[garde(skip)]
+    platform: Option<Id<PlatformMarker>>,
+    #[serde(default = "crate::util::return_false")]
+    #[garde(skip)]
+    pub emulated: bool,
+    /// The other runners of a co-op run, as usernames or guest names
+    #[serde(default)]
+    #[garde(
+        length(max = crate::util::MAX_CATEGORY_PLAYERS),
+        inner(length(max = crate::util::MAX_GUEST_NAME_LEN))
+    )]
+    runners: Vec<String>,
 }
 
 impl RunCreateForm {
@@ -48,11 +101,216 @@ impl RunCreateForm {
     const MS_PER_MINUTE: i64 = 60_000;
     const MS_PER_SECOND: i64 = 1000;
 
-    pub fn consolidate_times(&self) -> i64 {
-        (self.hours * Self::MS_PER_HOUR)
-            + (self.minutes * Self::MS_PER_MINUTE)
-            + (self.seconds * Self::MS_PER_SECOND)
-            + self.milliseconds
+    pub fn consolidate_times(&self, method: TimingMethod) -> Result<i64, Error> {
+        let (hours, minutes, seconds, milliseconds) = match method {
+            TimingMethod::Rta => (self.hours, self.minutes, self.seconds, self.milliseconds),
+            TimingMethod::Igt => (
+                self.igt_hours,
+                self.igt_minutes,
+                self.igt_seconds,
+                self.igt_milliseconds,
+            ),
+            TimingMethod::Lrt => (
+                self.lrt_hours,
+                self.lrt_minutes,
+                self.lrt_seconds,
+                self.lrt_milliseconds,
+            ),
+        };
+        hours
+            .checked_mul(Self::MS_PER_HOUR)
+            .and_then(|ms| ms.checked_add(minutes.checked_mul(Self::MS_PER_MINUTE)?))
+            .and_then(|ms| ms.checked_add(seconds.checked_mul(Self::MS_PER_SECOND)?))
+            .and_then(|ms| ms.checked_add(milliseconds))
+            .ok_or_else(|| {
+                Error::CustomFormValidation(format!("{} time is too long", method.abbreviation()))
+            })
+    }
+
+    /// Get the time for `method`, or 0 (not recorded) if `category` doesn't accept it
+    pub fn time_for(&self, category: &Category, method: TimingMethod) -> Result<i64, Error> {
+        if category.scoreboard || !category.timing_methods.contains(method) {
+            Ok(0)
+        } else {
+            self.consolidate_times(method)
+        }
+    }
+
+    /// Check this form against the category it is being submitted to
+    pub async fn check(
+        &self,
+        state: &AppState,
+        game: &Game,
+        category: &Category,
+        submitter: Id<UserMarker>,
+    ) -> Result<RunSubmission, Error> {
+        let score = if category.scoreboard {
+            if self.score.trim().is_empty() {
+                return Err(Error::CustomFormValidation(
+                    "score must be filled when the leaderboard is a scoreboard".to_string(),
+                ));
+            }
+            parse_score(&self.score, category.score_precision)?
+        } else {
+            0
+        };
+        if !category.scoreboard && self.time_for(category, category.primary_timing)? == 0 {
+            return Err(Error::CustomFormValidation(format!(
+                "{} time must be filled when the leaderboard is a speedrun",
+                category.primary_timing.abbreviation()
+            )));
+        }
+        let level = if category.per_level {
+            let level = Level::from_db(state, self.level.ok_or(Error::MissingLevel)?).await?;
+            if level.game != game.id {
+                return Err(Error::InvalidGameLevelPair);
+            }
+            Some(level.id.get())
+        } else {
+            None
+        };
+        let platforms = Platform::from_db_game(state, game.id).await?;
+        let platform = match self.platform {
+            Some(id) => Some(
+                platforms
+                    .iter()
+                    .find(|platform| platform.id == id)
+                    .ok_or(Error::InvalidGamePlatformPair)?
+                    .id
+                    .get(),
+            ),
+            None if platforms.is_empty() => None,
+            None => return Err(Error::MissingPlatform),
+        };
+        let variables = Variable::from_db_category(state, category.id).await?;
+        let (variable_ids, value_ids) = Variable::validate_submission(&variables, &self.values)?
+            .into_iter()
+            .map(|value| (value.variable.get(), value.id.get()))
+            .unzip();
+        let (participant_users, participant_guests) =
+            self.check_runners(state, category, submitter).await?;
+        Ok(RunSubmission {
+            score,
+            time: self.time_for(category, TimingMethod::Rta)?,
+            igt: self.time_for(category, TimingMethod::Igt)?,
+            lrt: self.time_for(category, TimingMethod::Lrt)?,
+            level,
+            platform,
+            variable_ids,
+            value_ids,
+            participant_users,
+            participant_guests,
+        })
+    }
+
+    /// Match up the other runners with registered users, treating unknown names as guests
+    async fn check_runners(
+        &self,
+        state: &AppState,
+        category: &Category,
+        submitter: Id<UserMarker>,
+    ) -> Result<(Vec<Option<i64>>, Vec<Option<String>>), Error> {
+        let runners: Vec<&str> = self
+            .runners
+            .iter()
+            .map(|runner| runner.trim())
+            .filter(|runner| !runner.is_empty())
+            .collect();
+        let expected = usize::try_from(category.players - 1)?;
+        if runners.len() != expected {
+            return Err(Error::CustomFormValidation(format!(
+                "this category needs {expected} other runners, but {} were given",
+                runners.len()
+            )));
+        }
+        let mut seen = HashSet::with_capacity(runners.len());
+        if !runners
+            .iter()
+            .all(|runner| seen.insert(runner.to_lowercase()))
+        {
+            return Err(Error::CustomFormValidation(
+                "each runner can only be listed once".to_string(),
+            ));
+        }
+        let users: HashMap<String, i64> = query!(
+            "SELECT id, username FROM users WHERE username = ANY($1)",
+            &runners as &[&str]
+        )
+        .fetch_all(&state.postgres)
+        .await?
+        .into_iter()
+        .map(|row| (row.username, row.id))
+        .collect();
+        let mut participant_users = Vec::with_capacity(runners.len());
+        let mut participant_guests = Vec::with_capacity(runners.len());
+        for runner in runners {
+            let user = users.get(runner).copied();
+            if user == Some(submitter.get()) {
+                return Err(Error::CustomFormValidation(
+                    "you don't need to list yourself as another runner".to_string(),
+                ));
+            }
+            participant_users.push(user);
+            participant_guests.push(user.is_none().then(|| runner.to_string()));
+        }
+        Ok((participant_users, participant_guests))
+    }
+}
+
+/// The database values of a checked [`RunCreateForm`]
+pub struct RunSubmission {
+    pub score: i64,
+    pub time: i64,
+    pub igt: i64,
+    pub lrt: i64,
+    pub level: Option<i64>,
+    pub platform: Option<i64>,
+    pub variable_ids: Vec<i64>,
+    pub value_ids: Vec<i64>,
+    /// Registered co-op runners, lined up with `participant_guests`
+    pub participant_users: Vec<Option<i64>>,
+    pub participant_guests: Vec<Option<String>>,
+}
+
+impl RunSubmission {
+    /// Record the other runners of `run`, in the order they were listed
+    pub async fn insert_participants(
+        &self,
+        executor: impl PgExecutor<'_>,
+        run: Id<RunMarker>,
+    ) -> Result<(), Error> {
+        query!(
+            "INSERT INTO run_participants (run, position, user_id, guest_name)
+            SELECT $1, position::SMALLINT, user_id, guest_name
+            FROM UNNEST($2::BIGINT[], $3::TEXT[])
+            WITH ORDINALITY AS runners(user_id, guest_name, position)",
+            run.get(),
+            &self.participant_users as &[Option<i64>],
+            &self.participant_guests as &[Option<String>]
+        )
+        .execute(executor)
+        .await?;
+        Ok(())
+    }
+}
+
+/// A time split up like the form fields of [`RunCreateForm`]
+#[derive(serde::Serialize, Debug, Clone, Copy)]
+pub struct TimeParts {
+    pub hours: i64,
+    pub minutes: i64,
+    pub seconds: i64,
+    pub milliseconds: i64,
+}
+
+impl TimeParts {
+    pub fn from_millis(millis: i64) -> Self {
+        Self {
+            hours: millis / RunCreateForm::MS_PER_HOUR,
+            minutes: millis % RunCreateForm::MS_PER_HOUR / RunCreateForm::MS_PER_MINUTE,
+            seconds: millis % RunCreateForm::MS_PER_MINUTE / RunCreateForm::MS_PER_SECOND,
+            milliseconds: millis % RunCreateForm::MS_PER_SECOND,
+        }
     }
 }
 
@@ -63,16 +321,29 @@ pub async fn get(
     base: BaseRenderInfo,
     Path((game_slug, category_id)): Path<(String, Id<CategoryMarker>)>,
 ) -> HandlerResult {
+    if !EmailVerification::is_verified(&state, user.id).await? {
+        return Err(Error::EmailNotVerified);
+    }
     let game = Game::from_db_slug(&state, &game_slug).await?;
     let category = Category::from_db(&state, category_id).await?;
     if category.game != game.id {
         return Err(Error::InvalidGameCategoryPair);
     }
+    let variables = Variable::from_db_category(&state, category.id).await?;
+    let levels = if category.per_level {
+        Level::from_db_game(&state, game.id).await?
+    } else {
+        Vec::new()
+    };
+    let platforms = Platform::from_db_game(&state, game.id).await?;
     let context = RunCreatePage {
         base,
         user,
         game,
         category,
+        variables,
+        levels,
+        platforms,
     };
     state.render("create_run.jinja", context)
 }
@@ -82,45 +353,67 @@ pub async fn create(
     State(state): State<AppState>,
     user: User,
     Path((game_slug, category_id)): Path<(String, Id<CategoryMarker>)>,
-    ValidatedForm(form): ValidatedForm<RunCreateForm>,
+    ValidatedMultipartForm { form, files }: ValidatedMultipartForm<RunCreateForm>,
 ) -> Result<Redirect, Error> {
+    if !EmailVerification::is_verified(&state, user.id).await? {
+        return Err(Error::EmailNotVerified);
+    }
     let game = Game::from_db_slug(&state, &game_slug).await?;
     let category = Category::from_db(&state, category_id).await?;
     if category.game != game.id {
         return Err(Error::InvalidGameCategoryPair);
     }
-    if category.scoreboard {
-        if form.score == 0 {
-            return Err(Error::CustomFormValidation(
-                "score must be filled when the leaderboard is a scoreboard".to_string(),
-            ));
-        }
-    } else if form.consolidate_times() == 0 {
-        return Err(Error::CustomFormValidation(
-            "time must be filled when the leaderboard is a speedrun".to_string(),
-        ));
-    }
-    let (score, time) = (form.score, form.consolidate_times());
+    let submission = form.check(&state, &game, &category, user.id).await?;
+    // splits are read before anything is saved, so a bad file doesn't leave a run behind
+    let splits = match files.get("splits") {
+        Some(lss) => Some((Splits::from_upload(lss, category.primary_timing)?, lss)),
+        None => None,
+    };
+    let mut trans = state.postgres.begin().await?;
     let run_id = query!(
         "INSERT INTO runs
         (
             game, category, submitter, video,
-            description, score, time, status,
-            created_at
+            description, score, time, igt,
+            lrt, status, created_at, level,
+            platform, emulated
         )
-        VALUES ($1, $2, $3, $4, $5, $6, $7, 0, NOW())
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, 0, NOW(), $10, $11, $12)
         RETURNING id",
         game.id.get(),
         category.id.get(),
         user.id.get(),
         form.video,
         form.description,
-        score,
-        time
+        submission.score,
+        submission.time,
+        submission.igt,
+        submission.lrt,
+        submission.level,
+        submission.platform,
+        form.emulated
     )
-    .fetch_one(&state.postgres)
+    .fetch_one(trans.as_mut())
     .await?
     .id;
+    query!(
+        "INSERT INTO run_variables (run, variable, value)
+        SELECT $1, * FROM UNNEST($2::BIGINT[], $3::BIGINT[])",
+        run_id,
+        &submission.variable_ids,
+        &submission.value_ids
+    )
+    .execute(trans.as_mut())
+    .await?;
+    submission
+        .insert_participants(trans.as_mut(), Id::new(run_id))
+        .await?;
+    match splits {
+        Some((splits, lss)) => {
+            super::splits::save(&state, trans, Id::new(run_id), &splits, lss).await?;
+        }
+        None => trans.commit().await?,
+    }
     Ok(state.redirect(format!(
         "/game/{game_slug}/category/{category_id}/run/{run_id}"
     )))
@@ -0,0 +1,32 @@
+use std::sync::Arc;
+
+use axum::extract::{Path, State};
+
+use crate::{
+    model::{Game, GameRanking},
+    template::BaseRenderInfo,
+    AppState, HandlerResult,
+};
+
+#[derive(serde::Serialize, Debug, Clone)]
+pub struct RankingPage {
+    #[serde(flatten)]
+    base: BaseRenderInfo,
+    game: Arc<Game>,
+    ranking: GameRanking,
+}
+
+pub async fn get(
+    State(state): State<AppState>,
+    Path(game_slug): Path<String>,
+    base: BaseRenderInfo,
+) -> HandlerResult {
+    let game = Arc::new(Game::from_db_slug(&state, &game_slug).await?);
+    let ranking = GameRanking::get(&state, game.clone()).await?;
+    let ctx = RankingPage {
+        base,
+        game,
+        ranking,
+    };
+    state.render("ranking.jinja", ctx)
+}
@@ -4,13 +4,23 @@ use axum::{
 };
 
 use crate::{
-    id::{CategoryMarker, Id, RunMarker},
-    model::{Category, Game, Permissions, ResolvedRun, User},
+    id::{CategoryMarker, Id, RunCommentMarker, RunMarker},
+    model::{
+        Category, Game, GameRanking, Member, Permissions, ResolvedRun, RunComment, RunRevision,
+        Splits, User, Variable,
+    },
     template::BaseRenderInfo,
-    util::game_n_member,
+    util::{game_n_member, ValidatedForm},
     AppState, Error, HandlerResult,
 };
 
+type RunCommentPath = (
+    String,
+    Id<CategoryMarker>,
+    Id<RunMarker>,
+    Id<RunCommentMarker>,
+);
+
 #[derive(serde::Serialize, Debug, Clone)]
 pub struct RunPage<'a> {
     pub user: &'a User,
@@ -18,10 +28,30 @@ pub struct RunPage<'a> {
     pub category: &'a Category,
     pub verifier: &'a Option<User>,
     pub run: &'a ResolvedRun,
+    pub variables: &'a [Variable],
+    pub revisions: &'a [RunRevision],
+    pub splits: &'a Option<Splits>,
+    pub comments: &'a [RunComment],
+    /// Whether the viewer can leave comments only the submitter and game team can see
+    pub private_comments: bool,
     #[serde(flatten)]
     pub base: BaseRenderInfo,
 }
 
+#[derive(serde::Deserialize, garde::Validate, Clone, Debug)]
+pub struct RunCommentForm {
+    #[garde(length(min = crate::util::MIN_RUN_COMMENT_LEN, max = crate::util::MAX_RUN_COMMENT_LEN))]
+    content: String,
+    #[serde(default = "crate::util::return_false")]
+    #[garde(skip)]
+    private: bool,
+}
+
+/// Private comments are for the submitter and anyone on the game's team
+fn sees_private_comments(member: &Member, run: &ResolvedRun) -> bool {
+    !member.perms.is_empty() || member.user.id == run.submitter.id
+}
+
 pub async fn get(
     State(state): State<AppState>,
     Path((game_slug, category_id, run_id)): Path<(String, Id<CategoryMarker>, Id<RunMarker>)>,
@@ -33,12 +63,31 @@ pub async fn get(
     if run.game.slug != game_slug || run.category.id != category_id {
         return Err(Error::NotFound);
     }
+    let variables = Variable::from_db_category(&state, run.category.id).await?;
+    let revisions = RunRevision::from_db_run(&state, run.id).await?;
+    let splits = Splits::from_db_run(&state, run.id).await?;
+    let viewer = match &base.logged_in_user {
+        Some(user) => Member::from_db(&state, user.id, &run.game).await?,
+        None => None,
+    };
+    let include_private = viewer
+        .as_ref()
+        .is_some_and(|member| sees_private_comments(member, &run));
+    let comments = RunComment::from_db_run(&state, run.id, include_private).await?;
+    let private_comments = viewer
+        .as_ref()
+        .is_some_and(|member| member.perms.contains(Permissions::VERIFY_RUNS));
     let ctx = RunPage {
         user: &run.submitter,
         game: &run.game,
         category: &run.category,
         verifier: &run.verifier,
         run: &run,
+        variables: &variables,
+        revisions: &revisions,
+        splits: &splits,
+        comments: &comments,
+        private_comments,
         base,
     };
     state.render("run.jinja", ctx)
@@ -50,17 +99,98 @@ pub async fn delete(
     user: User,
 ) -> Result<Redirect, Error> {
     let (_game, member) = game_n_member(&state, user, &game_slug).await?;
-    let run = query!("SELECT submitter FROM runs WHERE id = $1", run_id.get())
-        .fetch_optional(&state.postgres)
-        .await?
-        .ok_or(Error::NotFound)?;
+    let run = query!(
+        "SELECT submitter, category, game FROM runs WHERE id = $1",
+        run_id.get()
+    )
+    .fetch_optional(&state.postgres)
+    .await?
+    .ok_or(Error::NotFound)?;
     if !member.perms.contains(Permissions::LEADERBOARD_MODERATOR)
         && member.user.id.get() != run.submitter
     {
         return Err(Error::InsufficientPermissions);
     }
+    let mut trans = state.postgres.begin().await?;
     query!("DELETE FROM runs WHERE id = $1", run_id.get())
-        .execute(&state.postgres)
+        .execute(trans.as_mut())
+        .await?;
+    // the deleted run might have been obsoleting an older one
+    Category::update_obsoleted(
+        trans.as_mut(),
+        Id::new(run.category),
+        Some(Id::new(run.submitter)),
+    )
+    .await?;
+    trans.commit().await?;
+    GameRanking::invalidate(&state, Id::new(run.game)).await?;
+    // deleting a missing object still succeeds, so this is fine for runs without splits
+    state
+        .delete_r2_file(&format!("/runs/{run_id}/splits.lss"))
         .await?;
     Ok(state.redirect(format!("/game/{game_slug}")))
 }
+
+pub async fn comment(
+    State(state): State<AppState>,
+    Path((game_slug, category_id, run_id)): Path<(String, Id<CategoryMarker>, Id<RunMarker>)>,
+    user: User,
+    ValidatedForm(form): ValidatedForm<RunCommentForm>,
+) -> Result<Redirect, Error> {
+    let (game, member) = game_n_member(&state, user, &game_slug).await?;
+    query!(
+        "SELECT id FROM runs WHERE id = $1 AND game = $2 AND category = $3",
+        run_id.get(),
+        game.id.get(),
+        category_id.get()
+    )
+    .fetch_optional(&state.postgres)
+    .await?
+    .ok_or(Error::NotFound)?;
+    if form.private {
+        member.perms.check(Permissions::VERIFY_RUNS)?;
+    }
+    let id = query!(
+        "INSERT INTO run_comments (parent, game, author, content, private, created_at)
+        VALUES ($1, $2, $3, $4, $5, NOW()) RETURNING id",
+        run_id.get(),
+        game.id.get(),
+        member.user.id.get(),
+        form.content,
+        form.private
+    )
+    .fetch_one(&state.postgres)
+    .await?
+    .id;
+    Ok(state.redirect(format!(
+        "/game/{game_slug}/category/{category_id}/run/{run_id}#comment-{id}"
+    )))
+}
+
+pub async fn delete_comment(
+    State(state): State<AppState>,
+    Path((game_slug, category_id, run_id, comment_id)): Path<RunCommentPath>,
+    user: User,
+) -> Result<Redirect, Error> {
+    let (game, member) = game_n_member(&state, user, &game_slug).await?;
+    let comment = query!(
+        "SELECT author FROM run_comments WHERE id = $1 AND parent = $2 AND game = $3",
+        comment_id.get(),
+        run_id.get(),
+        game.id.get()
+    )
+    .fetch_optional(&state.postgres)
+    .await?
+    .ok_or(Error::NotFound)?;
+    if comment.author != member.user.id.get()
+        && !member.perms.contains(Permissions::FORUM_MODERATOR)
+    {
+        return Err(Error::InsufficientPermissions);
+    }
+    query!("DELETE FROM run_comments WHERE id = $1", comment_id.get())
+        .execute(&state.postgres)
+        .await?;
+    Ok(state.redirect(format!(
+        "/game/{game_slug}/category/{category_id}/run/{run_id}#comments"
+    )))
+}
@@ -0,0 +1,52 @@
+use axum::{
+    extract::{Path, State},
+    response::Redirect,
+};
+
+use crate::{
+    id::{CannedReasonMarker, Id},
+    model::{Permissions, User},
+    util::{self, ValidatedForm},
+    AppState, Error,
+};
+
+#[derive(serde::Deserialize, Clone, Debug, garde::Validate)]
+pub struct NewCannedReasonForm {
+    #[garde(length(min = crate::util::MIN_CANNED_REASON_LEN, max = crate::util::MAX_CANNED_REASON_LEN))]
+    reason: String,
+}
+
+pub async fn new(
+    State(state): State<AppState>,
+    Path(game_slug): Path<String>,
+    user: User,
+    ValidatedForm(form): ValidatedForm<NewCannedReasonForm>,
+) -> Result<Redirect, Error> {
+    let (game, member) = util::game_n_member(&state, user, &game_slug).await?;
+    member.perms.check(Permissions::ADMINISTRATOR)?;
+    query!(
+        "INSERT INTO canned_reasons (game, reason) VALUES ($1, $2)",
+        game.id.get(),
+        form.reason
+    )
+    .execute(&state.postgres)
+    .await?;
+    Ok(state.redirect(format!("/game/{game_slug}/edit")))
+}
+
+pub async fn delete(
+    State(state): State<AppState>,
+    Path((game_slug, reason_id)): Path<(String, Id<CannedReasonMarker>)>,
+    user: User,
+) -> Result<Redirect, Error> {
+    let (game, member) = util::game_n_member(&state, user, &game_slug).await?;
+    member.perms.check(Permissions::ADMINISTRATOR)?;
+    query!(
+        "DELETE FROM canned_reasons WHERE id = $1 AND game = $2",
+        reason_id.get(),
+        game.id.get()
+    )
+    .execute(&state.postgres)
+    .await?;
+    Ok(state.redirect(format!("/game/{game_slug}/edit")))
+}
@@ -5,7 +5,9 @@ use axum::{
 
 use crate::{
     id::{CategoryMarker, Id},
-    model::{Category, Game, Permissions, User},
+    model::{
+        Category, Game, GameRanking, Permissions, TimingMethod, TimingMethods, User, Variable,
+    },
     template::{BaseRenderInfo, ConfirmContext},
     util::{self, ValidatedForm},
     AppState, Error, HandlerResult,
@@ -16,6 +18,7 @@ pub struct CategoryEditPage {
     #[serde(flatten)]
     base: BaseRenderInfo,
     category: Category,
+    variables: Vec<Variable>,
     game: Game,
 }
 
@@ -30,6 +33,59 @@ pub struct NewCategoryForm {
     #[serde(default = "crate::util::return_false")]
     #[garde(skip)]
     scoreboard: bool,
+    #[serde(default = "crate::util::return_false")]
+    #[garde(skip)]
+    per_level: bool,
+    #[serde(default = "crate::util::return_false")]
+    #[garde(skip)]
+    rta: bool,
+    #[serde(default = "crate::util::return_false")]
+    #[garde(skip)]
+    igt: bool,
+    #[serde(default = "crate::util::return_false")]
+    #[garde(skip)]
+    lrt: bool,
+    #[serde(default)]
+    #[garde(skip)]
+    primary_timing: TimingMethod,
+    #[serde(default = "crate::util::return_1_i16")]
+    #[garde(range(min = 1, max = crate::util::MAX_CATEGORY_PLAYERS_I16))]
+    players: i16,
+    #[serde(default = "crate::util::return_false")]
+    #[garde(skip)]
+    lower_score_wins: bool,
+    #[serde(default)]
+    #[garde(range(min = 0, max = crate::util::MAX_SCORE_PRECISION))]
+    score_precision: i16,
+    #[serde(default)]
+    #[garde(length(max = crate::util::MAX_SCORE_UNIT_LEN))]
+    score_unit: String,
+}
+
+impl NewCategoryForm {
+    /// Get the accepted timing methods, making sure the primary one is among them.
+    /// Accepting nothing is treated as accepting only real time.
+    fn timing_methods(&self) -> Result<TimingMethods, Error> {
+        let mut methods = TimingMethods::EMPTY;
+        for (accepted, method) in [
+            (self.rta, TimingMethods::RTA),
+            (self.igt, TimingMethods::IGT),
+            (self.lrt, TimingMethods::LRT),
+        ] {
+            if accepted {
+                methods |= method;
+            }
+        }
+        if methods.is_empty() {
+            methods = TimingMethods::RTA;
+        }
+        if !methods.contains(self.primary_timing) {
+            return Err(Error::CustomFormValidation(
+                "the primary timing method must be one of the accepted ones".to_string(),
+            ));
+        }
+        Ok(methods)
+    }
 }
 
 pub async fn new(
@@ -40,15 +96,28 @@ pub async fn new(
 ) -> Result<Redirect, Error> {
     let (game, member) = util::game_n_member(&state, user, &game_slug).await?;
     member.perms.check(Permissions::ADMINISTRATOR)?;
+    let timing_methods = form.timing_methods()?;
     let cat_id = query!(
-        "INSERT INTO categories (game, name, description, rules, scoreboard)
-        VALUES ($1, $2, $3, $4, $5)
+        "INSERT INTO categories
+        (
+            game, name, description, rules, scoreboard,
+            per_level, timing_methods, primary_timing, players,
+            lower_score_wins, score_precision, score_unit
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)
         RETURNING id",
         game.id.get(),
         form.name,
         form.description,
         form.rules,
-        form.scoreboard
+        form.scoreboard,
+        form.per_level,
+        timing_methods.get(),
+        i16::from(form.primary_timing),
+        form.players,
+        form.lower_score_wins,
+        form.score_precision,
+        form.score_unit.trim()
     )
     .fetch_one(&state.postgres)
     .await?
@@ -91,6 +160,7 @@ pub async fn delete(
     )
     .execute(&state.postgres)
     .await?;
+    GameRanking::invalidate(&state, game.id).await?;
     Ok(state.redirect(format!("/game/{game_slug}/edit")))
 }
 
@@ -103,20 +173,36 @@ pub async fn edit(
     let (game, member) = util::game_n_member(&state, user, &game_slug).await?;
     member.perms.check(Permissions::ADMINISTRATOR)?;
     println!("{form:?}");
+    let timing_methods = form.timing_methods()?;
+    if form.per_level {
+        Category::check_can_be_per_level(&state.postgres, category_id).await?;
+    }
     query!(
         "UPDATE categories
             SET name = $3, description = $4,
-            rules = $5, scoreboard = $6
+            rules = $5, scoreboard = $6, per_level = $7,
+            timing_methods = $8, primary_timing = $9, players = $10,
+            lower_score_wins = $11, score_precision = $12, score_unit = $13
             WHERE id = $1 AND game = $2",
         category_id.get(),
         game.id.get(),
         form.name,
         form.description,
         form.rules,
-        form.scoreboard
+        form.scoreboard,
+        form.per_level,
+        timing_methods.get(),
+        i16::from(form.primary_timing),
+        form.players,
+        form.lower_score_wins,
+        form.score_precision,
+        form.score_unit.trim()
     )
     .execute(&state.postgres)
     .await?;
+    // changing how runs are ranked can change which runs are personal bests
+    Category::update_obsoleted(&state.postgres, category_id, None).await?;
+    GameRanking::invalidate(&state, game.id).await?;
     Ok(state.redirect(format!("/game/{game_slug}/category/{category_id}/edit")))
 }
 
@@ -129,9 +215,11 @@ pub async fn get(
     let (game, member) = util::game_n_member(&state, user, &game_slug).await?;
     member.perms.check(Permissions::ADMINISTRATOR)?;
     let category = Category::from_db(&state, category_id).await?;
+    let variables = Variable::from_db_category(&state, category.id).await?;
     let ctx = CategoryEditPage {
         base,
         category,
+        variables,
         game,
     };
     state.render("edit_category.jinja", ctx)
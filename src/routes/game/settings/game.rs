@@ -2,10 +2,14 @@ use axum::{
     extract::{Path, State},
     response::Redirect,
 };
+use redis::AsyncCommands;
 
 use crate::{
     id::{CategoryMarker, Id},
-    model::{Category, Game, Permissions, User},
+    model::{
+        CannedReason, Category, Game, GameRanking, Level, Permissions, Platform, RankingFormula,
+        RankingSettings, TwoFactor, User,
+    },
     template::BaseRenderInfo,
     util::{self, ValidatedForm},
     AppState, Error, HandlerResult,
@@ -15,6 +19,11 @@ use crate::{
 pub struct GameEditPage {
     game: Game,
     categories: Vec<Category>,
+    levels: Vec<Level>,
+    platforms: Vec<Platform>,
+    ranking: RankingSettings,
+    canned_reasons: Vec<CannedReason>,
+    require_two_factor: bool,
     #[serde(flatten)]
     base: BaseRenderInfo,
 }
@@ -29,6 +38,21 @@ pub struct GameEditForm {
     description: String,
 }
 
+#[derive(serde::Deserialize, garde::Validate, Clone, Debug)]
+pub struct RankingSettingsForm {
+    #[garde(skip)]
+    formula: RankingFormula,
+    #[garde(range(min = 1, max = crate::util::MAX_RANKING_POINTS))]
+    first_place_points: i64,
+}
+
+#[derive(serde::Deserialize, garde::Validate, Clone, Debug)]
+pub struct TwoFactorPolicyForm {
+    #[garde(skip)]
+    #[serde(default = "crate::util::return_false")]
+    require_two_factor: bool,
+}
+
 pub async fn get(
     State(state): State<AppState>,
     Path(game_slug): Path<String>,
@@ -39,16 +63,26 @@ pub async fn get(
     member.perms.check(Permissions::ADMINISTRATOR)?;
     let categories = query_as!(
         Category,
-        "SELECT name, id, game, scoreboard,
-        description, rules, flags
+        "SELECT name, id, game, scoreboard, per_level,
+        timing_methods, primary_timing, players, description, rules,
+        lower_score_wins, score_precision, score_unit, flags
         FROM categories WHERE game = $1",
         game.id.get()
     )
     .fetch_all(&state.postgres)
     .await?;
+    let levels = Level::from_db_game(&state, game.id).await?;
+    let platforms = Platform::from_db_game(&state, game.id).await?;
+    let ranking = RankingSettings::from_db(&state, game.id).await?;
+    let canned_reasons = CannedReason::from_db_game(&state, game.id).await?;
     let context = GameEditPage {
+        require_two_factor: game.requires_two_factor(),
         game,
         categories,
+        levels,
+        platforms,
+        ranking,
+        canned_reasons,
         base,
     };
     state.render("edit_game.jinja", context)
@@ -74,6 +108,59 @@ pub async fn edit(
     Ok(state.redirect(format!("/game/{}/edit", game.slug)))
 }
 
+pub async fn edit_ranking(
+    State(state): State<AppState>,
+    Path(game_slug): Path<String>,
+    user: User,
+    ValidatedForm(form): ValidatedForm<RankingSettingsForm>,
+) -> Result<Redirect, Error> {
+    let (game, member) = util::game_n_member(&state, user, &game_slug).await?;
+    member.perms.check(Permissions::ADMINISTRATOR)?;
+    query!(
+        "INSERT INTO ranking_settings (game, formula, first_place_points)
+        VALUES ($1, $2, $3)
+        ON CONFLICT (game) DO UPDATE
+        SET formula = excluded.formula, first_place_points = excluded.first_place_points",
+        game.id.get(),
+        i16::from(form.formula),
+        form.first_place_points
+    )
+    .execute(&state.postgres)
+    .await?;
+    GameRanking::invalidate(&state, game.id).await?;
+    Ok(state.redirect(format!("/game/{game_slug}/edit")))
+}
+
+pub async fn edit_two_factor(
+    State(state): State<AppState>,
+    Path(game_slug): Path<String>,
+    user: User,
+    ValidatedForm(form): ValidatedForm<TwoFactorPolicyForm>,
+) -> Result<Redirect, Error> {
+    let (game, member) = util::game_n_member(&state, user, &game_slug).await?;
+    member.perms.check(Permissions::ADMINISTRATOR)?;
+    // otherwise turning the policy on would lock them out of this page
+    if form.require_two_factor && !TwoFactor::is_enabled(&state, member.user.id).await? {
+        return Err(Error::TwoFactorRequired);
+    }
+    query!(
+        "UPDATE games SET flags = CASE WHEN $2 THEN flags | $3 ELSE flags & ~$3 END
+        WHERE id = $1",
+        game.id.get(),
+        form.require_two_factor,
+        Game::REQUIRE_TWO_FACTOR
+    )
+    .execute(&state.postgres)
+    .await?;
+    state
+        .redis
+        .get()
+        .await?
+        .del(format!("game:{}", game.slug))
+        .await?;
+    Ok(state.redirect(format!("/game/{game_slug}/edit")))
+}
+
 pub async fn set_default_category(
     State(state): State<AppState>,
     Path((game_slug, category_id)): Path<(String, Id<CategoryMarker>)>,
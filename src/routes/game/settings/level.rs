@@ -0,0 +1,66 @@
+use axum::{
+    extract::{Path, State},
+    response::Redirect,
+};
+
+use crate::{
+    id::{Id, LevelMarker},
+    model::{Level, Permissions, User},
+    template::{BaseRenderInfo, ConfirmContext},
+    util::{self, ValidatedForm},
+    AppState, Error, HandlerResult,
+};
+
+#[derive(serde::Deserialize, Clone, Debug, garde::Validate)]
+pub struct NewLevelForm {
+    #[garde(length(min = crate::util::MIN_LEVEL_NAME_LEN, max = crate::util::MAX_LEVEL_NAME_LEN))]
+    name: String,
+}
+
+pub async fn new(
+    State(state): State<AppState>,
+    Path(game_slug): Path<String>,
+    user: User,
+    ValidatedForm(form): ValidatedForm<NewLevelForm>,
+) -> Result<Redirect, Error> {
+    let (game, member) = util::game_n_member(&state, user, &game_slug).await?;
+    member.perms.check(Permissions::ADMINISTRATOR)?;
+    query!(
+        "INSERT INTO levels (game, name) VALUES ($1, $2)",
+        game.id.get(),
+        form.name
+    )
+    .execute(&state.postgres)
+    .await?;
+    Ok(state.redirect(format!("/game/{game_slug}/edit")))
+}
+
+#[allow(clippy::unused_async)]
+pub async fn confirm_delete(
+    State(state): State<AppState>,
+    user: User,
+    base: BaseRenderInfo,
+    Path((game_slug, level_id)): Path<(String, Id<LevelMarker>)>,
+) -> HandlerResult {
+    let (_game, member) = util::game_n_member(&state, user, &game_slug).await?;
+    member.perms.check(Permissions::ADMINISTRATOR)?;
+    let ctx = ConfirmContext {
+        base,
+        action: "edit_game.levels.confirm_delete".to_string(),
+        action_url: format!("/game/{game_slug}/edit/level/{level_id}/delete"),
+        return_to: format!("/game/{game_slug}/edit"),
+    };
+    state.render("confirm.jinja", ctx)
+}
+
+pub async fn delete(
+    State(state): State<AppState>,
+    Path((game_slug, level_id)): Path<(String, Id<LevelMarker>)>,
+    user: User,
+) -> Result<Redirect, Error> {
+    let (game, member) = util::game_n_member(&state, user, &game_slug).await?;
+    member.perms.check(Permissions::ADMINISTRATOR)?;
+    let mut conn = state.postgres.acquire().await?;
+    Level::delete(&mut conn, game.id, level_id).await?;
+    Ok(state.redirect(format!("/game/{game_slug}/edit")))
+}
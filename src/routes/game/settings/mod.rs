@@ -1,2 +1,6 @@
+pub mod canned_reason;
 pub mod category;
 pub mod game;
+pub mod level;
+pub mod platform;
+pub mod variable;
@@ -0,0 +1,112 @@
+use axum::{
+    extract::{Path, State},
+    response::Redirect,
+};
+
+use crate::{
+    id::{CategoryMarker, Id, VariableMarker, VariableValueMarker},
+    model::{Category, GameRanking, Permissions, User, VariableValue},
+    util::{self, ValidatedForm},
+    AppState, Error,
+};
+
+type VariableValuePath = (
+    String,
+    Id<CategoryMarker>,
+    Id<VariableMarker>,
+    Id<VariableValueMarker>,
+);
+
+#[derive(serde::Deserialize, Clone, Debug, garde::Validate)]
+pub struct NewVariableForm {
+    #[garde(length(min = crate::util::MIN_VARIABLE_NAME_LEN, max = crate::util::MAX_VARIABLE_NAME_LEN))]
+    name: String,
+}
+
+pub async fn new(
+    State(state): State<AppState>,
+    Path((game_slug, category_id)): Path<(String, Id<CategoryMarker>)>,
+    user: User,
+    ValidatedForm(form): ValidatedForm<NewVariableForm>,
+) -> Result<Redirect, Error> {
+    let (game, member) = util::game_n_member(&state, user, &game_slug).await?;
+    member.perms.check(Permissions::ADMINISTRATOR)?;
+    let category = Category::from_db(&state, category_id).await?;
+    if category.game != game.id {
+        return Err(Error::InvalidGameCategoryPair);
+    }
+    query!(
+        "INSERT INTO variables (game, category, name)
+        VALUES ($1, $2, $3)",
+        game.id.get(),
+        category.id.get(),
+        form.name
+    )
+    .execute(&state.postgres)
+    .await?;
+    Ok(state.redirect(format!("/game/{game_slug}/category/{category_id}/edit")))
+}
+
+pub async fn delete(
+    State(state): State<AppState>,
+    Path((game_slug, category_id, variable_id)): Path<(
+        String,
+        Id<CategoryMarker>,
+        Id<VariableMarker>,
+    )>,
+    user: User,
+) -> Result<Redirect, Error> {
+    let (game, member) = util::game_n_member(&state, user, &game_slug).await?;
+    member.perms.check(Permissions::ADMINISTRATOR)?;
+    query!(
+        "DELETE FROM variables WHERE id = $1 AND category = $2 AND game = $3",
+        variable_id.get(),
+        category_id.get(),
+        game.id.get()
+    )
+    .execute(&state.postgres)
+    .await?;
+    // runs which were on separate boards might now be on the same one
+    Category::update_obsoleted(&state.postgres, category_id, None).await?;
+    GameRanking::invalidate(&state, game.id).await?;
+    Ok(state.redirect(format!("/game/{game_slug}/category/{category_id}/edit")))
+}
+
+pub async fn new_value(
+    State(state): State<AppState>,
+    Path((game_slug, category_id, variable_id)): Path<(
+        String,
+        Id<CategoryMarker>,
+        Id<VariableMarker>,
+    )>,
+    user: User,
+    ValidatedForm(form): ValidatedForm<NewVariableForm>,
+) -> Result<Redirect, Error> {
+    let (game, member) = util::game_n_member(&state, user, &game_slug).await?;
+    member.perms.check(Permissions::ADMINISTRATOR)?;
+    let mut trans = state.postgres.begin().await?;
+    VariableValue::create(
+        trans.as_mut(),
+        game.id,
+        category_id,
+        variable_id,
+        &form.name,
+    )
+    .await?;
+    trans.commit().await?;
+    GameRanking::invalidate(&state, game.id).await?;
+    Ok(state.redirect(format!("/game/{game_slug}/category/{category_id}/edit")))
+}
+
+pub async fn delete_value(
+    State(state): State<AppState>,
+    Path((game_slug, category_id, variable_id, value_id)): Path<VariableValuePath>,
+    user: User,
+) -> Result<Redirect, Error> {
+    let (game, member) = util::game_n_member(&state, user, &game_slug).await?;
+    member.perms.check(Permissions::ADMINISTRATOR)?;
+    let mut trans = state.postgres.begin().await?;
+    VariableValue::delete(trans.as_mut(), game.id, category_id, variable_id, value_id).await?;
+    trans.commit().await?;
+    Ok(state.redirect(format!("/game/{game_slug}/category/{category_id}/edit")))
+}
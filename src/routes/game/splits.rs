@@ -0,0 +1,89 @@
+use axum::{
+    extract::{Path, State},
+    response::Redirect,
+};
+use axum_extra::extract::multipart::Multipart;
+use sqlx::{Postgres, Transaction};
+
+use super::edit_run::check_can_edit;
+use crate::{
+    id::{CategoryMarker, Id, RunMarker},
+    model::{ResolvedRun, Splits, User},
+    routes::settings::files::multipart_into_bytes,
+    util::game_n_member,
+    AppState, Error,
+};
+
+fn splits_path(run: Id<RunMarker>) -> String {
+    format!("/runs/{run}/splits.lss")
+}
+
+/// Store `splits` for `run` and commit `trans`, along with the `lss` file they were read from.
+/// The file is only uploaded once the segments are written, and nothing is kept if it fails.
+pub async fn save(
+    state: &AppState,
+    mut trans: Transaction<'_, Postgres>,
+    run: Id<RunMarker>,
+    splits: &Splits,
+    lss: &[u8],
+) -> Result<(), Error> {
+    splits.save(trans.as_mut(), run).await?;
+    state
+        .put_r2_file(&splits_path(run), lss, "application/xml")
+        .await?;
+    trans.commit().await?;
+    Ok(())
+}
+
+async fn editable_run(
+    state: &AppState,
+    user: User,
+    game_slug: &str,
+    category_id: Id<CategoryMarker>,
+    run_id: Id<RunMarker>,
+) -> Result<ResolvedRun, Error> {
+    let (game, member) = game_n_member(state, user, game_slug).await?;
+    let run = ResolvedRun::from_db(state, run_id)
+        .await?
+        .ok_or(Error::NotFound)?;
+    if run.game.id != game.id || run.category.id != category_id {
+        return Err(Error::NotFound);
+    }
+    check_can_edit(&member, &run)?;
+    Ok(run)
+}
+
+pub async fn upload(
+    State(state): State<AppState>,
+    Path((game_slug, category_id, run_id)): Path<(String, Id<CategoryMarker>, Id<RunMarker>)>,
+    user: User,
+    multipart: Multipart,
+) -> Result<Redirect, Error> {
+    let run = editable_run(&state, user, &game_slug, category_id, run_id).await?;
+    let (_ctype, bytes) = multipart_into_bytes(multipart, "splits").await?;
+    let splits = Splits::from_upload(&bytes, run.category.primary_timing)?;
+    save(
+        &state,
+        state.postgres.begin().await?,
+        run_id,
+        &splits,
+        &bytes,
+    )
+    .await?;
+    Ok(state.redirect(format!(
+        "/game/{game_slug}/category/{category_id}/run/{run_id}"
+    )))
+}
+
+pub async fn delete(
+    State(state): State<AppState>,
+    Path((game_slug, category_id, run_id)): Path<(String, Id<CategoryMarker>, Id<RunMarker>)>,
+    user: User,
+) -> Result<Redirect, Error> {
+    editable_run(&state, user, &game_slug, category_id, run_id).await?;
+    Splits::delete(&state.postgres, run_id).await?;
+    state.delete_r2_file(&splits_path(run_id)).await?;
+    Ok(state.redirect(format!(
+        "/game/{game_slug}/category/{category_id}/run/{run_id}"
+    )))
+}
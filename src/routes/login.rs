@@ -3,13 +3,14 @@ use axum::{
     response::Redirect,
 };
 use axum_extra::extract::{cookie::Cookie, CookieJar};
-use rand::distributions::DistString;
 use redis::AsyncCommands;
 
 use crate::{
-    model::User,
+    id::{Id, UserMarker},
+    model::{ClientInfo, LoginAttempt, Session, TwoFactor, User},
+    oauth::ProviderInfo,
     template::BaseRenderInfo,
-    util::{auth_cookie, ValidatedForm, AUTHTOKEN_COOKIE, AUTHTOKEN_TTL},
+    util::{auth_cookie, ValidatedForm, AUTHTOKEN_COOKIE},
     AppState, Error, HandlerResult,
 };
 
@@ -18,6 +19,18 @@ pub struct LoginPage {
     #[serde(flatten)]
     base: BaseRenderInfo,
     incorrect: bool,
+    /// Whether the user took too long or too many tries to enter their two-factor code
+    expired: bool,
+    return_to: String,
+    oauth_providers: Vec<ProviderInfo>,
+}
+
+#[derive(serde::Serialize, Debug, Clone)]
+pub struct TwoFactorLoginPage {
+    #[serde(flatten)]
+    base: BaseRenderInfo,
+    incorrect: bool,
+    token: String,
     return_to: String,
 }
 
@@ -31,6 +44,16 @@ pub struct LoginForm {
     return_to: String,
 }
 
+#[derive(serde::Deserialize, garde::Validate, Clone, Debug)]
+pub struct TwoFactorLoginForm {
+    #[garde(skip)]
+    token: String,
+    #[garde(length(max = 64))]
+    code: String,
+    #[garde(skip)]
+    return_to: String,
+}
+
 #[derive(serde::Deserialize, Debug, Clone)]
 pub struct LoginQuery {
     #[serde(default = "crate::util::default_return_to")]
@@ -47,6 +70,8 @@ pub async fn get(
         base,
         return_to: query.return_to,
         incorrect: false,
+        expired: false,
+        oauth_providers: crate::oauth::provider_infos(&state.config.oauth_providers),
     };
     state.render("login.jinja", ctx)
 }
@@ -55,6 +80,7 @@ pub async fn post(
     State(state): State<AppState>,
     cookies: CookieJar,
     base: BaseRenderInfo,
+    client: ClientInfo,
     ValidatedForm(form): ValidatedForm<LoginForm>,
 ) -> Result<Result<(CookieJar, Redirect), HandlerResult>, Error> {
     let Ok(user) = User::from_db_auth(&state, &state.postgres, form.email, form.password).await?
@@ -63,26 +89,70 @@ pub async fn post(
             base,
             return_to: form.return_to,
             incorrect: true,
+            expired: false,
+            oauth_providers: crate::oauth::provider_infos(&state.config.oauth_providers),
         };
         return Ok(Err(state.render("login.jinja", ctx)));
     };
-    let token = rand::distributions::Alphanumeric.sample_string(&mut rand::thread_rng(), 64);
-    state
-        .redis
-        .get()
-        .await?
-        .set_ex(format!("token:user:{token}"), user.id.get(), AUTHTOKEN_TTL)
-        .await?;
-    state
-        .redis
-        .get()
-        .await?
-        .set_ex(
-            format!("user:{}", user.id),
-            serde_json::to_string(&user)?,
-            AUTHTOKEN_TTL,
-        )
-        .await?;
+    if TwoFactor::is_enabled(&state, user.id).await? {
+        return Ok(Err(
+            two_factor_page(&state, base, user.id, form.return_to).await
+        ));
+    }
+    let token = Session::start(&state, &user, &client).await?;
+    Ok(Ok((
+        cookies.add(auth_cookie(token)),
+        state.redirect(form.return_to),
+    )))
+}
+
+/// Ask `user`, who has proven who they are some other way, for their two-factor code
+pub(crate) async fn two_factor_page(
+    state: &AppState,
+    base: BaseRenderInfo,
+    user: Id<UserMarker>,
+    return_to: String,
+) -> HandlerResult {
+    let ctx = TwoFactorLoginPage {
+        base,
+        incorrect: false,
+        token: TwoFactor::start_login(state, user).await?,
+        return_to,
+    };
+    state.render("login_two_factor.jinja", ctx)
+}
+
+/// The second step of logging in, for users with two-factor authentication
+pub async fn two_factor(
+    State(state): State<AppState>,
+    cookies: CookieJar,
+    base: BaseRenderInfo,
+    client: ClientInfo,
+    ValidatedForm(form): ValidatedForm<TwoFactorLoginForm>,
+) -> Result<Result<(CookieJar, Redirect), HandlerResult>, Error> {
+    let user = match TwoFactor::finish_login(&state, &form.token, &form.code).await? {
+        LoginAttempt::Success(id) => User::from_db(&state, id).await?,
+        LoginAttempt::Incorrect => {
+            let ctx = TwoFactorLoginPage {
+                base,
+                incorrect: true,
+                token: form.token,
+                return_to: form.return_to,
+            };
+            return Ok(Err(state.render("login_two_factor.jinja", ctx)));
+        }
+        LoginAttempt::Expired => {
+            let ctx = LoginPage {
+                base,
+                return_to: form.return_to,
+                incorrect: false,
+                expired: true,
+                oauth_providers: crate::oauth::provider_infos(&state.config.oauth_providers),
+            };
+            return Ok(Err(state.render("login.jinja", ctx)));
+        }
+    };
+    let token = Session::start(&state, &user, &client).await?;
     Ok(Ok((
         cookies.add(auth_cookie(token)),
         state.redirect(form.return_to),
@@ -96,13 +166,7 @@ pub async fn logout(
     let Some(token) = cookies.get(AUTHTOKEN_COOKIE).map(Cookie::value) else {
         return Ok((cookies, state.redirect("/")));
     };
-    let maybe_id: Option<String> = state
-        .redis
-        .get()
-        .await?
-        .get_del(format!("token:user:{token}"))
-        .await?;
-    let Some(id) = maybe_id else {
+    let Some(id) = Session::end(&state, token).await? else {
         return Ok((cookies, state.redirect("/")));
     };
     state.redis.get().await?.del(format!("user:{id}")).await?;
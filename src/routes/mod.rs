@@ -10,9 +10,13 @@ pub mod forum;
 pub mod game;
 pub mod index;
 pub mod login;
+pub mod oauth;
+pub mod reset_password;
+pub mod search;
 pub mod settings;
 pub mod signup;
 pub mod user;
+pub mod verify_email;
 
 #[derive(serde::Serialize, Clone, Debug)]
 pub struct NotFoundPage {
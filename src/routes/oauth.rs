@@ -0,0 +1,142 @@
+use axum::{
+    extract::{Path, Query, State},
+    response::Redirect,
+};
+use axum_extra::extract::{
+    cookie::{Cookie, SameSite},
+    CookieJar,
+};
+use s3::creds::time::Duration as S3Duration;
+
+use crate::{
+    model::{ClientInfo, LinkedAccount, Session, TwoFactor, User},
+    oauth::{find_provider, take_pending, OAuthProvider},
+    template::BaseRenderInfo,
+    util::auth_cookie,
+    AppState, Error, HandlerResult,
+};
+
+/// Holds the state of a login that's been sent to a provider, so it can only be finished
+/// by the browser that started it. The auth cookie is `Strict`, so this one carries
+/// the login across the provider's redirect back instead.
+const OAUTH_STATE_COOKIE: &str = "oauth_state";
+
+#[derive(serde::Serialize, Debug, Clone)]
+pub struct OAuthContinuePage {
+    #[serde(flatten)]
+    base: BaseRenderInfo,
+    return_to: String,
+}
+
+#[derive(serde::Deserialize, Debug, Clone)]
+pub struct OAuthStartQuery {
+    #[serde(default = "crate::util::default_return_to")]
+    return_to: String,
+}
+
+#[derive(serde::Deserialize, Debug, Clone)]
+pub struct OAuthCallbackQuery {
+    code: Option<String>,
+    state: Option<String>,
+    error: Option<String>,
+}
+
+pub async fn start(
+    State(state): State<AppState>,
+    Path(provider): Path<String>,
+    Query(query): Query<OAuthStartQuery>,
+    cookies: CookieJar,
+) -> Result<(CookieJar, Redirect), Error> {
+    let provider = find_provider(&state.config.oauth_providers, &provider)?;
+    let (url, csrf_state) = provider.authorize(&state, query.return_to, None).await?;
+    Ok((cookies.add(state_cookie(csrf_state)), Redirect::to(&url)))
+}
+
+pub async fn link(
+    State(state): State<AppState>,
+    Path(provider): Path<String>,
+    user: User,
+    cookies: CookieJar,
+) -> Result<(CookieJar, Redirect), Error> {
+    let provider = find_provider(&state.config.oauth_providers, &provider)?;
+    let (url, csrf_state) = provider
+        .authorize(&state, "/settings".to_string(), Some(user.id))
+        .await?;
+    Ok((cookies.add(state_cookie(csrf_state)), Redirect::to(&url)))
+}
+
+pub async fn unlink(
+    State(state): State<AppState>,
+    Path(provider): Path<String>,
+    user: User,
+) -> Result<Redirect, Error> {
+    LinkedAccount::unlink(&state, user.id, &provider).await?;
+    Ok(state.redirect("/settings"))
+}
+
+pub async fn callback(
+    State(state): State<AppState>,
+    Path(provider): Path<String>,
+    Query(query): Query<OAuthCallbackQuery>,
+    cookies: CookieJar,
+    base: BaseRenderInfo,
+    client: ClientInfo,
+) -> Result<(CookieJar, HandlerResult), Error> {
+    let provider = find_provider(&state.config.oauth_providers, &provider)?;
+    if let Some(error) = query.error {
+        return Err(Error::OAuthDenied(error));
+    }
+    let csrf_state = query.state.ok_or(Error::InvalidOAuthState)?;
+    if cookies.get(OAUTH_STATE_COOKIE).map(Cookie::value) != Some(csrf_state.as_str()) {
+        return Err(Error::InvalidOAuthState);
+    }
+    let code = query.code.ok_or(Error::InvalidOAuthState)?;
+    let pending = take_pending(&state, &csrf_state).await?;
+    if pending.provider != provider.name {
+        return Err(Error::InvalidOAuthState);
+    }
+    let endpoints = provider.endpoints(&state).await?;
+    let redirect_uri = provider.redirect_uri(&state.config.root_url);
+    let identity = provider
+        .exchange(&state.http, &endpoints, &redirect_uri, &code, &pending)
+        .await?;
+    let cookies = cookies.remove(Cookie::build(OAUTH_STATE_COOKIE).path("/"));
+
+    if let Some(user) = pending.link_user {
+        LinkedAccount::link(&state, user, &provider.name, &identity).await?;
+        return Ok((cookies, continue_page(&state, base, pending.return_to)));
+    }
+
+    let user = match LinkedAccount::find_user(&state, &provider.name, &identity.subject).await? {
+        Some(id) => User::from_db(&state, id).await?,
+        None => LinkedAccount::sign_up(&state, &provider.name, &identity).await?,
+    };
+    if TwoFactor::is_enabled(&state, user.id).await? {
+        let page =
+            crate::routes::login::two_factor_page(&state, base, user.id, pending.return_to).await;
+        return Ok((cookies, page));
+    }
+    let token = Session::start(&state, &user, &client).await?;
+    Ok((
+        cookies.add(auth_cookie(token)),
+        continue_page(&state, base, pending.return_to),
+    ))
+}
+
+/// Browsers don't send `Strict` cookies on requests that come from another site,
+/// including redirects from the provider, so users click through from here instead
+fn continue_page(state: &AppState, base: BaseRenderInfo, return_to: String) -> HandlerResult {
+    let ctx = OAuthContinuePage { base, return_to };
+    state.render("oauth_continue.jinja", ctx)
+}
+
+fn state_cookie<'a>(csrf_state: String) -> Cookie<'a> {
+    const STATE_TTL: S3Duration = S3Duration::seconds(OAuthProvider::STATE_TTL as i64);
+    Cookie::build((OAUTH_STATE_COOKIE, csrf_state))
+        .path("/")
+        .secure(true)
+        .http_only(true)
+        .max_age(STATE_TTL)
+        .same_site(SameSite::Lax)
+        .build()
+}
@@ -0,0 +1,96 @@
+use axum::{
+    extract::{Query, State},
+    response::Redirect,
+};
+
+use crate::{
+    model::PasswordReset, template::BaseRenderInfo, util::ValidatedForm, AppState, Error,
+    HandlerResult,
+};
+
+#[derive(serde::Serialize, Debug, Clone)]
+pub struct ForgotPasswordPage {
+    #[serde(flatten)]
+    base: BaseRenderInfo,
+    sent: bool,
+}
+
+#[derive(serde::Deserialize, Debug, Clone)]
+pub struct ForgotPasswordQuery {
+    #[serde(default = "crate::util::return_false")]
+    sent: bool,
+}
+
+#[derive(serde::Deserialize, garde::Validate, Clone, Debug)]
+pub struct ForgotPasswordForm {
+    #[garde(email, length(min = crate::util::MIN_EMAIL_LEN, max = crate::util::MAX_EMAIL_LEN))]
+    email: String,
+}
+
+#[derive(serde::Serialize, Debug, Clone)]
+pub struct ResetPasswordPage {
+    #[serde(flatten)]
+    base: BaseRenderInfo,
+    token: String,
+}
+
+#[derive(serde::Deserialize, Debug, Clone)]
+pub struct ResetPasswordQuery {
+    token: String,
+}
+
+#[derive(serde::Deserialize, garde::Validate, Clone, Debug)]
+pub struct ResetPasswordForm {
+    #[garde(skip)]
+    token: String,
+    #[garde(length(min = crate::util::MIN_PASSWORD_LEN))]
+    password: String,
+}
+
+#[allow(clippy::unused_async)]
+pub async fn forgot(
+    State(state): State<AppState>,
+    Query(query): Query<ForgotPasswordQuery>,
+    base: BaseRenderInfo,
+) -> HandlerResult {
+    let ctx = ForgotPasswordPage {
+        base,
+        sent: query.sent,
+    };
+    state.render("forgot_password.jinja", ctx)
+}
+
+pub async fn request(
+    State(state): State<AppState>,
+    ValidatedForm(form): ValidatedForm<ForgotPasswordForm>,
+) -> Result<Redirect, Error> {
+    // sent in the background, so how long this takes doesn't give away who has an account
+    let mail_state = state.clone();
+    tokio::spawn(async move {
+        if let Err(error) = PasswordReset::request(&mail_state, &form.email).await {
+            error!(?error, "failed to send password reset");
+        }
+    });
+    Ok(state.redirect("/forgot-password?sent=true"))
+}
+
+pub async fn get(
+    State(state): State<AppState>,
+    Query(query): Query<ResetPasswordQuery>,
+    base: BaseRenderInfo,
+) -> HandlerResult {
+    PasswordReset::check(&state, &query.token).await?;
+    let ctx = ResetPasswordPage {
+        base,
+        token: query.token,
+    };
+    state.render("reset_password.jinja", ctx)
+}
+
+pub async fn post(
+    State(state): State<AppState>,
+    ValidatedForm(form): ValidatedForm<ResetPasswordForm>,
+) -> Result<Redirect, Error> {
+    PasswordReset::redeem(&state, &form.token, form.password).await?;
+    Ok(state.redirect("/login"))
+}
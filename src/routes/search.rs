@@ -0,0 +1,207 @@
+use axum::extract::State;
+use axum_extra::extract::Query;
+use chrono::{NaiveDate, NaiveDateTime};
+
+use crate::{
+    id::{CategoryMarker, Id, RunMarker, UserMarker},
+    model::{
+        parse_duration, Category, Game, MiniCategory, ResolvedRun, RunSearch, RunStatus, SearchSort,
+    },
+    template::BaseRenderInfo,
+    AppState, Error, HandlerResult,
+};
+
+/// Every filter is a string, since the search form sends blank fields
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct SearchQuery {
+    /// game slug
+    #[serde(default)]
+    game: String,
+    #[serde(default)]
+    category: String,
+    /// username of a runner of the run
+    #[serde(default)]
+    runner: String,
+    /// `verified` (the default), `pending` or `rejected`
+    #[serde(default)]
+    status: String,
+    /// submitted on or after this date, as `YYYY-MM-DD`
+    #[serde(default)]
+    from: String,
+    /// submitted on or before this date, as `YYYY-MM-DD`
+    #[serde(default)]
+    to: String,
+    #[serde(default)]
+    min_time: String,
+    #[serde(default)]
+    max_time: String,
+    #[serde(default)]
+    sort: SearchSort,
+    /// the last run of the previous page
+    #[serde(skip_serializing)]
+    after: Option<Id<RunMarker>>,
+}
+
+#[derive(serde::Serialize, Debug, Clone)]
+pub struct SearchPage {
+    #[serde(flatten)]
+    base: BaseRenderInfo,
+    query: SearchQuery,
+    sorts: [SearchSort; 6],
+    /// categories of the game being searched, if there is one
+    categories: Vec<MiniCategory>,
+    runs: Vec<ResolvedRun>,
+    /// query string for the next page of results, if there is one
+    next_query: Option<String>,
+}
+
+const RESULTS_PER_PAGE: usize = 50;
+
+pub async fn get(
+    State(state): State<AppState>,
+    Query(query): Query<SearchQuery>,
+    base: BaseRenderInfo,
+) -> HandlerResult {
+    let game = match non_empty(&query.game) {
+        Some(slug) => Some(Game::from_db_slug(&state, slug).await.map_err(|error| {
+            if matches!(error, Error::NotFound) {
+                Error::CustomFormValidation(format!("no game has the slug {slug}"))
+            } else {
+                error
+            }
+        })?),
+        None => None,
+    };
+    let categories = match &game {
+        Some(game) => {
+            query_as!(
+                MiniCategory,
+                "SELECT name, id, game, scoreboard, per_level, flags
+                FROM categories WHERE game = $1 ORDER BY id",
+                game.id.get()
+            )
+            .fetch_all(&state.postgres)
+            .await?
+        }
+        None => Vec::new(),
+    };
+    let category = non_empty(&query.category)
+        .map(|raw| {
+            let id: Id<CategoryMarker> = Id::new(raw.parse().map_err(|_| invalid("category"))?);
+            // a category only makes sense along with its game
+            if categories.iter().any(|category| category.id == id) {
+                Ok(id)
+            } else {
+                Err(Error::InvalidGameCategoryPair)
+            }
+        })
+        .transpose()?;
+    // times are compared by the category's primary timing method
+    let timing = match category {
+        Some(id) => Some(Category::from_db(&state, id).await?.primary_timing),
+        None => None,
+    };
+    let runner = match non_empty(&query.runner) {
+        Some(username) => Some(runner_id(&state, username).await?),
+        None => None,
+    };
+    let status = match non_empty(&query.status) {
+        None | Some("verified") => RunStatus::Verified,
+        Some("pending") => RunStatus::Pending,
+        Some("rejected") => RunStatus::Rejected,
+        Some(_) => return Err(invalid("status")),
+    };
+    // runs which aren't verified are only shown to their runners
+    let runner = if status == RunStatus::Verified {
+        runner
+    } else {
+        let searcher = base
+            .logged_in_user
+            .as_ref()
+            .ok_or_else(|| Error::NeedsLogin("/search".to_string()))?;
+        if runner.is_some_and(|runner| runner != searcher.id) {
+            return Err(Error::InsufficientPermissions);
+        }
+        Some(searcher.id)
+    };
+    let search = RunSearch {
+        game: game.as_ref().map(|game| game.id),
+        category,
+        timing,
+        runner,
+        status: Some(status),
+        submitted_after: non_empty(&query.from).map(parse_date).transpose()?,
+        submitted_before: non_empty(&query.to)
+            .map(|raw| Ok::<_, Error>(parse_date(raw)? + chrono::Duration::days(1)))
+            .transpose()?,
+        min_time: non_empty(&query.min_time)
+            .map(|raw| parse_duration(raw).ok_or_else(|| invalid("minimum time")))
+            .transpose()?,
+        max_time: non_empty(&query.max_time)
+            .map(|raw| parse_duration(raw).ok_or_else(|| invalid("maximum time")))
+            .transpose()?,
+        sort: query.sort,
+        after: query.after,
+    };
+    let (runs, next) = ResolvedRun::search(&state, search, RESULTS_PER_PAGE).await?;
+    let next_query = next
+        .map(|after| next_page_query(&query, after))
+        .transpose()?;
+    let ctx = SearchPage {
+        base,
+        query,
+        sorts: SearchSort::ALL,
+        categories,
+        runs,
+        next_query,
+    };
+    state.render("search.jinja", ctx)
+}
+
+/// The query string of the page of results after `after`, keeping the filters of `query`
+fn next_page_query(query: &SearchQuery, after: Id<RunMarker>) -> Result<String, Error> {
+    let sort = serde_json::to_value(query.sort)?;
+    Ok(url::form_urlencoded::Serializer::new(String::new())
+        .extend_pairs([
+            ("game", query.game.as_str()),
+            ("category", &query.category),
+            ("runner", &query.runner),
+            ("status", &query.status),
+            ("from", &query.from),
+            ("to", &query.to),
+            ("min_time", &query.min_time),
+            ("max_time", &query.max_time),
+            ("sort", sort.as_str().unwrap_or_default()),
+            ("after", &after.to_string()),
+        ])
+        .finish())
+}
+
+fn non_empty(input: &str) -> Option<&str> {
+    Some(input.trim()).filter(|trimmed| !trimmed.is_empty())
+}
+
+fn invalid(field: &str) -> Error {
+    Error::CustomFormValidation(format!("invalid {field}"))
+}
+
+async fn runner_id(state: &AppState, username: &str) -> Result<Id<UserMarker>, Error> {
+    query!("SELECT id FROM users WHERE username = $1", username)
+        .fetch_optional(&state.postgres)
+        .await?
+        .map(|row| Id::new(row.id))
+        .ok_or_else(|| Error::CustomFormValidation(format!("no runner is named {username}")))
+}
+
+/// Parse a `YYYY-MM-DD` date into the start of that day
+fn parse_date(input: &str) -> Result<NaiveDateTime, Error> {
+    let mut parts = input.splitn(3, '-').map(str::parse::<u32>);
+    let (Some(Ok(year)), Some(Ok(month)), Some(Ok(day))) =
+        (parts.next(), parts.next(), parts.next())
+    else {
+        return Err(invalid("date"));
+    };
+    NaiveDate::from_ymd_opt(i32::try_from(year)?, month, day)
+        .and_then(|date| date.and_hms_opt(0, 0, 0))
+        .ok_or_else(|| invalid("date"))
+}
@@ -1,6 +1,11 @@
 use axum::{extract::State, response::Redirect};
+use axum_extra::extract::{cookie::Cookie, CookieJar};
 
-use crate::{model::User, util::ValidatedForm, AppState, Error};
+use crate::{
+    model::{EmailVerification, Session, User},
+    util::{ValidatedForm, AUTHTOKEN_COOKIE},
+    AppState, Error,
+};
 
 #[derive(serde::Deserialize, garde::Validate, Clone, Debug)]
 pub struct UpdateEmailForm {
@@ -24,6 +29,7 @@ pub struct UpdatePasswordForm {
 
 pub async fn update_password(
     State(state): State<AppState>,
+    cookies: CookieJar,
     ValidatedForm(form): ValidatedForm<UpdatePasswordForm>,
 ) -> Result<Redirect, Error> {
     let mut trans = state.postgres.begin().await?;
@@ -40,11 +46,15 @@ pub async fn update_password(
     .execute(trans.as_mut())
     .await?;
     trans.commit().await?;
+    // anyone who stole a session shouldn't keep it
+    let current = cookies.get(AUTHTOKEN_COOKIE).map(Cookie::value);
+    Session::end_others(&state, user.id, current).await?;
     Ok(state.redirect("/settings"))
 }
 
 pub async fn update_email(
     State(state): State<AppState>,
+    cookies: CookieJar,
     ValidatedForm(form): ValidatedForm<UpdateEmailForm>,
 ) -> Result<Redirect, Error> {
     let mut trans = state.postgres.begin().await?;
@@ -54,12 +64,30 @@ pub async fn update_email(
         return Ok(state.redirect("/settings?incorrect=true"));
     };
     query!(
-        "UPDATE users SET email = $2 WHERE id = $1",
+        "UPDATE users SET email = $2, email_verified = false WHERE id = $1",
         user.id.get(),
         form.new_email
     )
     .execute(trans.as_mut())
     .await?;
     trans.commit().await?;
+    let current = cookies.get(AUTHTOKEN_COOKIE).map(Cookie::value);
+    Session::end_others(&state, user.id, current).await?;
+    EmailVerification::new(user.id, form.new_email).send_in_background(&state, user);
     Ok(state.redirect("/settings"))
 }
+
+/// Send another verification link to the user's current address
+pub async fn resend_verification(
+    State(state): State<AppState>,
+    user: User,
+) -> Result<Redirect, Error> {
+    let email = query!("SELECT email FROM users WHERE id = $1", user.id.get())
+        .fetch_one(&state.postgres)
+        .await?
+        .email;
+    EmailVerification::new(user.id, email)
+        .send(&state, &user)
+        .await?;
+    Ok(state.redirect("/settings?verification_sent=true"))
+}
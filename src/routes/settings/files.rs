@@ -153,7 +153,7 @@ fn encode_webp(image_data: &DynamicImage) -> Vec<u8> {
     encoder.encode(80.0).to_vec()
 }
 
-async fn multipart_into_bytes(
+pub async fn multipart_into_bytes(
     mut multipart: Multipart,
     target_name: &str,
 ) -> Result<(String, Bytes), Error> {
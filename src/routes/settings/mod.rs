@@ -1,18 +1,22 @@
 pub mod credentials;
 pub mod files;
+pub mod sessions;
+pub mod two_factor;
 
 use axum::{
     extract::{Query, State},
     response::Redirect,
 };
+use axum_extra::extract::{cookie::Cookie, CookieJar};
 use strum::IntoEnumIterator;
 
 use crate::{
     id::Id,
     language::Language,
-    model::{User, UserUpdate},
+    model::{LinkedAccount, Session, SessionInfo, TwoFactor, User, UserUpdate},
+    oauth::ProviderInfo,
     template::BaseRenderInfo,
-    util::ValidatedForm,
+    util::{ValidatedForm, AUTHTOKEN_COOKIE},
     AppState, Error, HandlerResult,
 };
 
@@ -22,6 +26,13 @@ pub struct SettingsPage {
     base: BaseRenderInfo,
     user: PrivateUser,
     incorrect: bool,
+    verified: bool,
+    verification_sent: bool,
+    two_factor: bool,
+    recovery_codes_left: i64,
+    sessions: Vec<SessionInfo>,
+    linked_accounts: Vec<LinkedAccount>,
+    oauth_providers: Vec<ProviderInfo>,
     languages: Vec<LanguageMetadata>,
     custom_styles_disabled: bool,
     js_url: String,
@@ -37,6 +48,10 @@ pub struct LanguageMetadata {
 pub struct SettingsQuery {
     #[serde(default = "crate::util::return_false")]
     incorrect: bool,
+    #[serde(default = "crate::util::return_false")]
+    verified: bool,
+    #[serde(default = "crate::util::return_false")]
+    verification_sent: bool,
 }
 
 #[derive(serde::Serialize, Debug, Clone)]
@@ -44,6 +59,7 @@ pub struct PrivateUser {
     #[serde(flatten)]
     base: User,
     email: String,
+    email_verified: bool,
 }
 
 #[derive(serde::Deserialize, garde::Validate, Clone, Debug)]
@@ -75,12 +91,13 @@ pub async fn get(
     State(state): State<AppState>,
     user: User,
     base: BaseRenderInfo,
+    cookies: CookieJar,
     Query(query): Query<SettingsQuery>,
 ) -> HandlerResult {
     let record = query!(
         "SELECT
         id, username, stylesheet, pfp, banner,
-        biography, email, admin, created_at, flags, language
+        biography, email, email_verified, admin, created_at, flags, language
         FROM users WHERE id = $1",
         user.id.get()
     )
@@ -105,10 +122,23 @@ pub async fn get(
     let private_user = PrivateUser {
         base: base_user,
         email: record.email,
+        email_verified: record.email_verified,
     };
     let ctx = SettingsPage {
         base,
         incorrect: query.incorrect,
+        verified: query.verified,
+        verification_sent: query.verification_sent,
+        two_factor: TwoFactor::is_enabled(&state, user.id).await?,
+        recovery_codes_left: TwoFactor::recovery_codes_left(&state, user.id).await?,
+        sessions: Session::list(
+            &state,
+            user.id,
+            cookies.get(AUTHTOKEN_COOKIE).map(Cookie::value),
+        )
+        .await?,
+        linked_accounts: LinkedAccount::from_db_user(&state, user.id).await?,
+        oauth_providers: crate::oauth::provider_infos(&state.config.oauth_providers),
         user: private_user,
         languages: Language::iter()
             .map(|lang| LanguageMetadata {
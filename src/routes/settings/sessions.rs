@@ -0,0 +1,44 @@
+use axum::{extract::State, response::Redirect};
+use axum_extra::extract::{cookie::Cookie, CookieJar};
+
+use crate::{
+    model::{Session, User},
+    util::{ValidatedForm, AUTHTOKEN_COOKIE},
+    AppState, Error,
+};
+
+#[derive(serde::Deserialize, garde::Validate, Clone, Debug)]
+pub struct RevokeSessionForm {
+    #[garde(length(max = 64))]
+    session: String,
+}
+
+pub async fn revoke(
+    State(state): State<AppState>,
+    user: User,
+    cookies: CookieJar,
+    ValidatedForm(form): ValidatedForm<RevokeSessionForm>,
+) -> Result<(CookieJar, Redirect), Error> {
+    Session::revoke(&state, user.id, &form.session).await?;
+    let current = cookies
+        .get(AUTHTOKEN_COOKIE)
+        .map(|cookie| Session::public_id(cookie.value()));
+    if current.as_deref() == Some(form.session.as_str()) {
+        return Ok((
+            cookies.remove(Cookie::build(AUTHTOKEN_COOKIE)),
+            state.redirect("/"),
+        ));
+    }
+    Ok((cookies, state.redirect("/settings")))
+}
+
+/// Log out everywhere except here
+pub async fn revoke_others(
+    State(state): State<AppState>,
+    user: User,
+    cookies: CookieJar,
+) -> Result<Redirect, Error> {
+    let current = cookies.get(AUTHTOKEN_COOKIE).map(Cookie::value);
+    Session::end_others(&state, user.id, current).await?;
+    Ok(state.redirect("/settings"))
+}
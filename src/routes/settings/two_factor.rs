@@ -0,0 +1,112 @@
+use std::fmt::Write;
+
+use axum::{extract::State, response::Redirect};
+use qrcodegen::{QrCode, QrCodeEcc};
+
+use crate::{
+    model::{Enrolment, TwoFactor, User},
+    template::BaseRenderInfo,
+    util::ValidatedForm,
+    AppState, Error, HandlerResult,
+};
+
+#[derive(serde::Serialize, Debug, Clone)]
+pub struct TwoFactorSetupPage {
+    #[serde(flatten)]
+    base: BaseRenderInfo,
+    enrolment: Enrolment,
+    /// The enrolment URL as an inline SVG, if it fits in a QR code
+    qr_code: Option<String>,
+}
+
+#[derive(serde::Serialize, Debug, Clone)]
+pub struct RecoveryCodesPage {
+    #[serde(flatten)]
+    base: BaseRenderInfo,
+    codes: Vec<String>,
+}
+
+#[derive(serde::Deserialize, garde::Validate, Clone, Debug)]
+pub struct TwoFactorCodeForm {
+    #[garde(length(max = 64))]
+    code: String,
+}
+
+pub async fn setup(
+    State(state): State<AppState>,
+    user: User,
+    base: BaseRenderInfo,
+) -> Result<Result<Redirect, HandlerResult>, Error> {
+    if TwoFactor::is_enabled(&state, user.id).await? {
+        return Ok(Ok(state.redirect("/settings")));
+    }
+    let enrolment = TwoFactor::enrol(&state, &user).await?;
+    let ctx = TwoFactorSetupPage {
+        base,
+        qr_code: qr_svg(&enrolment.url),
+        enrolment,
+    };
+    Ok(Err(state.render("two_factor_setup.jinja", ctx)))
+}
+
+pub async fn enable(
+    State(state): State<AppState>,
+    user: User,
+    base: BaseRenderInfo,
+    ValidatedForm(form): ValidatedForm<TwoFactorCodeForm>,
+) -> HandlerResult {
+    let codes = TwoFactor::confirm(&state, user.id, &form.code).await?;
+    let ctx = RecoveryCodesPage { base, codes };
+    state.render("two_factor_recovery.jinja", ctx)
+}
+
+pub async fn disable(
+    State(state): State<AppState>,
+    user: User,
+    ValidatedForm(form): ValidatedForm<TwoFactorCodeForm>,
+) -> Result<Redirect, Error> {
+    check_code(&state, &user, &form.code).await?;
+    TwoFactor::disable(&state, user.id).await?;
+    Ok(state.redirect("/settings"))
+}
+
+pub async fn recovery_codes(
+    State(state): State<AppState>,
+    user: User,
+    base: BaseRenderInfo,
+    ValidatedForm(form): ValidatedForm<TwoFactorCodeForm>,
+) -> HandlerResult {
+    check_code(&state, &user, &form.code).await?;
+    let codes = TwoFactor::regenerate_recovery_codes(&state, user.id).await?;
+    let ctx = RecoveryCodesPage { base, codes };
+    state.render("two_factor_recovery.jinja", ctx)
+}
+
+async fn check_code(state: &AppState, user: &User, code: &str) -> Result<(), Error> {
+    let two_factor = TwoFactor::from_db(state, user.id)
+        .await?
+        .ok_or(Error::InvalidTwoFactorCode)?;
+    if two_factor.verify(state, code).await? {
+        Ok(())
+    } else {
+        Err(Error::InvalidTwoFactorCode)
+    }
+}
+
+/// Draw `text` as a QR code, with one SVG unit per module and the standard 4 module border
+fn qr_svg(text: &str) -> Option<String> {
+    const BORDER: i32 = 4;
+    let qr = QrCode::encode_text(text, QrCodeEcc::Medium).ok()?;
+    let size = qr.size() + BORDER * 2;
+    let mut path = String::new();
+    for y in 0..qr.size() {
+        for x in 0..qr.size() {
+            if qr.get_module(x, y) {
+                write!(path, "M{},{}h1v1h-1z", x + BORDER, y + BORDER).ok()?;
+            }
+        }
+    }
+    Some(format!(
+        r##"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 {size} {size}" width="256" height="256" shape-rendering="crispEdges"><rect width="100%" height="100%" fill="#ffffff"/><path d="{path}" fill="#000000"/></svg>"##
+    ))
+}
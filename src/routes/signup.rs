@@ -3,15 +3,13 @@ use axum::{
     response::Redirect,
 };
 use axum_extra::extract::CookieJar;
-use rand::distributions::DistString;
-use redis::AsyncCommands;
 
 use crate::{
     id::Id,
     language::Language,
-    model::User,
+    model::{ClientInfo, EmailVerification, Session, User},
     template::BaseRenderInfo,
-    util::{auth_cookie, ValidatedForm, AUTHTOKEN_TTL},
+    util::{auth_cookie, ValidatedForm},
     AppState, Error, HandlerResult,
 };
 
@@ -54,6 +52,7 @@ pub async fn get(
 pub async fn post(
     State(state): State<AppState>,
     cookies: CookieJar,
+    client: ClientInfo,
     ValidatedForm(form): ValidatedForm<SignUpForm>,
 ) -> Result<(CookieJar, Redirect), Error> {
     let password_hash_res = state
@@ -71,7 +70,7 @@ pub async fn post(
         RETURNING id, username, stylesheet, pfp, banner,
         biography, admin, created_at, flags, language",
         form.username,
-        form.email,
+        &form.email,
         password_hash.to_string()
     )
     .fetch_one(&state.postgres)
@@ -91,22 +90,7 @@ pub async fn post(
             .map(|v| Language::from_lang_code(&v))
             .unwrap_or_default(),
     };
-    let token = rand::distributions::Alphanumeric.sample_string(&mut rand::thread_rng(), 64);
-    state
-        .redis
-        .get()
-        .await?
-        .set_ex(format!("token:user:{token}"), user.id.get(), AUTHTOKEN_TTL)
-        .await?;
-    state
-        .redis
-        .get()
-        .await?
-        .set_ex(
-            format!("user:{}", user.id),
-            serde_json::to_string(&user)?,
-            AUTHTOKEN_TTL,
-        )
-        .await?;
+    EmailVerification::new(user.id, form.email).send_in_background(&state, user.clone());
+    let token = Session::start(&state, &user, &client).await?;
     Ok((cookies.add(auth_cookie(token)), state.redirect("/")))
 }
@@ -1,14 +1,43 @@
 use axum::extract::{Path, State};
 
-use crate::{id::Id, model::User, template::BaseRenderInfo, AppState, Error, HandlerResult};
+use crate::{
+    id::{CategoryMarker, Id, RunMarker},
+    model::{RankedRun, ResolvedRun, RunStatus, User},
+    template::BaseRenderInfo,
+    AppState, Error, HandlerResult,
+};
 
 #[derive(serde::Serialize, Debug, Clone)]
 pub struct UserPage {
     #[serde(flatten)]
     base: BaseRenderInfo,
     user: User,
+    /// Current personal bests this user submitted or took part in, in every game
+    personal_bests: Vec<RankedRun>,
+    /// Only filled in when users look at their own profile
+    pending_runs: Vec<ResolvedRun>,
+    /// Only filled in when users look at their own profile
+    rejected_runs: Vec<ResolvedRun>,
+    /// Only filled in when users look at their own profile
+    reviewed_runs: Vec<ReviewedRun>,
 }
 
+/// One of a user's runs which a verifier left a note on
+#[derive(serde::Serialize, Debug, Clone)]
+pub struct ReviewedRun {
+    id: Id<RunMarker>,
+    game_slug: String,
+    game_name: String,
+    category: Id<CategoryMarker>,
+    category_name: String,
+    status: RunStatus,
+    review_note: String,
+}
+
+const MAX_REVIEWED_RUNS: i64 = 50;
+const MAX_PERSONAL_BESTS: usize = 500;
+const MAX_SUBMISSIONS: usize = 50;
+
 #[allow(clippy::unused_async)]
 pub async fn get(
     State(state): State<AppState>,
@@ -37,6 +66,70 @@ pub async fn get(
         flags: row.flags,
         language: None,
     };
-    let ctx = UserPage { base, user };
+    let is_own_profile = base
+        .logged_in_user
+        .as_ref()
+        .is_some_and(|logged_in| logged_in.id == user.id);
+    let (pending_runs, rejected_runs, reviewed_runs) = if is_own_profile {
+        (
+            ResolvedRun::fetch_runner_submissions(
+                &state,
+                user.id,
+                RunStatus::Pending,
+                MAX_SUBMISSIONS,
+            )
+            .await?,
+            ResolvedRun::fetch_runner_submissions(
+                &state,
+                user.id,
+                RunStatus::Rejected,
+                MAX_SUBMISSIONS,
+            )
+            .await?,
+            get_reviewed_runs(&state, user.id.get()).await?,
+        )
+    } else {
+        (Vec::new(), Vec::new(), Vec::new())
+    };
+    let personal_bests =
+        ResolvedRun::fetch_personal_bests(&state, user.id, MAX_PERSONAL_BESTS).await?;
+    let ctx = UserPage {
+        base,
+        user,
+        personal_bests,
+        pending_runs,
+        rejected_runs,
+        reviewed_runs,
+    };
     state.render("user.jinja", ctx)
 }
+
+async fn get_reviewed_runs(state: &AppState, user_id: i64) -> Result<Vec<ReviewedRun>, Error> {
+    let runs = query!(
+        r#"SELECT runs.id, games.slug, games.name AS game_name,
+        runs.category, categories.name AS category_name,
+        runs.status, runs.review_note AS "review_note!"
+        FROM runs
+        JOIN games ON runs.game = games.id
+        JOIN categories ON runs.category = categories.id
+        WHERE runs.submitter = $1 AND runs.review_note IS NOT NULL
+        ORDER BY runs.verified_at DESC
+        LIMIT $2"#,
+        user_id,
+        MAX_REVIEWED_RUNS
+    )
+    .fetch_all(&state.postgres)
+    .await?
+    .into_iter()
+    .map(|row| ReviewedRun {
+        id: Id::new(row.id),
+        game_slug: row.slug,
+        game_name: row.game_name,
+        category: Id::new(row.category),
+        category_name: row.category_name,
+        status: row.status.into(),
+        review_note: row.review_note,
+    })
+    .collect();
+    Ok(runs)
+}
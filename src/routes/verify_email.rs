@@ -0,0 +1,36 @@
+use axum::{
+    extract::{Query, State},
+    response::Redirect,
+};
+
+use crate::{
+    id::{Id, UserMarker},
+    model::EmailVerification,
+    AppState, Error,
+};
+
+#[derive(serde::Deserialize, Debug, Clone)]
+pub struct VerifyEmailQuery {
+    user: Id<UserMarker>,
+    email: String,
+    expires: u64,
+    nonce: String,
+    signature: String,
+}
+
+pub async fn get(
+    State(state): State<AppState>,
+    Query(query): Query<VerifyEmailQuery>,
+) -> Result<Redirect, Error> {
+    let verification = EmailVerification {
+        user: query.user,
+        email: query.email,
+        expires: query.expires,
+        nonce: query.nonce,
+    };
+    if !verification.is_valid(&state.config.secret_key, &query.signature) {
+        return Err(Error::InvalidVerificationLink);
+    }
+    verification.confirm(&state).await?;
+    Ok(state.redirect("/settings?verified=true"))
+}
@@ -20,7 +20,7 @@ use sqlx::{postgres::PgPoolOptions, PgPool};
 use tera::Tera;
 use url::Url;
 
-use crate::{config::Config, Error};
+use crate::{config::Config, mail::Mailer, Error};
 
 #[derive(Clone)]
 pub struct AppState {
@@ -34,6 +34,7 @@ pub struct AppState {
     pub static_hashes: Arc<ArcSwap<HashMap<String, String>>>,
     bucket: Arc<s3::Bucket>,
     csp: Arc<HeaderValue>,
+    pub mailer: Arc<Mailer>,
 }
 
 impl AppState {
@@ -51,6 +52,7 @@ impl AppState {
         static_hashes: Arc<ArcSwap<HashMap<String, String>>>,
         bucket: Arc<s3::Bucket>,
         csp: Arc<HeaderValue>,
+        mailer: Arc<Mailer>,
     ) -> Self {
         Self {
             config,
@@ -63,6 +65,7 @@ impl AppState {
             static_hashes,
             bucket,
             csp,
+            mailer,
         }
     }
 
@@ -297,10 +300,11 @@ impl AppState {
         let csp = Arc::new(
             HeaderValue::from_str(&format!(
                 "default-src {0} {1}; script-src {0}/static/page-scripts/; \
-                frame-src https://youtube.com https://clips.twitch.tv {1}; \
+                frame-src {2} {1}; \
                  object-src 'none'; require-trusted-types-for 'script';",
                 Self::url_to_origin(&config.root_url),
                 Self::url_to_origin(&config.user_content_url),
+                crate::template::frame_sources(&config.video_providers),
             ))
             .expect("Invalid csp header value (check your USER_CONTENT_URL)"),
         );
@@ -337,6 +341,10 @@ impl AppState {
             .build()
             .unwrap();
         let bucket = Arc::new(Self::get_s3_bucket_from_config(&config).await);
+        let mailer = match Mailer::from_config(&config) {
+            Ok(mailer) => Arc::new(mailer),
+            Err(error) => panic!("Failed to set up mail (check MAIL_TRANSPORT): {error}"),
+        };
         let tera = Arc::new(RwLock::new(Tera::default()));
         let temp_state = AppState::new(
             config,
@@ -349,6 +357,7 @@ impl AppState {
             static_hashes,
             bucket,
             csp,
+            mailer,
         );
         *temp_state.clone().tera.write() = crate::template::tera(temp_state.clone());
         temp_state
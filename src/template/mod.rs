@@ -1,16 +1,21 @@
 mod linkify;
 mod translate;
+mod video;
 
 use std::{collections::HashMap, fmt::Write};
 
 use axum::{extract::FromRequestParts, http::request::Parts};
 use tera::{Tera, Value};
 pub use translate::{get_translations, GetTranslation};
+pub use video::{frame_sources, VideoProvider};
 
 use crate::{
     language::Language,
-    model::User,
-    template::linkify::{CategoryLinks, ForumPostLinks, GameLinks, GetLinks, RunLinks, UserLinks},
+    model::{format_score, TimingMethod, TimingMethods, User},
+    template::{
+        linkify::{CategoryLinks, ForumPostLinks, GameLinks, GetLinks, RunLinks, UserLinks},
+        video::VideoEmbedder,
+    },
     AppState, Error,
 };
 
@@ -35,7 +40,11 @@ pub fn tera(state: AppState) -> Tera {
     tera.register_filter("markdown", MarkdownFilter);
     tera.register_filter("long_format_duration", HumanizeDuration);
     tera.register_filter("duration", Duration);
-    tera.register_filter("video_embed", VideoEmbedder);
+    tera.register_filter("score", Score);
+    tera.register_filter(
+        "video_embed",
+        VideoEmbedder::new(state.config.video_providers.clone(), &state.config.root_url),
+    );
     tera.register_function("devmode", DevModeFunction);
     tera.register_function("gettrans", GetTranslation::new(translations));
     tera.register_function("getuserlinks", GetLinks::<UserLinks>::new(state.clone()));
@@ -127,75 +136,6 @@ impl tera::Filter for MarkdownFilter {
     }
 }
 
-#[derive(Debug, Clone, Copy)]
-struct VideoEmbedder;
-
-impl tera::Filter for VideoEmbedder {
-    #[tracing::instrument(name = "embed_video", level = tracing::Level::TRACE, skip(_args))]
-    fn filter(
-        &self,
-        value: &Value,
-        _args: &std::collections::HashMap<String, Value>,
-    ) -> tera::Result<Value> {
-        let Value::String(input) = value else {
-            return Ok(value.clone());
-        };
-        let sanitized_link = tera::escape_html(input);
-        trace!(sanitized_link, input, "sanitized link");
-        let data = convert_link(input).unwrap_or_else(|_| simple_a(&sanitized_link));
-        trace!(data, input, "converted to clickable");
-        Ok(Value::String(data))
-    }
-
-    fn is_safe(&self) -> bool {
-        true
-    }
-}
-
-#[tracing::instrument(name = "convert_link", level = tracing::Level::TRACE)]
-fn convert_link(input: &str) -> Result<String, Error> {
-    let url = url::Url::parse(input)?;
-    trace!(?url, input, "parsed URL");
-    let Some(domain) = url.domain().map(str::to_ascii_lowercase) else {
-        return Err(Error::NoDomainInUrl);
-    };
-    match domain.as_str() {
-        "youtube.com" | "www.youtube.com" => {
-            trace!(?url, "converting YouTube.com URL");
-            let mut query = url.query_pairs();
-            let maybe_id = query.find_map(|v| if v.0 == "v" { Some(v.1) } else { None });
-            if let Some(id) = maybe_id {
-                Ok(get_yt_embed_iframe(&id))
-            } else {
-                Ok(simple_a(url.as_str()))
-            }
-        }
-        "youtu.be" | "www.youtu.be" => {
-            trace!(?url, "converting YouTu.be URL");
-            if let Some(Some(id)) = url.path_segments().map(|mut v| v.next()) {
-                Ok(get_yt_embed_iframe(id))
-            } else {
-                Ok(simple_a(url.as_str()))
-            }
-        }
-        _ => Ok(simple_a(url.as_str())),
-    }
-}
-
-fn simple_a(safe_link: &str) -> String {
-    format!(r#"<a href="{safe_link}" target="_blank" rel="noopener noreferrer">{safe_link}</a>"#)
-}
-
-fn get_yt_embed_iframe(dangerous_video_id: &str) -> String {
-    let clean_data = tera::escape_html(dangerous_video_id);
-
-    format!(
-        r#"<iframe width="560" height="315" src="https://www.youtube.com/embed/{clean_data}"
-            allow="accelerometer; autoplay; encrypted-media; gyroscope; picture-in-picture"
-            frameborder="0" allowfullscreen></iframe>"#
-    )
-}
-
 struct HumanizeDuration;
 
 impl tera::Filter for HumanizeDuration {
@@ -218,12 +158,49 @@ struct Duration;
 
 impl tera::Filter for Duration {
     fn filter(&self, value: &Value, _args: &HashMap<String, Value>) -> tera::Result<Value> {
+        // a whole run gets its category's primary time, with its other times after it
+        if value.is_object() {
+            return run_durations(value).map(Value::String);
+        }
         let total_time = value
             .as_u64()
             .ok_or_else(|| tera::Error::msg("Display duration was not a real number"))?;
-        let (days, hours, minutes, seconds, milliseconds) = millis_to_ddhhmmssms(total_time);
-        let output = millis_to_sr_string(days, hours, minutes, seconds, milliseconds)
-            .map_err(|v| tera::Error::msg(format!("Failed formatting string: {v:?}")))?;
+        Ok(Value::String(sr_duration(total_time)?))
+    }
+
+    fn is_safe(&self) -> bool {
+        false
+    }
+}
+
+/// Formats a score with its category's decimal places and unit. Takes either a
+/// whole run, or a score along with `category`. Pass `unit=false` to leave off the unit.
+struct Score;
+
+impl tera::Filter for Score {
+    fn filter(&self, value: &Value, args: &HashMap<String, Value>) -> tera::Result<Value> {
+        let (score, category) = if value.is_object() {
+            (&value["score"], &value["category"])
+        } else {
+            let category = args
+                .get("category")
+                .ok_or_else(|| tera::Error::msg("Score needs the category it belongs to"))?;
+            (value, category)
+        };
+        let score = score
+            .as_i64()
+            .ok_or_else(|| tera::Error::msg("Score was not a whole number"))?;
+        let precision = category["score_precision"]
+            .as_i64()
+            .and_then(|precision| i16::try_from(precision).ok())
+            .unwrap_or(0);
+        let mut output = format_score(score, precision);
+        let unit = category["score_unit"].as_str().unwrap_or_default();
+        let with_unit = args.get("unit").and_then(Value::as_bool).unwrap_or(true);
+        if with_unit && !unit.is_empty() {
+            output.push(' ');
+            output.push_str(unit);
+        }
         Ok(Value::String(output))
     }
 
@@ -232,6 +209,40 @@ impl tera::Filter for Duration {
     }
 }
 
+fn sr_duration(total_time: u64) -> tera::Result<String> {
+    let (days, hours, minutes, seconds, milliseconds) = millis_to_ddhhmmssms(total_time);
+    millis_to_sr_string(days, hours, minutes, seconds, milliseconds)
+        .map_err(|v| tera::Error::msg(format!("Failed formatting string: {v:?}")))
+}
+
+/// Format a serialized [`ResolvedRun`](crate::model::ResolvedRun) as its primary time,
+/// followed by every other recorded time, like `1:02.000 (IGT 58.000, LRT 1:00.000)`
+fn run_durations(run: &Value) -> tera::Result<String> {
+    let category = &run["category"];
+    let primary: TimingMethod = serde_json::from_value(category["primary_timing"].clone())
+        .map_err(|_| tera::Error::msg("Run passed to duration had no primary timing method"))?;
+    let methods: TimingMethods = serde_json::from_value(category["timing_methods"].clone())
+        .map_err(|_| tera::Error::msg("Run passed to duration had no timing methods"))?;
+    let time_for = |method: TimingMethod| run[method.column()].as_u64().unwrap_or(0);
+    let mut output = sr_duration(time_for(primary))?;
+    let others: Vec<String> = methods
+        .methods()
+        .filter(|method| *method != primary && time_for(*method) != 0)
+        .map(|method| {
+            Ok(format!(
+                "{} {}",
+                method.abbreviation(),
+                sr_duration(time_for(method))?
+            ))
+        })
+        .collect::<tera::Result<_>>()?;
+    if !others.is_empty() {
+        write!(output, " ({})", others.join(", "))
+            .map_err(|v| tera::Error::msg(format!("Failed formatting string: {v:?}")))?;
+    }
+    Ok(output)
+}
+
 fn millis_to_long_string(
     days: u64,
     hours: u64,
@@ -0,0 +1,314 @@
+use std::collections::HashMap;
+
+use tera::Value;
+use url::Url;
+
+use crate::Error;
+
+/// A video host whose links are embedded as players
+#[derive(serde::Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum VideoProvider {
+    YouTube,
+    Twitch,
+    Bilibili,
+    Nicovideo,
+    Streamable,
+}
+
+impl VideoProvider {
+    pub const ALL: [Self; 5] = [
+        Self::YouTube,
+        Self::Twitch,
+        Self::Bilibili,
+        Self::Nicovideo,
+        Self::Streamable,
+    ];
+
+    /// Origins this provider's players are served from, which the CSP allows as frames
+    pub fn frame_origins(self) -> &'static [&'static str] {
+        match self {
+            Self::YouTube => &["https://www.youtube.com"],
+            Self::Twitch => &["https://player.twitch.tv", "https://clips.twitch.tv"],
+            Self::Bilibili => &["https://player.bilibili.com"],
+            Self::Nicovideo => &["https://embed.nicovideo.jp"],
+            Self::Streamable => &["https://streamable.com"],
+        }
+    }
+
+    /// The player for `url`, if it links to a video on this provider. Twitch only plays
+    /// on the site named by `parent`.
+    fn embed_url(self, url: &Url, parent: &str) -> Option<String> {
+        let host = url.domain()?;
+        let host = host
+            .strip_prefix("www.")
+            .or_else(|| host.strip_prefix("m."))
+            .unwrap_or(host);
+        let segments: Vec<&str> = url
+            .path_segments()?
+            .filter(|segment| !segment.is_empty())
+            .collect();
+        match (self, host, segments.as_slice()) {
+            (Self::YouTube, "youtube.com", ["watch"]) => {
+                let id = query_param(url, "v")?;
+                youtube_embed(safe_id(&id)?, url)
+            }
+            (Self::YouTube, "youtube.com", ["shorts" | "live" | "embed", id])
+            | (Self::YouTube, "youtu.be", [id]) => youtube_embed(safe_id(id)?, url),
+            (Self::Twitch, "twitch.tv", ["videos", id]) => {
+                let id = safe_id(id)?;
+                let time = query_param(url, "t")
+                    .and_then(|t| parse_timestamp(&t))
+                    .map(|seconds| format!("&time={}", twitch_timestamp(seconds)))
+                    .unwrap_or_default();
+                Some(format!(
+                    "https://player.twitch.tv/?video={id}&parent={parent}&autoplay=false{time}"
+                ))
+            }
+            (Self::Twitch, "twitch.tv", [_, "clip", slug])
+            | (Self::Twitch, "clips.twitch.tv", [slug]) => Some(format!(
+                "https://clips.twitch.tv/embed?clip={}&parent={parent}&autoplay=false",
+                safe_id(slug)?
+            )),
+            (Self::Bilibili, "bilibili.com", ["video", id]) => {
+                let id = safe_id(id)?;
+                let video = match id.strip_prefix("av") {
+                    Some(aid) if aid.bytes().all(|byte| byte.is_ascii_digit()) => {
+                        format!("aid={aid}")
+                    }
+                    _ => format!("bvid={id}"),
+                };
+                let page = query_param(url, "p")
+                    .and_then(|page| page.parse::<u32>().ok())
+                    .map(|page| format!("&page={page}"))
+                    .unwrap_or_default();
+                Some(format!(
+                    "https://player.bilibili.com/player.html?{video}{page}&autoplay=0"
+                ))
+            }
+            (Self::Nicovideo, "nicovideo.jp", ["watch", id])
+            | (Self::Nicovideo, "nico.ms", [id]) => {
+                let from = query_param(url, "from")
+                    .and_then(|from| parse_timestamp(&from))
+                    .map(|seconds| format!("?from={seconds}"))
+                    .unwrap_or_default();
+                Some(format!(
+                    "https://embed.nicovideo.jp/watch/{}{from}",
+                    safe_id(id)?
+                ))
+            }
+            (Self::Streamable, "streamable.com", [id] | ["e", id]) => {
+                Some(format!("https://streamable.com/e/{}", safe_id(id)?))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// The `frame-src` sources of the CSP, for the players of `providers`
+pub fn frame_sources(providers: &[VideoProvider]) -> String {
+    providers
+        .iter()
+        .flat_map(|provider| provider.frame_origins())
+        .copied()
+        .collect::<Vec<&str>>()
+        .join(" ")
+}
+
+#[derive(Debug, Clone)]
+pub struct VideoEmbedder {
+    providers: Vec<VideoProvider>,
+    /// The host of the site, which some players need to be told about
+    parent: String,
+}
+
+impl VideoEmbedder {
+    pub fn new(providers: Vec<VideoProvider>, root_url: &str) -> Self {
+        let parent = Url::parse(root_url)
+            .ok()
+            .and_then(|url| url.host_str().map(str::to_string))
+            .unwrap_or_default();
+        Self { providers, parent }
+    }
+
+    #[tracing::instrument(name = "convert_link", level = tracing::Level::TRACE, skip(self))]
+    fn convert_link(&self, input: &str) -> Result<String, Error> {
+        let url = Url::parse(input)?;
+        trace!(?url, input, "parsed URL");
+        if url.domain().is_none() {
+            return Err(Error::NoDomainInUrl);
+        }
+        let embed = self
+            .providers
+            .iter()
+            .find_map(|provider| provider.embed_url(&url, &self.parent));
+        trace!(?embed, input, "found embed URL");
+        Ok(embed.map_or_else(
+            || simple_a(&tera::escape_html(url.as_str())),
+            |embed| iframe(&embed),
+        ))
+    }
+}
+
+impl tera::Filter for VideoEmbedder {
+    #[tracing::instrument(name = "embed_video", level = tracing::Level::TRACE, skip(self, _args))]
+    fn filter(&self, value: &Value, _args: &HashMap<String, Value>) -> tera::Result<Value> {
+        let Value::String(input) = value else {
+            return Ok(value.clone());
+        };
+        let sanitized_link = tera::escape_html(input);
+        trace!(sanitized_link, input, "sanitized link");
+        let data = self
+            .convert_link(input)
+            .unwrap_or_else(|_| simple_a(&sanitized_link));
+        trace!(data, input, "converted to clickable");
+        Ok(Value::String(data))
+    }
+
+    fn is_safe(&self) -> bool {
+        true
+    }
+}
+
+fn simple_a(safe_link: &str) -> String {
+    format!(r#"<a href="{safe_link}" target="_blank" rel="noopener noreferrer">{safe_link}</a>"#)
+}
+
+fn iframe(embed_url: &str) -> String {
+    let clean_url = tera::escape_html(embed_url);
+    format!(
+        r#"<iframe width="560" height="315" src="{clean_url}"
+            allow="accelerometer; autoplay; encrypted-media; gyroscope; picture-in-picture"
+            sandbox="allow-scripts allow-same-origin allow-presentation allow-popups"
+            referrerpolicy="strict-origin-when-cross-origin"
+            frameborder="0" allowfullscreen></iframe>"#
+    )
+}
+
+fn youtube_embed(id: &str, url: &Url) -> Option<String> {
+    let start = query_param(url, "t")
+        .or_else(|| query_param(url, "start"))
+        .and_then(|t| parse_timestamp(&t))
+        .map(|seconds| format!("?start={seconds}"))
+        .unwrap_or_default();
+    Some(format!("https://www.youtube.com/embed/{id}{start}"))
+}
+
+fn query_param(url: &Url, name: &str) -> Option<String> {
+    url.query_pairs()
+        .find_map(|(key, value)| (key == name).then(|| value.into_owned()))
+}
+
+/// Video ids go straight into player URLs, so only plain ones are accepted
+fn safe_id(id: &str) -> Option<&str> {
+    let plain = id
+        .bytes()
+        .all(|byte| byte.is_ascii_alphanumeric() || byte == b'-' || byte == b'_');
+    (plain && !id.is_empty() && id.len() <= 64).then_some(id)
+}
+
+/// Parse a timestamp like `90`, `90s` or `1h2m3s` into seconds
+fn parse_timestamp(input: &str) -> Option<u64> {
+    if let Ok(seconds) = input.parse() {
+        return Some(seconds);
+    }
+    let mut total: u64 = 0;
+    let mut number = String::new();
+    for character in input.chars() {
+        if character.is_ascii_digit() {
+            number.push(character);
+            continue;
+        }
+        let unit = match character {
+            'h' => 60 * 60,
+            'm' => 60,
+            's' => 1,
+            _ => return None,
+        };
+        let value: u64 = number.parse().ok()?;
+        total = total.checked_add(value.checked_mul(unit)?)?;
+        number.clear();
+    }
+    number.is_empty().then_some(total)
+}
+
+fn twitch_timestamp(seconds: u64) -> String {
+    format!(
+        "{}h{}m{}s",
+        seconds / (60 * 60),
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn embed(input: &str) -> Option<String> {
+        let url = Url::parse(input).unwrap();
+        VideoProvider::ALL
+            .into_iter()
+            .find_map(|provider| provider.embed_url(&url, "localhost"))
+    }
+
+    #[test]
+    fn embed_urls() {
+        let cases = [
+            (
+                "https://www.youtube.com/watch?v=vOLivyykLqk&t=1m30s",
+                "https://www.youtube.com/embed/vOLivyykLqk?start=90",
+            ),
+            (
+                "https://youtu.be/vOLivyykLqk?t=42",
+                "https://www.youtube.com/embed/vOLivyykLqk?start=42",
+            ),
+            (
+                "https://youtube.com/shorts/vOLivyykLqk",
+                "https://www.youtube.com/embed/vOLivyykLqk",
+            ),
+            (
+                "https://www.twitch.tv/videos/123456?t=1h2m3s",
+                "https://player.twitch.tv/?video=123456&parent=localhost&autoplay=false&time=1h2m3s",
+            ),
+            (
+                "https://www.twitch.tv/someone/clip/FunnyClip-abc_123",
+                "https://clips.twitch.tv/embed?clip=FunnyClip-abc_123&parent=localhost&autoplay=false",
+            ),
+            (
+                "https://www.bilibili.com/video/BV1xx411c7mD?p=2",
+                "https://player.bilibili.com/player.html?bvid=BV1xx411c7mD&page=2&autoplay=0",
+            ),
+            (
+                "https://www.bilibili.com/video/av170001/",
+                "https://player.bilibili.com/player.html?aid=170001&autoplay=0",
+            ),
+            (
+                "https://www.nicovideo.jp/watch/sm9?from=30",
+                "https://embed.nicovideo.jp/watch/sm9?from=30",
+            ),
+            ("https://streamable.com/abc12", "https://streamable.com/e/abc12"),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(embed(input).as_deref(), Some(expected), "{input}");
+        }
+    }
+
+    #[test]
+    fn unsafe_or_unknown_links() {
+        assert_eq!(embed("https://example.com/watch?v=vOLivyykLqk"), None);
+        assert_eq!(embed("https://www.youtube.com/watch?v=a%22onload%3D"), None);
+        assert_eq!(embed("https://www.youtube.com/feed/subscriptions"), None);
+        assert_eq!(embed("https://www.twitch.tv/someone"), None);
+    }
+
+    #[test]
+    fn timestamps() {
+        assert_eq!(parse_timestamp("90"), Some(90));
+        assert_eq!(parse_timestamp("90s"), Some(90));
+        assert_eq!(parse_timestamp("1h2m3s"), Some(3723));
+        assert_eq!(parse_timestamp("1h2"), None);
+        assert_eq!(parse_timestamp("soon"), None);
+        assert_eq!(twitch_timestamp(3723), "1h2m3s");
+    }
+}
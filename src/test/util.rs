@@ -4,7 +4,7 @@ use chrono::NaiveDateTime;
 
 use crate::{
     id::Id,
-    model::{Category, Game, User},
+    model::{Category, Game, TimingMethod, TimingMethods, User},
 };
 
 pub static TEST_DB_NUM: AtomicUsize = AtomicUsize::new(0);
@@ -31,6 +31,13 @@ pub(crate) fn test_category() -> Category {
         description: "test category".to_string(),
         rules: "(test)".to_string(),
         scoreboard: false,
+        per_level: false,
+        timing_methods: TimingMethods::RTA,
+        primary_timing: TimingMethod::Rta,
+        players: 1,
+        lower_score_wins: false,
+        score_precision: 0,
+        score_unit: String::new(),
         flags: 0,
     }
 }
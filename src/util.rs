@@ -1,16 +1,20 @@
-use std::fmt::Debug;
+use std::{collections::HashMap, fmt::Debug};
 
 use argon2::{password_hash::SaltString, Argon2, PasswordHasher};
 use axum::{
+    body::{Body, Bytes},
     extract::{FromRequest, Request, State},
     http::{
-        header::{CACHE_CONTROL, CONTENT_SECURITY_POLICY},
+        header::{CACHE_CONTROL, CONTENT_SECURITY_POLICY, CONTENT_TYPE},
         HeaderValue,
     },
     middleware::Next,
     response::Response,
 };
-use axum_extra::extract::cookie::{Cookie, SameSite};
+use axum_extra::extract::{
+    cookie::{Cookie, SameSite},
+    Multipart,
+};
 use rand::rngs::OsRng;
 use s3::creds::time::Duration as S3Duration;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
@@ -43,10 +47,36 @@ pub const MAX_CATEGORY_DESCRIPTION_LEN: usize = 4000;
 pub const MIN_CATEGORY_DESCRIPTION_LEN: usize = 0;
 pub const MAX_CATEGORY_RULES_LEN: usize = 20_000;
 pub const MIN_CATEGORY_RULES_LEN: usize = 0;
+pub const MAX_LEVEL_NAME_LEN: usize = 128;
+pub const MIN_LEVEL_NAME_LEN: usize = 1;
+pub const MAX_PLATFORM_NAME_LEN: usize = 128;
+pub const MIN_PLATFORM_NAME_LEN: usize = 1;
+pub const MAX_VARIABLE_NAME_LEN: usize = 128;
+pub const MIN_VARIABLE_NAME_LEN: usize = 1;
+pub const MAX_CATEGORY_PLAYERS: usize = 8;
+pub const MAX_CATEGORY_PLAYERS_I16: i16 = 8;
+pub const MAX_RANKING_POINTS: i64 = 1_000_000;
+pub const MAX_RUN_HOURS: i64 = 100_000;
+pub const MAX_SCORE_PRECISION: i16 = 6;
+pub const MAX_SCORE_UNIT_LEN: usize = 32;
+pub const MAX_SCORE_INPUT_LEN: usize = 32;
+pub const MAX_GUEST_NAME_LEN: usize = 64;
 pub const MAX_RUN_VIDEO_LEN: usize = 256;
 pub const MIN_RUN_VIDEO_LEN: usize = 12;
 pub const MAX_RUN_DESCRIPTION_LEN: usize = 4000;
 pub const MIN_RUN_DESCRIPTION_LEN: usize = 0;
+pub const MAX_CANNED_REASON_LEN: usize = 512;
+pub const MIN_CANNED_REASON_LEN: usize = 1;
+pub const MAX_REVIEW_NOTE_LEN: usize = 4000;
+pub const MIN_REVIEW_NOTE_LEN: usize = 0;
+pub const MAX_BULK_REVIEW_RUNS: usize = 100;
+pub const MAX_SPLITS_LEN: usize = 1024 * 1024 * 5;
+pub const MAX_SPLITS_SEGMENTS: usize = 1024;
+pub const MAX_SPLITS_COMPARISONS: usize = 64;
+pub const MAX_SEGMENT_NAME_LEN: usize = 256;
+pub const MAX_COMPARISON_NAME_LEN: usize = 128;
+pub const MAX_RUN_COMMENT_LEN: usize = 4000;
+pub const MIN_RUN_COMMENT_LEN: usize = 1;
 pub const MAX_FORUM_TITLE_LEN: usize = 128;
 pub const MIN_FORUM_TITLE_LEN: usize = 5;
 pub const MAX_FORUM_POST_LEN: usize = 4000;
@@ -91,8 +121,8 @@ pub fn return_0_i64() -> i64 {
     0
 }
 
-pub fn return_0_usize() -> usize {
-    0
+pub fn return_1_i16() -> i16 {
+    1
 }
 
 #[allow(clippy::trivially_copy_pass_by_ref)]
@@ -130,13 +160,60 @@ where
     type Rejection = crate::Error;
 
     async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
-        let form: T = axum::Form::from_request(req, state).await?.0;
+        let form: T = axum_extra::extract::Form::from_request(req, state).await?.0;
         trace!(data = ?form, "deserialized form-data, validating");
         form.validate()?;
         Ok(Self(form))
     }
 }
 
+/// A `multipart/form-data` form, for forms with file inputs. The text fields are
+/// read and validated like a [`ValidatedForm`], and the files are kept by field name.
+pub struct ValidatedMultipartForm<T> {
+    pub form: T,
+    pub files: HashMap<String, Bytes>,
+}
+
+#[axum::async_trait]
+impl<S, T> FromRequest<S> for ValidatedMultipartForm<T>
+where
+    T: serde::de::DeserializeOwned + garde::Validate<Context = ()> + Debug,
+    S: Send + Sync,
+{
+    type Rejection = crate::Error;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let mut multipart = Multipart::from_request(req, state).await?;
+        let mut fields = Vec::new();
+        let mut files = HashMap::new();
+        while let Some(field) = multipart.next_field().await? {
+            let Some(name) = field.name().map(ToOwned::to_owned) else {
+                continue;
+            };
+            if field.file_name().is_some() {
+                let bytes = field.bytes().await?;
+                // browsers send file inputs that were left empty as empty files
+                if !bytes.is_empty() {
+                    files.insert(name, bytes);
+                }
+            } else {
+                fields.push((name, field.text().await?));
+            }
+        }
+        let encoded = url::form_urlencoded::Serializer::new(String::new())
+            .extend_pairs(fields)
+            .finish();
+        let mut form_req = Request::new(Body::from(encoded));
+        form_req.headers_mut().insert(
+            CONTENT_TYPE,
+            HeaderValue::from_static("application/x-www-form-urlencoded"),
+        );
+        *form_req.method_mut() = axum::http::Method::POST;
+        let ValidatedForm(form) = ValidatedForm::from_request(form_req, state).await?;
+        Ok(Self { form, files })
+    }
+}
+
 pub async fn game_n_member(
     state: &AppState,
     user: User,
@@ -179,6 +256,7 @@ pub async fn game_n_member(
         cover_art: data.cover_art,
         flags: data.flags,
     };
+    let member = member.apply_two_factor_policy(state, &game).await?;
     Ok((game, member))
 }
 